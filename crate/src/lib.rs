@@ -2,7 +2,16 @@ use std::fs;
 use std::path::Path;
 
 use anyhow::{anyhow, Context, Result};
+use secp256k1::{Message, Secp256k1};
+use serde::Deserialize;
 use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+
+pub use secp256k1::{PublicKey, SecretKey};
+
+pub mod verify;
+
+use verify::{decode_hash, verify_signature};
 
 #[derive(Debug, Clone)]
 pub struct UploadRequest<'a> {
@@ -14,6 +23,14 @@ pub struct UploadRequest<'a> {
     pub toolchain: &'a str,
     pub commit: &'a str,
     pub zkvm: &'a str,
+    /// File holding the hex-encoded secp256k1 key the upload is signed with.
+    pub signing_key: Option<&'a Path>,
+    /// JSON build provenance document stored next to the binary.
+    pub provenance: Option<&'a Path>,
+    /// Files attached to the program, as `(name, path)` (e.g. `("vk", vk_path)`).
+    pub artifacts: &'a [(&'a str, &'a Path)],
+    /// OIDC token (e.g. from GitHub Actions) sent instead of `api_key`.
+    pub oidc_token: Option<&'a str>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,14 +52,123 @@ pub fn program_id_from_file(path: &Path) -> Result<String> {
     Ok(text.trim().to_string())
 }
 
+/// Reads a hex-encoded secp256k1 secret key from a file
+pub fn signing_key_from_file(path: &Path) -> Result<SecretKey> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read signing key file {}", path.display()))?;
+    let bytes = hex::decode(raw.trim()).context("Signing key file is not hex")?;
+    SecretKey::from_slice(&bytes).context("Invalid secp256k1 signing key")
+}
+
+/// Publisher signature of an upload, as sent to the registry
+#[derive(Debug, Clone)]
+pub struct UploadSignature {
+    /// Compressed public key, hex encoded
+    pub public_key: String,
+    /// Compact ECDSA signature, hex encoded
+    pub signature: String,
+}
+
+/// Signs the SHA-256 of `hyli-registry-upload:v1\n<contract>\n<program_id>\n<hex sha256 of the binary>`,
+/// the message the registry checks against the contract's publisher keys
+pub fn sign_upload(
+    key: &SecretKey,
+    contract: &str,
+    program_id: &str,
+    binary: &[u8],
+) -> UploadSignature {
+    let message = format!(
+        "hyli-registry-upload:v1\n{contract}\n{program_id}\n{}",
+        hex::encode(Sha256::digest(binary))
+    );
+    let digest: [u8; 32] = Sha256::digest(message.as_bytes()).into();
+    let secp = Secp256k1::signing_only();
+    let signature = secp.sign_ecdsa(&Message::from_digest(digest), key);
+    UploadSignature {
+        public_key: hex::encode(PublicKey::from_secret_key(&secp, key).serialize()),
+        signature: hex::encode(signature.serialize_compact()),
+    }
+}
+
+/// Reads a JSON build provenance document from a file
+///
+/// Expected fields: `source_repo`, `source_ref`, and optionally `builder_image_digest`
+/// (`sha256:<hex>`), `rustc_version`, `cargo_prove_version`, `cargo_lock_sha256` and
+/// `build_command`. The registry rejects documents that do not match.
+pub fn provenance_from_file(path: &Path) -> Result<JsonValue> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read provenance file {}", path.display()))?;
+    serde_json::from_str(&raw).context("Provenance file is not valid JSON")
+}
+
+/// How a request to the registry is authenticated
+#[derive(Debug, Clone, Copy)]
+enum Credentials<'a> {
+    ApiKey(&'a str),
+    /// OIDC token, sent as `Authorization: Bearer`
+    OidcToken(&'a str),
+}
+
+impl Credentials<'_> {
+    fn apply(self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            Credentials::ApiKey(api_key) => request.header("x-api-key", api_key),
+            Credentials::OidcToken(token) => request.bearer_auth(token),
+        }
+    }
+}
+
+/// Requests an OIDC token for `audience` from the GitHub Actions runner
+/// Needs the `id-token: write` permission in the workflow, which sets
+/// ACTIONS_ID_TOKEN_REQUEST_URL and ACTIONS_ID_TOKEN_REQUEST_TOKEN
+pub async fn github_actions_oidc_token(audience: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        value: String,
+    }
+
+    let url = std::env::var("ACTIONS_ID_TOKEN_REQUEST_URL").context(
+        "ACTIONS_ID_TOKEN_REQUEST_URL not set, is the workflow allowed `id-token: write`?",
+    )?;
+    let request_token = std::env::var("ACTIONS_ID_TOKEN_REQUEST_TOKEN")
+        .context("ACTIONS_ID_TOKEN_REQUEST_TOKEN not set")?;
+    tracing::debug!(audience = %audience, "Requesting GitHub Actions OIDC token");
+    let response = reqwest::Client::new()
+        .get(&url)
+        .query(&[("audience", audience)])
+        .bearer_auth(request_token)
+        .send()
+        .await
+        .context("Failed to request GitHub Actions OIDC token")?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("OIDC token request failed: {status} {body}"));
+    }
+    let token: TokenResponse = response
+        .json()
+        .await
+        .context("Invalid GitHub Actions OIDC token response")?;
+    Ok(token.value)
+}
+
+/// Optional documents sent along with a binary
+struct UploadAttachments<'a> {
+    signing_key: Option<&'a SecretKey>,
+    provenance: Option<JsonValue>,
+    /// Artifact names and contents
+    artifacts: Vec<(String, Vec<u8>)>,
+}
+
 /// Core upload function that sends binary bytes to the registry
 async fn upload_bytes(
     server_url: &str,
-    api_key: &str,
+    credentials: Credentials<'_>,
     contract: &str,
     program_id: &str,
     binary_bytes: Vec<u8>,
     metadata: JsonValue,
+    attachments: UploadAttachments<'_>,
 ) -> Result<UploadResponse> {
     let binary_size = binary_bytes.len();
     tracing::info!(
@@ -53,7 +179,10 @@ async fn upload_bytes(
         "Starting upload to registry"
     );
 
-    let form = reqwest::multipart::Form::new()
+    let signature = attachments
+        .signing_key
+        .map(|key| sign_upload(key, contract, program_id, &binary_bytes));
+    let mut form = reqwest::multipart::Form::new()
         .text("program_id", program_id.to_string())
         .text("metadata", metadata.to_string())
         .part(
@@ -62,14 +191,31 @@ async fn upload_bytes(
                 .file_name("program.bin")
                 .mime_str("application/octet-stream")?,
         );
+    if let Some(signature) = signature {
+        tracing::debug!(public_key = %signature.public_key, "Signing upload");
+        form = form
+            .text("public_key", signature.public_key)
+            .text("signature", signature.signature);
+    }
+    if let Some(provenance) = attachments.provenance {
+        form = form.text("provenance", provenance.to_string());
+    }
+    for (name, bytes) in attachments.artifacts {
+        tracing::debug!(artifact = %name, size = %bytes.len(), "Attaching artifact");
+        form = form.part(
+            format!("artifact:{name}"),
+            reqwest::multipart::Part::bytes(bytes)
+                .file_name(name)
+                .mime_str("application/octet-stream")?,
+        );
+    }
 
     let url = format!("{}/api/elfs/{}", server_url.trim_end_matches('/'), contract);
     tracing::debug!(url = %url, "Sending POST request");
 
     let client = reqwest::Client::new();
-    let response = client
-        .post(url)
-        .header("x-api-key", api_key)
+    let response = credentials
+        .apply(client.post(url))
         .multipart(form)
         .send()
         .await
@@ -101,6 +247,10 @@ async fn upload_bytes(
 
 /// Upload an ELF binary with minimal metadata (zkvm only)
 /// Reads server URL from HYLI_REGISTRY_URL env var and API key from HYLI_REGISTRY_API_KEY
+/// Authenticates with the OIDC token in HYLI_REGISTRY_OIDC_TOKEN instead when set, or with a
+/// GitHub Actions token for the audience in HYLI_REGISTRY_OIDC_AUDIENCE
+/// Signs the upload with the key file at HYLI_REGISTRY_SIGNING_KEY when set
+/// Attaches the provenance file at HYLI_REGISTRY_PROVENANCE when set
 /// Additional metadata fields can be provided via the `additional_metadata` parameter
 pub async fn upload_elf(
     elf_bytes: &[u8],
@@ -112,8 +262,32 @@ pub async fn upload_elf(
     tracing::debug!("Reading registry configuration from environment variables");
     let server_url = std::env::var("HYLI_REGISTRY_URL")
         .context("HYLI_REGISTRY_URL environment variable not set")?;
-    let api_key = std::env::var("HYLI_REGISTRY_API_KEY")
-        .context("HYLI_REGISTRY_API_KEY environment variable not set")?;
+    let oidc_token = match (
+        std::env::var("HYLI_REGISTRY_OIDC_TOKEN"),
+        std::env::var("HYLI_REGISTRY_OIDC_AUDIENCE"),
+    ) {
+        (Ok(token), _) => Some(token),
+        (Err(_), Ok(audience)) => Some(github_actions_oidc_token(&audience).await?),
+        _ => None,
+    };
+    let api_key = match oidc_token {
+        Some(_) => String::new(),
+        None => std::env::var("HYLI_REGISTRY_API_KEY")
+            .context("HYLI_REGISTRY_API_KEY environment variable not set")?,
+    };
+    let credentials = match &oidc_token {
+        Some(token) => Credentials::OidcToken(token),
+        None => Credentials::ApiKey(&api_key),
+    };
+
+    let signing_key = std::env::var("HYLI_REGISTRY_SIGNING_KEY")
+        .ok()
+        .map(|path| signing_key_from_file(Path::new(&path)))
+        .transpose()?;
+    let provenance = std::env::var("HYLI_REGISTRY_PROVENANCE")
+        .ok()
+        .map(|path| provenance_from_file(Path::new(&path)))
+        .transpose()?;
 
     tracing::debug!(server_url = %server_url, "Using registry URL from environment");
 
@@ -134,11 +308,16 @@ pub async fn upload_elf(
 
     upload_bytes(
         &server_url,
-        &api_key,
+        credentials,
         contract,
         program_id,
         elf_bytes.to_vec(),
         metadata,
+        UploadAttachments {
+            signing_key: signing_key.as_ref(),
+            provenance,
+            artifacts: Vec::new(),
+        },
     )
     .await
 }
@@ -151,32 +330,297 @@ pub async fn upload(request: UploadRequest<'_>) -> Result<UploadResponse> {
         )
     })?;
 
+    let signing_key = request.signing_key.map(signing_key_from_file).transpose()?;
+    let provenance = request.provenance.map(provenance_from_file).transpose()?;
+    let artifacts = request
+        .artifacts
+        .iter()
+        .map(|(name, path)| {
+            let bytes = fs::read(path)
+                .with_context(|| format!("Failed to read artifact file {}", path.display()))?;
+            Ok((name.to_string(), bytes))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
     let metadata = serde_json::json!({
         "toolchain": request.toolchain,
         "commit": request.commit,
         "zkvm": request.zkvm,
     });
 
+    let credentials = match request.oidc_token {
+        Some(token) => Credentials::OidcToken(token),
+        None => Credentials::ApiKey(request.api_key),
+    };
     upload_bytes(
         request.server_url,
-        request.api_key,
+        credentials,
         request.contract,
         request.program_id,
         binary_bytes,
         metadata,
+        UploadAttachments {
+            signing_key: signing_key.as_ref(),
+            provenance,
+            artifacts,
+        },
     )
     .await
 }
 
-/// Download an ELF binary from the registry
-/// Reads server URL from HYLI_REGISTRY_URL env var and API key from HYLI_REGISTRY_API_KEY
-pub async fn download_elf(contract: &str, program_id: &str) -> Result<Vec<u8>> {
-    tracing::debug!("Reading registry configuration from environment variables");
-    let server_url = std::env::var("HYLI_REGISTRY_URL")
-        .context("HYLI_REGISTRY_URL environment variable not set")?;
-    let api_key = std::env::var("HYLI_REGISTRY_API_KEY")
-        .context("HYLI_REGISTRY_API_KEY environment variable not set")?;
+/// Signed manifest of a contract, as served by `/api/manifests/:contract`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignedManifest {
+    /// JSON manifest, exactly as signed
+    pub payload: String,
+    /// Key the registry claims to sign with; verification uses a key known beforehand instead
+    pub public_key: String,
+    /// Compact ECDSA signature, hex encoded
+    pub signature: String,
+}
+
+/// Programs of a contract with the digest of their ELF
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContractManifest {
+    pub contract: String,
+    pub generated_at: String,
+    pub programs: Vec<ManifestProgram>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestProgram {
+    pub program_id: String,
+    /// Hex SHA-256 of the ELF
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub uploaded_at: String,
+}
+
+/// Parses a hex-encoded compressed secp256k1 public key
+pub fn public_key_from_hex(key: &str) -> Result<PublicKey> {
+    let bytes = hex::decode(key.trim()).context("Public key is not hex")?;
+    PublicKey::from_slice(&bytes).context("Invalid secp256k1 public key")
+}
+
+impl SignedManifest {
+    /// Checks the signature of SHA-256(`hyli-registry-manifest:v1\n<payload>`) against
+    /// the registry key and returns the manifest of `contract`
+    pub fn verify(&self, contract: &str, registry_key: &PublicKey) -> Result<ContractManifest> {
+        verify_signature(
+            verify::manifest_digest(&self.payload),
+            &self.signature,
+            registry_key,
+        )
+        .with_context(|| format!("Manifest of {contract} is not signed by the registry key"))?;
+        let manifest: ContractManifest =
+            serde_json::from_str(&self.payload).context("Failed to parse manifest")?;
+        if manifest.contract != contract {
+            return Err(anyhow!(
+                "Manifest is for contract {}, not {contract}",
+                manifest.contract
+            ));
+        }
+        Ok(manifest)
+    }
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    url: &str,
+    api_key: Option<&str>,
+    what: &str,
+) -> Result<T> {
+    tracing::debug!(url = %url, "Fetching {what}");
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(api_key) = api_key {
+        request = request.header("x-api-key", api_key);
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to send {what} request"))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Request for {what} failed: {status} {body}"));
+    }
+    response
+        .json()
+        .await
+        .with_context(|| format!("Failed to parse {what}"))
+}
+
+/// Fetch the manifest of a contract and verify it with the registry public key
+pub async fn fetch_manifest(
+    server_url: &str,
+    contract: &str,
+    registry_key: &PublicKey,
+) -> Result<ContractManifest> {
+    fetch_manifest_with_key(server_url, None, contract, registry_key).await
+}
+
+/// Private contracts need an API key allowed to read them
+async fn fetch_manifest_with_key(
+    server_url: &str,
+    api_key: Option<&str>,
+    contract: &str,
+    registry_key: &PublicKey,
+) -> Result<ContractManifest> {
+    let url = format!(
+        "{}/api/manifests/{}",
+        server_url.trim_end_matches('/'),
+        contract
+    );
+    let signed: SignedManifest = get_json(&url, api_key, "signed manifest").await?;
+    signed.verify(contract, registry_key)
+}
+
+/// Signed head of the registry transparency log, as served by `/api/transparency/head`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TreeHead {
+    pub tree_size: u64,
+    /// Hex Merkle root of the first `tree_size` leaves
+    pub root_hash: String,
+    pub timestamp: String,
+    pub public_key: Option<String>,
+    /// Compact ECDSA signature, hex encoded
+    pub signature: Option<String>,
+}
 
+impl TreeHead {
+    /// Checks the signature of
+    /// SHA-256(`hyli-registry-tree-head:v1\n<tree_size>\n<root_hash>\n<timestamp>`)
+    /// against the registry key
+    pub fn verify(&self, registry_key: &PublicKey) -> Result<()> {
+        let signature = self
+            .signature
+            .as_deref()
+            .ok_or_else(|| anyhow!("Transparency log head is not signed"))?;
+        let digest = verify::tree_head_digest(self.tree_size, &self.root_hash, &self.timestamp);
+        verify_signature(digest, signature, registry_key)
+            .context("Transparency log head is not signed by the registry key")
+    }
+}
+
+/// Upload recorded in the transparency log
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogLeaf {
+    pub contract: String,
+    pub program_id: String,
+    /// Hex SHA-256 of the uploaded ELF
+    pub sha256: String,
+    pub uploaded_at: String,
+}
+
+impl LogLeaf {
+    /// Merkle leaf hash:
+    /// SHA-256(`0x00` || `hyli-registry-leaf:v1\n<contract>\n<program_id>\n<sha256>\n<uploaded_at>`)
+    pub fn hash(&self) -> [u8; 32] {
+        verify::leaf_hash(
+            &self.contract,
+            &self.program_id,
+            &self.sha256,
+            &self.uploaded_at,
+        )
+    }
+}
+
+/// Audit path of a leaf, as served by `/api/transparency/proof/:contract/:program_id`
+#[derive(Debug, Clone, Deserialize)]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub leaf: LogLeaf,
+    /// Hex hashes, from the leaf up
+    pub audit_path: Vec<String>,
+}
+
+impl InclusionProof {
+    /// Checks that the leaf is part of the tree of the given head (RFC 9162, section 2.1.3.2)
+    pub fn verify(&self, head: &TreeHead) -> Result<()> {
+        if self.tree_size != head.tree_size {
+            return Err(anyhow!(
+                "Proof is for a tree of size {}, not {}",
+                self.tree_size,
+                head.tree_size
+            ));
+        }
+        if self.leaf_index >= self.tree_size {
+            return Err(anyhow!("Leaf index is outside of the tree"));
+        }
+        let audit_path = self
+            .audit_path
+            .iter()
+            .map(|hash| decode_hash(hash))
+            .collect::<Result<Vec<_>>>()?;
+        if !verify::verify_inclusion(
+            &self.leaf.hash(),
+            self.leaf_index,
+            self.tree_size,
+            &audit_path,
+            &decode_hash(&head.root_hash)?,
+        ) {
+            return Err(anyhow!(
+                "{}/{} is not included in the transparency log",
+                self.leaf.contract,
+                self.leaf.program_id
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Fetch the head of the transparency log and verify it with the registry public key
+pub async fn fetch_tree_head(server_url: &str, registry_key: &PublicKey) -> Result<TreeHead> {
+    let url = format!("{}/api/transparency/head", server_url.trim_end_matches('/'));
+    let head: TreeHead = get_json(&url, None, "transparency log head").await?;
+    head.verify(registry_key)?;
+    Ok(head)
+}
+
+/// Fetch the proof that the latest upload of a program is in the tree of size `tree_size`.
+/// Private contracts need an API key allowed to read them
+pub async fn fetch_inclusion_proof(
+    server_url: &str,
+    api_key: Option<&str>,
+    contract: &str,
+    program_id: &str,
+    tree_size: u64,
+) -> Result<InclusionProof> {
+    let url = format!(
+        "{}/api/transparency/proof/{}/{}?tree_size={}",
+        server_url.trim_end_matches('/'),
+        contract,
+        program_id,
+        tree_size
+    );
+    get_json(&url, api_key, "inclusion proof").await
+}
+
+/// Check that the latest upload of a program is in the transparency log signed by the registry,
+/// returning the logged upload
+pub async fn verify_inclusion(
+    server_url: &str,
+    api_key: Option<&str>,
+    contract: &str,
+    program_id: &str,
+    registry_key: &PublicKey,
+) -> Result<LogLeaf> {
+    let head = fetch_tree_head(server_url, registry_key).await?;
+    let proof =
+        fetch_inclusion_proof(server_url, api_key, contract, program_id, head.tree_size).await?;
+    if proof.leaf.contract != contract || proof.leaf.program_id != program_id {
+        return Err(anyhow!("Inclusion proof is for another program"));
+    }
+    proof.verify(&head)?;
+    Ok(proof.leaf)
+}
+
+async fn fetch_elf(
+    server_url: &str,
+    api_key: &str,
+    contract: &str,
+    program_id: &str,
+) -> Result<Vec<u8>> {
     tracing::info!(
         program_id = %program_id,
         contract = %contract,
@@ -194,7 +638,7 @@ pub async fn download_elf(contract: &str, program_id: &str) -> Result<Vec<u8>> {
     let client = reqwest::Client::new();
     let response = client
         .get(&url)
-        .header("x-api-key", &api_key)
+        .header("x-api-key", api_key)
         .send()
         .await
         .context("Failed to send download request")?;
@@ -219,3 +663,147 @@ pub async fn download_elf(contract: &str, program_id: &str) -> Result<Vec<u8>> {
 
     Ok(bytes.to_vec())
 }
+
+/// Attach an artifact to a program already in the registry, replacing any artifact of the same name
+pub async fn upload_artifact(
+    server_url: &str,
+    api_key: &str,
+    contract: &str,
+    program_id: &str,
+    name: &str,
+    path: &Path,
+) -> Result<()> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("Failed to read artifact file {}", path.display()))?;
+    let url = format!(
+        "{}/api/elfs/{}/{}/artifacts/{}",
+        server_url.trim_end_matches('/'),
+        contract,
+        program_id,
+        name
+    );
+    tracing::debug!(url = %url, size = %bytes.len(), "Uploading artifact");
+
+    let response = reqwest::Client::new()
+        .put(&url)
+        .header("x-api-key", api_key)
+        .header("content-type", "application/octet-stream")
+        .body(bytes)
+        .send()
+        .await
+        .context("Failed to send artifact upload request")?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Artifact upload failed: {status} {body}"));
+    }
+    Ok(())
+}
+
+/// Download an artifact attached to a program (e.g. its `vk`)
+pub async fn download_artifact(
+    server_url: &str,
+    contract: &str,
+    program_id: &str,
+    name: &str,
+) -> Result<Vec<u8>> {
+    let url = format!(
+        "{}/api/elfs/{}/{}/artifacts/{}",
+        server_url.trim_end_matches('/'),
+        contract,
+        program_id,
+        name
+    );
+    tracing::debug!(url = %url, "Downloading artifact");
+
+    let response = reqwest::Client::new()
+        .get(&url)
+        .send()
+        .await
+        .context("Failed to send artifact download request")?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Artifact download failed: {status} {body}"));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .context("Failed to read response body")?;
+    Ok(bytes.to_vec())
+}
+
+/// Download an ELF binary through a pre-signed URL issued by the registry, without an API key
+pub async fn download_elf_presigned(url: &str) -> Result<Vec<u8>> {
+    tracing::info!("Downloading ELF from pre-signed URL");
+
+    let response = reqwest::Client::new()
+        .get(url)
+        .send()
+        .await
+        .context("Failed to send download request")?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Download failed: {status} {body}"));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .context("Failed to read response body")?;
+    Ok(bytes.to_vec())
+}
+
+/// Download an ELF binary and check it against the contract manifest signed by the registry
+pub async fn download_elf_verified(
+    server_url: &str,
+    api_key: &str,
+    contract: &str,
+    program_id: &str,
+    registry_key: &PublicKey,
+) -> Result<Vec<u8>> {
+    let manifest =
+        fetch_manifest_with_key(server_url, Some(api_key), contract, registry_key).await?;
+    let expected = manifest
+        .programs
+        .iter()
+        .find(|program| program.program_id == program_id)
+        .ok_or_else(|| {
+            anyhow!("Program {program_id} is not in the signed manifest of {contract}")
+        })?;
+
+    let bytes = fetch_elf(server_url, api_key, contract, program_id).await?;
+    let digest = hex::encode(Sha256::digest(&bytes));
+    if bytes.len() as u64 != expected.size_bytes || digest != expected.sha256 {
+        tracing::error!(
+            program_id = %program_id,
+            contract = %contract,
+            expected = %expected.sha256,
+            actual = %digest,
+            "Downloaded ELF does not match the signed manifest"
+        );
+        return Err(anyhow!(
+            "ELF of {contract}/{program_id} does not match the signed manifest"
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Download an ELF binary from the registry
+/// Reads server URL from HYLI_REGISTRY_URL env var and API key from HYLI_REGISTRY_API_KEY
+/// When HYLI_REGISTRY_PUBLIC_KEY is set, the ELF is checked against the signed manifest
+pub async fn download_elf(contract: &str, program_id: &str) -> Result<Vec<u8>> {
+    tracing::debug!("Reading registry configuration from environment variables");
+    let server_url = std::env::var("HYLI_REGISTRY_URL")
+        .context("HYLI_REGISTRY_URL environment variable not set")?;
+    let api_key = std::env::var("HYLI_REGISTRY_API_KEY")
+        .context("HYLI_REGISTRY_API_KEY environment variable not set")?;
+
+    match std::env::var("HYLI_REGISTRY_PUBLIC_KEY") {
+        Ok(key) => {
+            let registry_key = public_key_from_hex(&key)?;
+            download_elf_verified(&server_url, &api_key, contract, program_id, &registry_key).await
+        }
+        Err(_) => fetch_elf(&server_url, &api_key, contract, program_id).await,
+    }
+}
@@ -1,8 +1,8 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use hyli_registry::{program_id_hex_from_file, upload, UploadRequest};
+use hyli_registry::{github_actions_oidc_token, program_id_hex_from_file, upload, UploadRequest};
 
 #[derive(Debug, Parser)]
 #[command(author, version, about = "Upload ZKVM binaries to the Hyli registry")]
@@ -13,7 +13,7 @@ struct Args {
 
 #[derive(Debug, Subcommand)]
 enum Command {
-    /// Upload an SP1 ELF using a verification key (vk)
+    /// Upload an SP1 ELF using a verification key (vk), attached as the `vk` artifact
     Sp1(Sp1Args),
     /// Upload a RISC0 image with a provided program_id
     Risc0(Risc0Args),
@@ -40,6 +40,49 @@ struct CommonArgs {
     /// Commit identifier
     #[arg(long)]
     commit: String,
+    /// File holding the hex secp256k1 key to sign the upload with
+    #[arg(long, env = "HYLI_REGISTRY_SIGNING_KEY")]
+    signing_key: Option<PathBuf>,
+    /// JSON build provenance document to attach to the upload
+    #[arg(long, env = "HYLI_REGISTRY_PROVENANCE")]
+    provenance: Option<PathBuf>,
+    /// File to attach to the program, as name=path (repeatable)
+    #[arg(long = "artifact", value_parser = parse_artifact)]
+    artifacts: Vec<(String, PathBuf)>,
+    /// OIDC token sent instead of the API key
+    #[arg(long, env = "HYLI_REGISTRY_OIDC_TOKEN")]
+    oidc_token: Option<String>,
+    /// Request an OIDC token for this audience from GitHub Actions and send it instead of the API key
+    #[arg(
+        long,
+        env = "HYLI_REGISTRY_OIDC_AUDIENCE",
+        conflicts_with = "oidc_token"
+    )]
+    oidc_audience: Option<String>,
+}
+
+fn parse_artifact(raw: &str) -> Result<(String, PathBuf), String> {
+    let (name, path) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=path, got {raw}"))?;
+    Ok((name.to_string(), PathBuf::from(path)))
+}
+
+impl CommonArgs {
+    async fn oidc_token(&self) -> Result<Option<String>> {
+        match (&self.oidc_token, &self.oidc_audience) {
+            (Some(token), _) => Ok(Some(token.clone())),
+            (None, Some(audience)) => github_actions_oidc_token(audience).await.map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn artifacts(&self) -> Vec<(&str, &Path)> {
+        self.artifacts
+            .iter()
+            .map(|(name, path)| (name.as_str(), path.as_path()))
+            .collect()
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -79,6 +122,11 @@ async fn main() -> Result<()> {
     let response = match args.command {
         Command::Sp1(args) => {
             let program_id = program_id_hex_from_file(&args.vk)?;
+            let oidc_token = args.common.oidc_token().await?;
+            let mut artifacts = args.common.artifacts();
+            if !artifacts.iter().any(|(name, _)| *name == "vk") {
+                artifacts.push(("vk", &args.vk));
+            }
             upload(UploadRequest {
                 server_url: &args.common.server_url,
                 api_key: &args.common.api_key,
@@ -88,10 +136,16 @@ async fn main() -> Result<()> {
                 toolchain: &args.common.toolchain,
                 commit: &args.common.commit,
                 zkvm: &args.zkvm,
+                signing_key: args.common.signing_key.as_deref(),
+                provenance: args.common.provenance.as_deref(),
+                artifacts: &artifacts,
+                oidc_token: oidc_token.as_deref(),
             })
             .await?
         }
         Command::Risc0(args) => {
+            let artifacts = args.common.artifacts();
+            let oidc_token = args.common.oidc_token().await?;
             upload(UploadRequest {
                 server_url: &args.common.server_url,
                 api_key: &args.common.api_key,
@@ -101,6 +155,10 @@ async fn main() -> Result<()> {
                 toolchain: &args.common.toolchain,
                 commit: &args.common.commit,
                 zkvm: &args.zkvm,
+                signing_key: args.common.signing_key.as_deref(),
+                provenance: args.common.provenance.as_deref(),
+                artifacts: &artifacts,
+                oidc_token: oidc_token.as_deref(),
             })
             .await?
         }
@@ -0,0 +1,268 @@
+//! Hashes and checks of what the registry signs, shared with the registry server
+//! so that both sides compute them the same way
+
+use anyhow::{anyhow, Context, Result};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1};
+use sha2::{Digest, Sha256};
+
+/// Domain separators of the signed encodings, bumped if their layout changes
+const LEAF_MESSAGE_PREFIX: &str = "hyli-registry-leaf:v1";
+const TREE_HEAD_MESSAGE_PREFIX: &str = "hyli-registry-tree-head:v1";
+const MANIFEST_MESSAGE_PREFIX: &str = "hyli-registry-manifest:v1";
+
+pub type Hash = [u8; 32];
+
+/// Merkle leaf hash of an upload:
+/// SHA-256(`0x00` || `hyli-registry-leaf:v1\n<contract>\n<program_id>\n<sha256>\n<uploaded_at>`)
+pub fn leaf_hash(contract: &str, program_id: &str, sha256: &str, uploaded_at: &str) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(
+        format!("{LEAF_MESSAGE_PREFIX}\n{contract}\n{program_id}\n{sha256}\n{uploaded_at}")
+            .as_bytes(),
+    );
+    hasher.finalize().into()
+}
+
+/// Merkle node hash: SHA-256(`0x01` || left || right)
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Digest of a transparency log head:
+/// SHA-256(`hyli-registry-tree-head:v1\n<tree_size>\n<root_hash>\n<timestamp>`)
+pub fn tree_head_digest(tree_size: u64, root_hash: &str, timestamp: &str) -> Hash {
+    Sha256::digest(
+        format!("{TREE_HEAD_MESSAGE_PREFIX}\n{tree_size}\n{root_hash}\n{timestamp}").as_bytes(),
+    )
+    .into()
+}
+
+/// Digest of a contract manifest: SHA-256(`hyli-registry-manifest:v1\n<payload>`)
+pub fn manifest_digest(payload: &str) -> Hash {
+    Sha256::digest(format!("{MANIFEST_MESSAGE_PREFIX}\n{payload}").as_bytes()).into()
+}
+
+/// Checks a hex compact ECDSA signature of `digest` against `key`
+pub fn verify_signature(digest: Hash, signature: &str, key: &PublicKey) -> Result<()> {
+    let signature = hex::decode(signature.trim())
+        .context("Signature is not hex")
+        .and_then(|bytes| Signature::from_compact(&bytes).context("Malformed signature"))?;
+    Secp256k1::verification_only()
+        .verify_ecdsa(&Message::from_digest(digest), &signature, key)
+        .map_err(|_| anyhow!("Invalid signature"))
+}
+
+/// Decodes a hex hash
+pub fn decode_hash(hash: &str) -> Result<Hash> {
+    hex::decode(hash.trim())
+        .context("Hash is not hex")?
+        .try_into()
+        .map_err(|_| anyhow!("Hash is not 32 bytes long"))
+}
+
+/// Checks an audit path against a root (RFC 9162, section 2.1.3.2)
+pub fn verify_inclusion(
+    leaf_hash: &Hash,
+    leaf_index: u64,
+    tree_size: u64,
+    audit_path: &[Hash],
+    root: &Hash,
+) -> bool {
+    if leaf_index >= tree_size {
+        return false;
+    }
+    let (mut fn_, mut sn) = (leaf_index, tree_size - 1);
+    let mut hash = *leaf_hash;
+    for sibling in audit_path {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            hash = node_hash(sibling, &hash);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && hash == *root
+}
+
+/// Checks that `second_root` extends `first_root` (RFC 9162, section 2.1.4.2)
+pub fn verify_consistency(
+    first: u64,
+    second: u64,
+    proof: &[Hash],
+    first_root: &Hash,
+    second_root: &Hash,
+) -> bool {
+    if first > second {
+        return false;
+    }
+    if first == second {
+        return proof.is_empty() && first_root == second_root;
+    }
+    if first == 0 {
+        return proof.is_empty();
+    }
+    let mut path = proof.to_vec();
+    if first.is_power_of_two() {
+        path.insert(0, *first_root);
+    }
+    let Some((start, rest)) = path.split_first() else {
+        return false;
+    };
+    let (mut fn_, mut sn) = (first - 1, second - 1);
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    let (mut first_hash, mut second_hash) = (*start, *start);
+    for hash in rest {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            first_hash = node_hash(hash, &first_hash);
+            second_hash = node_hash(hash, &second_hash);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            second_hash = node_hash(&second_hash, hash);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && first_hash == *first_root && second_hash == *second_root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::SecretKey;
+
+    fn hash(hex_hash: &str) -> Hash {
+        decode_hash(hex_hash).expect("hash")
+    }
+
+    /// Leaves of `orders`, `p0` to `p2`, and the hashes of the tree they form
+    fn tree() -> ([Hash; 3], Hash, Hash) {
+        let leaves = [0, 1, 2].map(|n| {
+            leaf_hash(
+                "orders",
+                &format!("p{n}"),
+                &hex::encode(Sha256::digest(n.to_string())),
+                "2024-01-01T00:00:00Z",
+            )
+        });
+        let left = node_hash(&leaves[0], &leaves[1]);
+        (leaves, left, node_hash(&left, &leaves[2]))
+    }
+
+    /// Computed independently of this module
+    #[test]
+    fn encodings_match_known_vectors() {
+        assert_eq!(
+            leaf_hash("orders", "p1", "00", "2024-01-01T00:00:00Z"),
+            hash("4e2f64df13460f8c3787607eaa488b14f31f36d4e39e55ad8f02dc7e5af5821d")
+        );
+        assert_eq!(
+            tree_head_digest(3, "ab", "2024-01-01T00:00:00Z"),
+            hash("db667302002167ff2e55ee20289f1d9487b9cfaced14a7dbe36be48a6bcffca1")
+        );
+        assert_eq!(
+            manifest_digest("{}"),
+            hash("351a9cbfb7643ffecb8f7f13d63e3ab375e4a0b644aa012c19172a373d43c63c")
+        );
+    }
+
+    #[test]
+    fn checks_inclusion_proofs() {
+        let (leaves, left, root) = tree();
+        assert!(verify_inclusion(
+            &leaves[0],
+            0,
+            3,
+            &[leaves[1], leaves[2]],
+            &root
+        ));
+        assert!(verify_inclusion(
+            &leaves[1],
+            1,
+            3,
+            &[leaves[0], leaves[2]],
+            &root
+        ));
+        assert!(verify_inclusion(&leaves[2], 2, 3, &[left], &root));
+
+        // Another leaf, index, tree size, path or root.
+        assert!(!verify_inclusion(
+            &leaves[1],
+            0,
+            3,
+            &[leaves[1], leaves[2]],
+            &root
+        ));
+        assert!(!verify_inclusion(
+            &leaves[0],
+            1,
+            3,
+            &[leaves[1], leaves[2]],
+            &root
+        ));
+        assert!(!verify_inclusion(&leaves[2], 2, 4, &[left], &root));
+        assert!(!verify_inclusion(&leaves[2], 3, 3, &[left], &root));
+        assert!(!verify_inclusion(&leaves[2], 2, 3, &[left, left], &root));
+        assert!(!verify_inclusion(&leaves[2], 2, 3, &[leaves[0]], &root));
+        assert!(!verify_inclusion(&leaves[2], 2, 3, &[left], &left));
+    }
+
+    #[test]
+    fn checks_consistency_proofs() {
+        let (leaves, left, root) = tree();
+        assert!(verify_consistency(2, 3, &[leaves[2]], &left, &root));
+        assert!(verify_consistency(
+            1,
+            3,
+            &[leaves[1], leaves[2]],
+            &leaves[0],
+            &root
+        ));
+        assert!(verify_consistency(3, 3, &[], &root, &root));
+
+        assert!(!verify_consistency(2, 3, &[leaves[1]], &left, &root));
+        assert!(!verify_consistency(2, 3, &[leaves[2]], &leaves[0], &root));
+        assert!(!verify_consistency(3, 2, &[leaves[2]], &root, &left));
+    }
+
+    #[test]
+    fn checks_signatures_of_digests() {
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[7; 32]).expect("key");
+        let public_key = PublicKey::from_secret_key(&secp, &key);
+        let digest = manifest_digest(r#"{"contract":"orders"}"#);
+        let signature = hex::encode(
+            secp.sign_ecdsa(&Message::from_digest(digest), &key)
+                .serialize_compact(),
+        );
+        assert!(verify_signature(digest, &signature, &public_key).is_ok());
+
+        let tampered = manifest_digest(r#"{"contract":"wallet"}"#);
+        assert!(verify_signature(tampered, &signature, &public_key).is_err());
+        let other =
+            PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[8; 32]).expect("key"));
+        assert!(verify_signature(digest, &signature, &other).is_err());
+        assert!(verify_signature(digest, "not hex", &public_key).is_err());
+    }
+}
@@ -82,6 +82,13 @@ const formatDate = (value: string | null) => {
 };
 
 const ADMIN_KEY_STORAGE = "hyli_registry_admin_key";
+// Server-sent events that change the listing; "resync" means some were missed.
+const REGISTRY_EVENTS = [
+  "program_uploaded",
+  "program_deleted",
+  "contract_deleted",
+  "resync",
+];
 
 const loadAdminKey = () => {
   if (typeof window === "undefined") {
@@ -121,8 +128,15 @@ function App() {
 
   useEffect(() => {
     fetchRegistry();
-    const interval = setInterval(fetchRegistry, 30000);
-    return () => clearInterval(interval);
+    if (typeof EventSource === "undefined") {
+      const interval = setInterval(fetchRegistry, 30000);
+      return () => clearInterval(interval);
+    }
+    const events = new EventSource(buildUrl("/api/events"));
+    for (const type of REGISTRY_EVENTS) {
+      events.addEventListener(type, () => fetchRegistry());
+    }
+    return () => events.close();
   }, []);
 
   useEffect(() => {
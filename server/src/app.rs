@@ -1,29 +1,68 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
 use std::sync::Arc;
 
 use anyhow::Result;
 use axum::{
-    extract::{Json, Multipart, Path, State},
+    body::Body,
+    extract::{Json, Multipart, Path, Query, State},
     http::{HeaderMap, Method, StatusCode},
-    response::{IntoResponse, Response},
-    routing::get,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Redirect, Response,
+    },
+    routing::{delete, get, post, put},
     Router,
 };
+use bytes::Bytes;
 use client_sdk::AppError;
+use futures_util::{Stream, StreamExt};
 
 use hyli_modules::{
-    bus::SharedMessageBus,
+    bus::{command_response::Query as BusQuery, BusClientSender, SharedMessageBus},
     log_error, module_bus_client, module_handle_messages,
     modules::{BuildApiContextInner, Module},
 };
 use sdk::ContractName;
+use tokio::io::AsyncWriteExt;
 use tower_http::cors::{Any, CorsLayer};
 
+use crate::artifacts::{
+    served_content_type, validate_artifact_name, ArtifactEntry, ArtifactUpload, PutArtifact,
+    MAX_ARTIFACTS_PER_PROGRAM,
+};
+use crate::auth::{
+    Actor, ApiKeyInfo, ApiKeys, AuthError, CreateApiKey, CreatedApiKey, Credential, NewApiKey,
+    Operation,
+};
+use crate::backup::{
+    apply_import, export_archive, export_contract_archive, plan_import, spawn_archive_stream,
+    Compression, ImportMode, ImportReport,
+};
+use crate::builder::RegistryBuilder;
 use crate::conf::Conf;
-use crate::registry::{ProgramInfo, ProgramMetadata, RegistryService};
+use crate::events::{GetProgramElf, ListContractPrograms, LoggedEvent, RegistryEvent};
+use crate::manifest::{contract_manifest, ManifestSigner, SignedManifest};
+use crate::oidc::OidcVerifier;
+use crate::ownership::{validate_identity, ContractOwnership, UploadClaim};
+use crate::presign::{DownloadUrlSigner, PresignError};
+use crate::provenance::{Provenance, ProvenanceSummary};
+use crate::query::{glob_match, PageQuery, ProgramQuery, SearchResults};
+use crate::registry::{
+    is_reserved_contract, ContractSummary, ProgramInfo, ProgramMetadata, RegistryService,
+    UploadAttachments, VisibleContracts, WarmupOptions, WarmupStrategy,
+};
+use crate::signing::{PublisherKeys, SignatureError, UploadSignature};
+use crate::stats::RegistryStats;
+use crate::transparency::{
+    ConsistencyProof, InclusionProof, ListedLeaf, SignedTreeHead, MAX_LEAVES_PER_PAGE,
+};
+use crate::webhooks::{RetryPolicy, WebhookDispatcher, WebhookStatus};
 
 pub struct AppModule {
     bus: AppModuleBusClient,
+    registry: Arc<RegistryService>,
+    events: tokio::sync::broadcast::Receiver<LoggedEvent>,
 }
 
 pub struct AppModuleCtx {
@@ -34,6 +73,9 @@ pub struct AppModuleCtx {
 module_bus_client! {
 #[derive(Debug)]
 pub struct AppModuleBusClient {
+    sender(RegistryEvent),
+    receiver(BusQuery<GetProgramElf, Option<Bytes>>),
+    receiver(BusQuery<ListContractPrograms, Option<Vec<ProgramInfo>>>),
 }
 }
 
@@ -41,57 +83,169 @@ impl Module for AppModule {
     type Context = Arc<AppModuleCtx>;
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
-        let registry = RegistryService::new(&ctx.config).await?;
-        let state = RouterCtx {
-            registry: Arc::new(registry),
-            api_key: ctx.config.api_key.clone(),
-            admin_key: ctx.config.admin_key.clone(),
-        };
-
-        // Créer un middleware CORS
-        let cors = CorsLayer::new()
-            .allow_origin(Any) // Permet toutes les origines (peut être restreint)
-            .allow_methods(vec![Method::GET, Method::POST, Method::DELETE]) // Permet les méthodes nécessaires
-            .allow_headers(Any); // Permet tous les en-têtes
-
-        let api = Router::new()
-            .route("/_health", get(health))
-            .route("/api/elfs", get(list_elfs))
-            .route(
-                "/api/elfs/{contract}",
-                get(list_contract).post(upload_elf).delete(delete_contract),
-            )
-            .route(
-                "/api/elfs/{contract}/{program_id}",
-                get(download_elf).delete(delete_program),
-            )
-            .with_state(state)
-            .layer(cors);
+        let app = RegistryBuilder::new((*ctx.config).clone()).build().await?;
+        let registry = app.registry().clone();
+        let events = registry.subscribe();
 
         if let Ok(mut guard) = ctx.api.router.lock() {
             if let Some(router) = guard.take() {
-                guard.replace(router.merge(api));
+                guard.replace(app.mount(router));
             }
         }
         let bus = AppModuleBusClient::new_from_bus(bus.new_handle()).await;
 
-        Ok(AppModule { bus })
+        Ok(AppModule {
+            bus,
+            registry,
+            events,
+        })
     }
 
     async fn run(&mut self) -> Result<()> {
         module_handle_messages! {
             on_self self,
+            command_response<GetProgramElf, Option<Bytes>> request => {
+                self.registry.download(&request.contract, &request.program_id).await
+            }
+            command_response<ListContractPrograms, Option<Vec<ProgramInfo>>> request => {
+                Ok(self.registry.list_contract(&request.contract).await)
+            }
+            event = self.events.recv() => {
+                match event {
+                    Ok(logged) => {
+                        let _ = log_error!(
+                            self.bus.send(logged.event),
+                            "Publishing registry event on the bus"
+                        );
+                    }
+                    Err(err) => tracing::warn!("Registry events not published on the bus: {err}"),
+                }
+            }
         };
 
         Ok(())
     }
 }
 
+/// Shared state of the HTTP routes.
 #[derive(Clone)]
-struct RouterCtx {
+pub(crate) struct RouterCtx {
     registry: Arc<RegistryService>,
-    api_key: String,
-    admin_key: String,
+    webhooks: Option<Arc<WebhookDispatcher>>,
+    publishers: Arc<PublisherKeys>,
+    manifests: Option<Arc<ManifestSigner>>,
+    download_urls: Option<Arc<DownloadUrlSigner>>,
+    api_keys: Arc<ApiKeys>,
+    oidc: Option<Arc<OidcVerifier>>,
+    warmup: WarmupOptions,
+}
+
+impl RouterCtx {
+    /// Starts the configured webhooks and reads the route settings from the config.
+    pub(crate) async fn new(registry: Arc<RegistryService>, config: &Conf) -> Result<Self> {
+        let webhooks = if config.webhooks.is_empty() {
+            None
+        } else {
+            Some(
+                WebhookDispatcher::start(
+                    config.webhooks.clone(),
+                    RetryPolicy::from_conf(config),
+                    registry.storage(),
+                    registry.subscribe(),
+                )
+                .await?,
+            )
+        };
+        let warmup_max_bytes = match config.cache_warmup_max_bytes {
+            0 => config.binary_cache_max_bytes,
+            max_bytes => max_bytes,
+        };
+        let api_keys = Arc::new(ApiKeys::load(config, registry.storage()).await?);
+        Ok(Self {
+            registry,
+            webhooks,
+            publishers: Arc::new(PublisherKeys::from_conf(config)?),
+            manifests: ManifestSigner::from_conf(config)?.map(Arc::new),
+            download_urls: DownloadUrlSigner::from_conf(config)?.map(Arc::new),
+            api_keys,
+            oidc: OidcVerifier::from_conf(config).await?.map(Arc::new),
+            warmup: WarmupOptions {
+                strategy: WarmupStrategy::Recent,
+                max_bytes: warmup_max_bytes,
+                programs_per_contract: config.cache_warmup_programs_per_contract,
+            },
+        })
+    }
+}
+
+/// Registry routes, with CORS, ready to be merged into another router.
+pub(crate) fn api_router(state: RouterCtx) -> Router {
+    // Créer un middleware CORS
+    let cors = CorsLayer::new()
+        .allow_origin(Any) // Permet toutes les origines (peut être restreint)
+        .allow_methods(vec![Method::GET, Method::POST, Method::PUT, Method::DELETE]) // Permet les méthodes nécessaires
+        .allow_headers(Any); // Permet tous les en-têtes
+
+    Router::new()
+        .route("/_health", get(health))
+        .route("/api/elfs", get(list_elfs))
+        .route("/api/contracts", get(list_contracts))
+        .route("/api/stats", get(registry_stats))
+        .route("/api/search", get(search_programs))
+        .route("/api/events", get(stream_events))
+        .route(
+            "/api/elfs/{contract}",
+            get(list_contract).post(upload_elf).delete(delete_contract),
+        )
+        .route(
+            "/api/elfs/{contract}/{program_id}",
+            get(download_elf).delete(delete_program),
+        )
+        .route(
+            "/api/elfs/{contract}/{program_id}/download-url",
+            post(create_download_url),
+        )
+        .route(
+            "/api/elfs/{contract}/{program_id}/provenance",
+            get(program_provenance),
+        )
+        .route(
+            "/api/elfs/{contract}/{program_id}/artifacts/{name}",
+            get(download_artifact)
+                .put(upload_artifact)
+                .delete(delete_artifact),
+        )
+        .route(
+            "/api/contracts/{contract}/owner",
+            get(contract_owner)
+                .put(transfer_contract)
+                .delete(release_contract),
+        )
+        .route(
+            "/api/contracts/{contract}/visibility",
+            put(set_contract_visibility),
+        )
+        .route(
+            "/api/contracts/{contract}/collaborators/{identity}",
+            put(add_collaborator).delete(remove_collaborator),
+        )
+        .route("/api/keys", get(list_api_keys).post(create_api_key))
+        .route("/api/keys/{id}", delete(revoke_api_key))
+        .route("/api/keys/{id}/expire", post(expire_api_key))
+        .route("/api/cache/warmup", post(warmup_cache))
+        .route("/api/webhooks/deliveries", get(webhook_deliveries))
+        .route("/api/backup", get(export_backup).post(import_backup))
+        .route("/api/archive/{contract}", get(download_contract_archive))
+        .route("/api/manifests/{contract}", get(signed_manifest))
+        .route("/api/transparency/head", get(transparency_head))
+        .route("/api/transparency/leaves", get(transparency_leaves))
+        .route(
+            "/api/transparency/proof/{contract}/{program_id}",
+            get(inclusion_proof),
+        )
+        .route("/api/transparency/consistency", get(consistency_proof))
+        .with_state(state)
+        .layer(cors)
 }
 
 async fn health() -> impl IntoResponse {
@@ -103,21 +257,200 @@ async fn health() -> impl IntoResponse {
 // --------------------------------------------------------
 
 const API_KEY_HEADER: &str = "x-api-key";
+/// Upload form fields named `artifact:<name>` attach artifacts to the program.
+const ARTIFACT_FIELD_PREFIX: &str = "artifact:";
 
-fn require_api_key(headers: &HeaderMap, expected: &str) -> Result<(), AppError> {
-    let key = headers
-        .get(API_KEY_HEADER)
-        .and_then(|v| v.to_str().ok())
-        .ok_or_else(|| AppError(StatusCode::UNAUTHORIZED, anyhow::anyhow!("Missing API key")))?;
-    if key != expected {
+fn require_writable(state: &RouterCtx) -> Result<(), AppError> {
+    if state.registry.read_only() {
         return Err(AppError(
+            StatusCode::FORBIDDEN,
+            anyhow::anyhow!("Registry is a read-only mirror"),
+        ));
+    }
+    Ok(())
+}
+
+/// Verifies the request's API key, or OIDC token sent as
+/// `Authorization: Bearer`.
+async fn authenticate(state: &RouterCtx, headers: &HeaderMap) -> Result<Credential, AppError> {
+    let key = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok());
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let authenticated = match (key, token, &state.oidc) {
+        (None, Some(_), None) => {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("OIDC tokens are not accepted by this registry"),
+            ))
+        }
+        (None, Some(token), Some(oidc)) => oidc.authenticate(token.trim()).await,
+        _ => state.api_keys.authenticate(key).await,
+    };
+    authenticated.map_err(auth_error)
+}
+
+fn auth_error(err: AuthError) -> AppError {
+    match err {
+        AuthError::Missing => {
+            AppError(StatusCode::UNAUTHORIZED, anyhow::anyhow!("Missing API key"))
+        }
+        AuthError::Invalid => {
+            AppError(StatusCode::UNAUTHORIZED, anyhow::anyhow!("Invalid API key"))
+        }
+        AuthError::Expired => {
+            AppError(StatusCode::UNAUTHORIZED, anyhow::anyhow!("Expired API key"))
+        }
+        AuthError::InvalidToken(reason) => AppError(
             StatusCode::UNAUTHORIZED,
-            anyhow::anyhow!("Invalid API key"),
+            anyhow::anyhow!("Invalid OIDC token: {reason}"),
+        ),
+        AuthError::Forbidden { key } => AppError(
+            StatusCode::FORBIDDEN,
+            anyhow::anyhow!("{key} is not allowed"),
+        ),
+    }
+}
+
+/// Checks that the request's credentials may perform `operation` on
+/// `contract` (on the whole registry when `None`) and returns who did it.
+async fn authorize(
+    state: &RouterCtx,
+    headers: &HeaderMap,
+    operation: Operation,
+    contract: Option<&str>,
+) -> Result<Actor, AppError> {
+    authenticate(state, headers)
+        .await?
+        .authorize(operation, contract)
+        .map_err(|err| match err {
+            AuthError::Forbidden { key } => AppError(
+                StatusCode::FORBIDDEN,
+                match contract {
+                    Some(contract) => anyhow::anyhow!(
+                        "{key} is not allowed to {} on {contract}",
+                        operation.as_str()
+                    ),
+                    None => anyhow::anyhow!("{key} is not allowed to {}", operation.as_str()),
+                },
+            ),
+            err => auth_error(err),
+        })
+}
+
+/// Private contracts can only be read with a key or token allowed to, public
+/// contracts by anyone. Other callers get the `not_found` answer of an
+/// unknown contract, so that they cannot tell private contracts exist.
+async fn authorize_read(
+    state: &RouterCtx,
+    headers: &HeaderMap,
+    contract: &str,
+    not_found: &str,
+) -> Result<(), AppError> {
+    if state.registry.is_private(contract).await
+        && authorize(state, headers, Operation::Read, Some(contract))
+            .await
+            .is_err()
+    {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            anyhow::anyhow!("{not_found}"),
         ));
     }
     Ok(())
 }
 
+/// Contracts listings show to the request: the public ones, and the private
+/// ones its credentials may read. Invalid credentials only see public
+/// contracts.
+async fn visible_contracts(state: &RouterCtx, headers: &HeaderMap) -> VisibleContracts {
+    let private = state.registry.private_contracts().await;
+    if private.is_empty() {
+        return VisibleContracts::default();
+    }
+    let Ok(credential) = authenticate(state, headers).await else {
+        return VisibleContracts::default();
+    };
+    VisibleContracts::public_and(
+        private
+            .into_iter()
+            .filter(|contract| credential.allows(Operation::Read, Some(contract)))
+            .collect(),
+    )
+}
+
+/// Checks that `actor` owns `contract` or collaborates on it, claiming the
+/// contract for it when nobody owns it yet. Returns whether it was claimed,
+/// so that a failed upload can give it back.
+async fn claim_for_upload(
+    state: &RouterCtx,
+    contract: &str,
+    actor: &Actor,
+) -> Result<bool, AppError> {
+    let claim = log_error!(
+        state.registry.claim_for_upload(contract, &actor.name).await,
+        "Claiming contract"
+    )
+    .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    match claim {
+        UploadClaim::Allowed => Ok(false),
+        UploadClaim::Claimed => Ok(true),
+        UploadClaim::Denied { owner } => Err(not_allowed(contract, actor, &owner)),
+    }
+}
+
+/// Gives back a contract claimed for an upload that failed.
+async fn release_failed_claim(state: &RouterCtx, contract: &str, identity: &str) {
+    if let Err(err) = state.registry.release_claim(contract, identity).await {
+        tracing::warn!("Releasing {contract} after a failed upload: {err:#}");
+    }
+}
+
+/// Lets the owner of `contract`, its collaborators, or an admin delete from it.
+async fn authorize_delete(
+    state: &RouterCtx,
+    headers: &HeaderMap,
+    contract: &str,
+) -> Result<Actor, AppError> {
+    let actor = authorize(state, headers, Operation::Delete, Some(contract)).await?;
+    match state.registry.check_owner(contract, &actor.name).await {
+        UploadClaim::Allowed | UploadClaim::Claimed => Ok(actor),
+        UploadClaim::Denied { owner } => authorize(state, headers, Operation::Admin, None)
+            .await
+            .map_err(|_| not_allowed(contract, &actor, &owner)),
+    }
+}
+
+fn not_allowed(contract: &str, actor: &Actor, owner: &str) -> AppError {
+    AppError(
+        StatusCode::FORBIDDEN,
+        anyhow::anyhow!(
+            "Contract {contract} is owned by {owner}, {} is not a collaborator",
+            actor.name
+        ),
+    )
+}
+
+/// Lets the owner of `contract`, or an admin, manage its collaborators.
+async fn authorize_owner(
+    state: &RouterCtx,
+    headers: &HeaderMap,
+    contract: &str,
+) -> Result<Actor, AppError> {
+    if let Ok(actor) = authorize(state, headers, Operation::Upload, Some(contract)).await {
+        let owned = state
+            .registry
+            .ownership(contract)
+            .await
+            .is_some_and(|ownership| ownership.owner == actor.name);
+        if owned {
+            return Ok(actor);
+        }
+    }
+    authorize(state, headers, Operation::Admin, None).await
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct UploadMetadataPayload {
     toolchain: Option<String>,
@@ -132,6 +465,14 @@ struct UploadResponse {
     size_bytes: u64,
     uploaded_at: String,
     metadata: ProgramMetadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provenance: Option<ProvenanceSummary>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    artifacts: BTreeMap<String, ArtifactEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uploaded_by: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    oidc_claims: BTreeMap<String, String>,
 }
 
 #[tracing::instrument(skip(state, headers, multipart))]
@@ -141,12 +482,23 @@ async fn upload_elf(
     headers: HeaderMap,
     mut multipart: Multipart,
 ) -> Result<Json<UploadResponse>, AppError> {
-    require_api_key(&headers, &state.api_key)?;
+    let actor = authorize(&state, &headers, Operation::Upload, Some(&contract.0)).await?;
+    require_writable(&state)?;
     contract.validate().map_err(bad_request)?;
+    if is_reserved_contract(&contract.0) {
+        return Err(bad_request(format!(
+            "Contract name {} is reserved",
+            contract.0
+        )));
+    }
 
     let mut program_id = None;
     let mut metadata = None;
     let mut file_bytes = None;
+    let mut signature = None;
+    let mut public_key = None;
+    let mut provenance = None;
+    let mut artifacts: Vec<ArtifactUpload> = Vec::new();
 
     while let Some(field) = multipart.next_field().await? {
         let name = field.name().unwrap_or_default().to_string();
@@ -172,7 +524,38 @@ async fn upload_elf(
                 let bytes = field.bytes().await?;
                 file_bytes = Some(bytes);
             }
-            _ => {}
+            "signature" => {
+                signature = Some(field.text().await?);
+            }
+            "public_key" => {
+                public_key = Some(field.text().await?);
+            }
+            "provenance" => {
+                let raw = field.text().await?;
+                provenance = Some(Provenance::parse(&raw).map_err(bad_request)?);
+            }
+            _ => {
+                let Some(artifact_name) = name.strip_prefix(ARTIFACT_FIELD_PREFIX) else {
+                    continue;
+                };
+                if artifacts
+                    .iter()
+                    .any(|artifact| artifact.name == artifact_name)
+                {
+                    return Err(bad_request(format!("Artifact {artifact_name} sent twice")));
+                }
+                if artifacts.len() == MAX_ARTIFACTS_PER_PROGRAM {
+                    return Err(bad_request(format!(
+                        "At most {MAX_ARTIFACTS_PER_PROGRAM} artifacts per program"
+                    )));
+                }
+                let content_type = field.content_type().map(str::to_string);
+                let bytes = field.bytes().await?;
+                artifacts.push(
+                    ArtifactUpload::new(artifact_name, content_type.as_deref(), bytes)
+                        .map_err(bad_request)?,
+                );
+            }
         }
     }
 
@@ -187,14 +570,60 @@ async fn upload_elf(
     let file_bytes = file_bytes
         .ok_or_else(|| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!("Missing ELF file")))?;
 
-    let entry = log_error!(
+    let signature = match (signature, public_key) {
+        (Some(signature), Some(public_key)) => Some(UploadSignature {
+            public_key,
+            signature,
+        }),
+        (None, None) => None,
+        _ => {
+            return Err(bad_request(
+                "signature and public_key must be sent together".to_string(),
+            ))
+        }
+    };
+    state
+        .publishers
+        .verify(&contract.0, &program_id, &file_bytes, signature.as_ref())
+        .map_err(|err| match err {
+            SignatureError::Missing => AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Uploads to {} must be signed", contract.0),
+            ),
+            SignatureError::Invalid(err) => AppError(
+                StatusCode::UNAUTHORIZED,
+                err.context("Invalid upload signature"),
+            ),
+            SignatureError::UnknownPublisher(key) => AppError(
+                StatusCode::FORBIDDEN,
+                anyhow::anyhow!("{key} is not a publisher of {}", contract.0),
+            ),
+        })?;
+    let claimed = claim_for_upload(&state, &contract.0, &actor).await?;
+
+    let uploaded = log_error!(
         state
             .registry
-            .upload(&contract.0, &program_id, metadata, file_bytes)
+            .upload_with(
+                &contract.0,
+                &program_id,
+                metadata,
+                UploadAttachments {
+                    signature,
+                    provenance,
+                    artifacts,
+                    uploaded_by: Some(actor.name.clone()),
+                    oidc_claims: actor.claims,
+                },
+                file_bytes,
+            )
             .await,
         "Uploading ELF"
-    )
-    .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    );
+    if uploaded.is_err() && claimed {
+        release_failed_claim(&state, &contract.0, &actor.name).await;
+    }
+    let entry = uploaded.map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
 
     Ok(Json(UploadResponse {
         program_id: entry.program_id,
@@ -202,31 +631,329 @@ async fn upload_elf(
         size_bytes: entry.size_bytes,
         uploaded_at: entry.uploaded_at,
         metadata: entry.metadata,
+        provenance: entry.provenance,
+        artifacts: entry.artifacts,
+        uploaded_by: entry.uploaded_by,
+        oidc_claims: entry.oidc_claims,
     }))
 }
 
-#[tracing::instrument(skip(state))]
+/// Attaches an artifact to a stored program; the body is the artifact and
+/// its `Content-Type` is kept for downloads.
+#[tracing::instrument(skip(state, headers, body))]
+async fn upload_artifact(
+    State(state): State<RouterCtx>,
+    Path((contract, program_id, name)): Path<(ContractName, String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ArtifactEntry>, AppError> {
+    let actor = authorize(&state, &headers, Operation::Upload, Some(&contract.0)).await?;
+    require_writable(&state)?;
+    contract.validate().map_err(bad_request)?;
+    // Publisher signatures cover uploads, not artifacts attached afterwards.
+    if state.publishers.requires_signature(&contract.0) {
+        return Err(AppError(
+            StatusCode::FORBIDDEN,
+            anyhow::anyhow!(
+                "Artifacts of {} can only be sent with a signed upload",
+                contract.0
+            ),
+        ));
+    }
+    let content_type = headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let artifact = ArtifactUpload::new(&name, content_type, body).map_err(bad_request)?;
+    let claimed = claim_for_upload(&state, &contract.0, &actor).await?;
+
+    let stored = log_error!(
+        state
+            .registry
+            .put_artifact(&contract.0, &program_id, artifact)
+            .await,
+        "Uploading artifact"
+    );
+    if claimed && !matches!(stored, Ok(PutArtifact::Stored(_))) {
+        release_failed_claim(&state, &contract.0, &actor.name).await;
+    }
+    let stored = stored.map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    match stored {
+        PutArtifact::Stored(artifact) => Ok(Json(artifact)),
+        PutArtifact::ProgramNotFound => Err(AppError(
+            StatusCode::NOT_FOUND,
+            anyhow::anyhow!("Program not found"),
+        )),
+        PutArtifact::TooMany => Err(bad_request(format!(
+            "At most {MAX_ARTIFACTS_PER_PROGRAM} artifacts per program"
+        ))),
+    }
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn download_artifact(
+    State(state): State<RouterCtx>,
+    Path((contract, program_id, name)): Path<(ContractName, String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    contract.validate().map_err(bad_request)?;
+    authorize_read(&state, &headers, &contract.0, "Artifact not found").await?;
+    validate_artifact_name(&name).map_err(bad_request)?;
+    let (artifact, bytes) = state
+        .registry
+        .artifact(&contract.0, &program_id, &name)
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow::anyhow!("Artifact not found")))?;
+    // Served from the registry origin: never let browsers render uploads.
+    Ok((
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                served_content_type(&artifact.content_type).to_string(),
+            ),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{name}\""),
+            ),
+            (
+                axum::http::header::X_CONTENT_TYPE_OPTIONS,
+                "nosniff".to_string(),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn delete_artifact(
+    State(state): State<RouterCtx>,
+    Path((contract, program_id, name)): Path<(ContractName, String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize_delete(&state, &headers, &contract.0).await?;
+    require_writable(&state)?;
+    contract.validate().map_err(bad_request)?;
+    validate_artifact_name(&name).map_err(bad_request)?;
+
+    let deleted = state
+        .registry
+        .delete_artifact(&contract.0, &program_id, &name)
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Ok(StatusCode::NOT_FOUND.into_response())
+    }
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn program_provenance(
+    State(state): State<RouterCtx>,
+    Path((contract, program_id)): Path<(ContractName, String)>,
+    headers: HeaderMap,
+) -> Result<Json<Provenance>, AppError> {
+    contract.validate().map_err(bad_request)?;
+    authorize_read(&state, &headers, &contract.0, "No provenance").await?;
+    state
+        .registry
+        .provenance(&contract.0, &program_id)
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+        .map(Json)
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow::anyhow!("No provenance")))
+}
+
+#[tracing::instrument(skip(state, headers))]
 async fn list_elfs(
     State(state): State<RouterCtx>,
-) -> Result<Json<HashMap<String, Vec<ProgramInfo>>>, AppError> {
-    let contracts = state.registry.list_all().await;
-    Ok(Json(contracts))
+    Query(page): Query<PageQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let visible = visible_contracts(&state, &headers).await;
+    if !page.is_paginated() {
+        return Ok(Json(state.registry.list_all(&visible).await).into_response());
+    }
+    let cursor = page.cursor().map_err(bad_request)?;
+    let programs = state
+        .registry
+        .list_all_page(cursor.as_ref(), page.limit(), &visible)
+        .await;
+    Ok(Json(programs).into_response())
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn registry_stats(
+    State(state): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<Json<RegistryStats>, AppError> {
+    let visible = visible_contracts(&state, &headers).await;
+    Ok(Json(state.registry.stats(&visible).await))
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn list_contracts(
+    State(state): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ContractSummary>>, AppError> {
+    let visible = visible_contracts(&state, &headers).await;
+    Ok(Json(state.registry.contract_summaries(&visible).await))
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn search_programs(
+    State(state): State<RouterCtx>,
+    Query(query): Query<ProgramQuery>,
+    headers: HeaderMap,
+) -> Result<Json<SearchResults>, AppError> {
+    let visible = visible_contracts(&state, &headers).await;
+    Ok(Json(state.registry.search(&query, &visible).await))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct EventStreamQuery {
+    /// Contract name pattern, `*` matches anything.
+    contract: Option<String>,
+    /// Fallback for clients that cannot set the `Last-Event-ID` header.
+    last_event_id: Option<u64>,
+}
+
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// How often event streams check that their key or token is still valid.
+#[cfg(not(test))]
+const CREDENTIAL_RECHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+#[cfg(test)]
+const CREDENTIAL_RECHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+#[tracing::instrument(skip(state, headers))]
+async fn stream_events(
+    State(state): State<RouterCtx>,
+    Query(query): Query<EventStreamQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let last_event_id = match headers.get(LAST_EVENT_ID_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| bad_request("Invalid Last-Event-ID".to_string()))?,
+        ),
+        None => query.last_event_id,
+    };
+    let (missed, gap, mut receiver) = match last_event_id {
+        Some(last_event_id) => {
+            let replay = state.registry.resume_events(last_event_id);
+            (replay.missed, replay.gap, replay.receiver)
+        }
+        None => (Vec::new(), false, state.registry.subscribe()),
+    };
+
+    let contract = query.contract;
+    let matches = move |logged: &LoggedEvent| {
+        contract
+            .as_deref()
+            .is_none_or(|pattern| glob_match(pattern, logged.event.contract()))
+    };
+    let mut credential = authenticate(&state, &headers).await.ok();
+    let registry = state.registry.clone();
+    let stream = async_stream::stream! {
+        if gap {
+            yield Ok(resync_event());
+        }
+        for logged in missed.iter().filter(|logged| matches(logged)) {
+            if event_visible(&registry, credential.as_ref(), logged).await {
+                yield Ok(sse_event(logged));
+            }
+        }
+        // Revoked keys and expired tokens close the stream: the client
+        // reconnects with whatever it is still allowed to see.
+        let mut recheck = tokio::time::interval_at(
+            tokio::time::Instant::now() + CREDENTIAL_RECHECK_INTERVAL,
+            CREDENTIAL_RECHECK_INTERVAL,
+        );
+        loop {
+            let received = tokio::select! {
+                received = receiver.recv() => received,
+                _ = recheck.tick(), if credential.is_some() => {
+                    match authenticate(&state, &headers).await {
+                        Ok(renewed) => {
+                            credential = Some(renewed);
+                            continue;
+                        }
+                        Err(_) => break,
+                    }
+                }
+            };
+            match received {
+                Ok(logged) => {
+                    if matches(&logged)
+                        && event_visible(&registry, credential.as_ref(), &logged).await
+                    {
+                        yield Ok(sse_event(&logged));
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    yield Ok(resync_event());
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Events of private contracts only reach subscribers allowed to read them.
+async fn event_visible(
+    registry: &RegistryService,
+    credential: Option<&Credential>,
+    logged: &LoggedEvent,
+) -> bool {
+    let contract = logged.event.contract();
+    !registry.is_private(contract).await
+        || credential.is_some_and(|credential| credential.allows(Operation::Read, Some(contract)))
+}
+
+fn sse_event(logged: &LoggedEvent) -> Event {
+    Event::default()
+        .id(logged.id.to_string())
+        .event(logged.event.kind())
+        .json_data(logged)
+        .unwrap_or_else(|_| Event::default().comment("unserializable event"))
 }
 
-#[tracing::instrument(skip(state))]
+/// Tells the client it missed events and should reload the listings.
+fn resync_event() -> Event {
+    Event::default().event("resync").data("{}")
+}
+
+#[tracing::instrument(skip(state, headers))]
 async fn list_contract(
     State(state): State<RouterCtx>,
     Path(contract): Path<ContractName>,
-) -> Result<Json<Vec<ProgramInfo>>, AppError> {
+    Query(page): Query<PageQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     contract.validate().map_err(bad_request)?;
+    authorize_read(&state, &headers, &contract.0, "Contract not found").await?;
 
-    match state.registry.list_contract(&contract.0).await {
-        Some(entries) => Ok(Json(entries)),
-        None => Err(AppError(
-            StatusCode::NOT_FOUND,
-            anyhow::anyhow!("Contract not found"),
-        )),
-    }
+    let response = if page.is_paginated() {
+        let cursor = page.cursor().map_err(bad_request)?;
+        state
+            .registry
+            .list_contract_page(&contract.0, cursor.as_ref(), page.limit())
+            .await
+            .map(|programs| Json(programs).into_response())
+    } else {
+        state
+            .registry
+            .list_contract(&contract.0)
+            .await
+            .map(|entries| Json(entries).into_response())
+    };
+    response.ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow::anyhow!("Contract not found")))
 }
 
 #[tracing::instrument(skip(state, headers))]
@@ -235,7 +962,8 @@ async fn delete_program(
     Path((contract, program_id)): Path<(ContractName, String)>,
     headers: HeaderMap,
 ) -> Result<Response, AppError> {
-    require_api_key(&headers, &state.admin_key)?;
+    authorize_delete(&state, &headers, &contract.0).await?;
+    require_writable(&state)?;
     contract.validate().map_err(bad_request)?;
 
     let deleted = state
@@ -256,7 +984,8 @@ async fn delete_contract(
     Path(contract): Path<ContractName>,
     headers: HeaderMap,
 ) -> Result<Response, AppError> {
-    require_api_key(&headers, &state.admin_key)?;
+    authorize_delete(&state, &headers, &contract.0).await?;
+    require_writable(&state)?;
     contract.validate().map_err(bad_request)?;
 
     let deleted = state
@@ -271,12 +1000,103 @@ async fn delete_contract(
     }
 }
 
-#[tracing::instrument(skip(state))]
+#[derive(Debug, Default, serde::Deserialize)]
+struct DownloadUrlRequest {
+    /// Validity of the URL, one hour by default.
+    expires_in_secs: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DownloadUrlResponse {
+    /// Path and query of the URL, relative to the registry.
+    path: String,
+    expires_at: String,
+}
+
+/// Issues a URL that downloads one ELF without credentials until it
+/// expires, for third parties which should not get a key.
+#[tracing::instrument(skip(state, headers, request))]
+async fn create_download_url(
+    State(state): State<RouterCtx>,
+    Path((contract, program_id)): Path<(ContractName, String)>,
+    headers: HeaderMap,
+    request: Option<Json<DownloadUrlRequest>>,
+) -> Result<Json<DownloadUrlResponse>, AppError> {
+    let Some(signer) = &state.download_urls else {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            anyhow::anyhow!("Pre-signed download URLs are not enabled"),
+        ));
+    };
+    let actor = authorize(&state, &headers, Operation::Read, Some(&contract.0)).await?;
+    contract.validate().map_err(bad_request)?;
+    if !state.registry.has_program(&contract.0, &program_id).await {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            anyhow::anyhow!("ELF not found"),
+        ));
+    }
+
+    let Json(request) = request.unwrap_or_default();
+    let expires = signer
+        .expiry(request.expires_in_secs, chrono::Utc::now().timestamp())
+        .map_err(bad_request)?;
+    let signature = signer.sign(&contract.0, &program_id, expires);
+    // Only used to percent-encode the path, the host is dropped.
+    let mut url = reqwest::Url::parse("http://registry/")
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err.into()))?;
+    url.path_segments_mut()
+        .map_err(|()| {
+            AppError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                anyhow::anyhow!("Download URL has no path"),
+            )
+        })?
+        .pop_if_empty()
+        .extend(["api", "elfs", &contract.0, &program_id]);
+    url.query_pairs_mut()
+        .append_pair("expires", &expires.to_string())
+        .append_pair("signature", &signature);
+    let expires_at = chrono::DateTime::from_timestamp(expires, 0)
+        .unwrap_or_default()
+        .to_rfc3339();
+    tracing::info!(
+        "Download URL for {}/{program_id} issued by {} until {expires_at}",
+        contract.0,
+        actor.name
+    );
+    Ok(Json(DownloadUrlResponse {
+        path: format!("{}?{}", url.path(), url.query().unwrap_or_default()),
+        expires_at,
+    }))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DownloadQuery {
+    /// Expiry of a pre-signed URL, in unix seconds.
+    expires: Option<i64>,
+    signature: Option<String>,
+}
+
+#[tracing::instrument(skip(state, headers, query))]
 async fn download_elf(
     State(state): State<RouterCtx>,
     Path((contract, program_id)): Path<(ContractName, String)>,
+    Query(query): Query<DownloadQuery>,
+    headers: HeaderMap,
 ) -> Result<Response, AppError> {
     contract.validate().map_err(bad_request)?;
+    match &query.signature {
+        Some(signature) => {
+            if let Some(redirect) =
+                check_download_url(&state, &contract.0, &program_id, query.expires, signature)
+                    .await?
+            {
+                return Ok(redirect);
+            }
+        }
+        None => authorize_read(&state, &headers, &contract.0, "ELF not found").await?,
+    }
 
     let bytes = match state.registry.download(&contract.0, &program_id).await {
         Ok(Some(bytes)) => bytes,
@@ -298,6 +1118,1193 @@ async fn download_elf(
     Ok(response)
 }
 
-fn bad_request(err: String) -> AppError {
-    AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(err))
+/// Checks a pre-signed URL, which stands in for credentials. Returns a
+/// redirect to the storage backend when `download_url_redirect` is set and
+/// the backend can sign URLs.
+async fn check_download_url(
+    state: &RouterCtx,
+    contract: &str,
+    program_id: &str,
+    expires: Option<i64>,
+    signature: &str,
+) -> Result<Option<Response>, AppError> {
+    let Some(signer) = &state.download_urls else {
+        return Err(AppError(
+            StatusCode::UNAUTHORIZED,
+            anyhow::anyhow!("Pre-signed download URLs are not enabled"),
+        ));
+    };
+    let expires = expires.ok_or_else(|| bad_request("Missing expires".to_string()))?;
+    let now = chrono::Utc::now().timestamp();
+    signer
+        .verify(contract, program_id, expires, signature, now)
+        .map_err(|err| match err {
+            PresignError::Invalid => AppError(
+                StatusCode::FORBIDDEN,
+                anyhow::anyhow!("Invalid download URL signature"),
+            ),
+            PresignError::Expired => AppError(
+                StatusCode::FORBIDDEN,
+                anyhow::anyhow!("Expired download URL"),
+            ),
+        })?;
+    if !signer.redirect() {
+        return Ok(None);
+    }
+
+    let remaining = std::time::Duration::from_secs((expires - now) as u64);
+    match state
+        .registry
+        .signed_download_url(contract, program_id, remaining)
+        .await
+    {
+        Ok(Some(url)) => Ok(Some(Redirect::temporary(&url).into_response())),
+        Ok(None) => Ok(None),
+        Err(err) => {
+            tracing::warn!("Signing storage URL of {contract}/{program_id}: {err:#}");
+            Ok(None)
+        }
+    }
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn signed_manifest(
+    State(state): State<RouterCtx>,
+    Path(contract): Path<ContractName>,
+    headers: HeaderMap,
+) -> Result<Json<SignedManifest>, AppError> {
+    contract.validate().map_err(bad_request)?;
+    authorize_read(&state, &headers, &contract.0, "Contract not found").await?;
+    let Some(signer) = &state.manifests else {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            anyhow::anyhow!("Signed manifests are not enabled"),
+        ));
+    };
+
+    let manifest = contract_manifest(&state.registry, &contract.0)
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow::anyhow!("Contract not found")))?;
+    Ok(Json(signer.sign(&manifest)?))
+}
+
+async fn transparency_head(State(state): State<RouterCtx>) -> Json<SignedTreeHead> {
+    let head = state.registry.transparency().tree_head().await;
+    Json(head.signed(state.manifests.as_deref()))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct LeavesQuery {
+    #[serde(default)]
+    start: u64,
+    limit: Option<usize>,
+}
+
+/// Leaves of private contracts the caller cannot read only show their hash.
+async fn transparency_leaves(
+    State(state): State<RouterCtx>,
+    Query(query): Query<LeavesQuery>,
+    headers: HeaderMap,
+) -> Json<Vec<ListedLeaf>> {
+    let leaves = state
+        .registry
+        .transparency()
+        .leaves(query.start, query.limit.unwrap_or(MAX_LEAVES_PER_PAGE))
+        .await;
+    let private = state.registry.private_contracts().await;
+    let visible = visible_contracts(&state, &headers).await;
+    Json(
+        leaves
+            .into_iter()
+            .map(|leaf| {
+                let shown =
+                    !private.contains(&leaf.contract) || visible.shows_private(&leaf.contract);
+                ListedLeaf::new(leaf, shown)
+            })
+            .collect(),
+    )
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct InclusionProofQuery {
+    /// Size of the tree to prove against, the current one when absent.
+    tree_size: Option<u64>,
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn inclusion_proof(
+    State(state): State<RouterCtx>,
+    Path((contract, program_id)): Path<(ContractName, String)>,
+    Query(query): Query<InclusionProofQuery>,
+    headers: HeaderMap,
+) -> Result<Json<InclusionProof>, AppError> {
+    contract.validate().map_err(bad_request)?;
+    authorize_read(
+        &state,
+        &headers,
+        &contract.0,
+        "Program not found in the transparency log",
+    )
+    .await?;
+    state
+        .registry
+        .transparency()
+        .inclusion_proof(&contract.0, &program_id, query.tree_size)
+        .await
+        .map_err(|err| AppError(StatusCode::BAD_REQUEST, err))?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError(
+                StatusCode::NOT_FOUND,
+                anyhow::anyhow!("Program not found in the transparency log"),
+            )
+        })
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ConsistencyProofQuery {
+    first: u64,
+    second: u64,
+}
+
+async fn consistency_proof(
+    State(state): State<RouterCtx>,
+    Query(query): Query<ConsistencyProofQuery>,
+) -> Result<Json<ConsistencyProof>, AppError> {
+    state
+        .registry
+        .transparency()
+        .consistency_proof(query.first, query.second)
+        .await
+        .map(Json)
+        .map_err(|err| AppError(StatusCode::BAD_REQUEST, err))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ContractArchiveQuery {
+    /// Comma-separated program ids, all programs of the contract when absent.
+    program_ids: Option<String>,
+    compression: Option<Compression>,
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn download_contract_archive(
+    State(state): State<RouterCtx>,
+    Path(contract): Path<ContractName>,
+    Query(query): Query<ContractArchiveQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    contract.validate().map_err(bad_request)?;
+    authorize_read(&state, &headers, &contract.0, "Contract not found").await?;
+
+    let Some(mut programs) = state.registry.list_contract(&contract.0).await else {
+        return Err(AppError(
+            StatusCode::NOT_FOUND,
+            anyhow::anyhow!("Contract not found"),
+        ));
+    };
+    if let Some(program_ids) = &query.program_ids {
+        let selected = program_ids
+            .split(',')
+            .map(str::trim)
+            .filter(|program_id| !program_id.is_empty())
+            .collect::<Vec<_>>();
+        if let Some(missing) = selected
+            .iter()
+            .find(|program_id| !programs.iter().any(|p| &p.program_id == *program_id))
+        {
+            return Err(AppError(
+                StatusCode::NOT_FOUND,
+                anyhow::anyhow!("Program {missing} not found"),
+            ));
+        }
+        programs.retain(|program| selected.contains(&program.program_id.as_str()));
+    }
+
+    let compression = query.compression.unwrap_or_default();
+    let registry = state.registry.clone();
+    let runtime = tokio::runtime::Handle::current();
+    let archived_contract = contract.0.clone();
+    let stream = spawn_archive_stream(move |writer| {
+        export_contract_archive(
+            &registry,
+            &runtime,
+            &archived_contract,
+            programs,
+            compression,
+            writer,
+        )
+        .map(|_| ())
+    });
+    Ok(archive_response(
+        &format!("{}.{}", contract.0, archive_extension(compression)),
+        Body::from_stream(stream),
+    ))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct WarmupQuery {
+    strategy: Option<WarmupStrategy>,
+    max_bytes: Option<u64>,
+    programs_per_contract: Option<usize>,
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn warmup_cache(
+    State(state): State<RouterCtx>,
+    Query(query): Query<WarmupQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize(&state, &headers, Operation::Admin, None).await?;
+
+    let options = WarmupOptions {
+        strategy: query.strategy.unwrap_or(state.warmup.strategy),
+        max_bytes: query.max_bytes.unwrap_or(state.warmup.max_bytes),
+        programs_per_contract: query
+            .programs_per_contract
+            .unwrap_or(state.warmup.programs_per_contract),
+    };
+    let summary = state.registry.spawn_warmup(options).await;
+    Ok((StatusCode::ACCEPTED, Json(summary)).into_response())
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn webhook_deliveries(
+    State(state): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<Json<WebhookStatus>, AppError> {
+    authorize(&state, &headers, Operation::Admin, None).await?;
+
+    let status = match &state.webhooks {
+        Some(webhooks) => webhooks.status().await,
+        None => WebhookStatus {
+            pending: 0,
+            attempts: Vec::new(),
+        },
+    };
+    Ok(Json(status))
+}
+
+fn validate_owned_contract(contract: &ContractName) -> Result<(), AppError> {
+    contract.validate().map_err(bad_request)?;
+    if is_reserved_contract(&contract.0) {
+        return Err(bad_request(format!(
+            "Contract name {} is reserved",
+            contract.0
+        )));
+    }
+    Ok(())
+}
+
+fn not_owned(contract: &str) -> AppError {
+    AppError(
+        StatusCode::NOT_FOUND,
+        anyhow::anyhow!("Contract {contract} has no owner"),
+    )
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn contract_owner(
+    State(state): State<RouterCtx>,
+    Path(contract): Path<ContractName>,
+    headers: HeaderMap,
+) -> Result<Json<ContractOwnership>, AppError> {
+    validate_owned_contract(&contract)?;
+    authorize_read(
+        &state,
+        &headers,
+        &contract.0,
+        &format!("Contract {} has no owner", contract.0),
+    )
+    .await?;
+    state
+        .registry
+        .ownership(&contract.0)
+        .await
+        .map(Json)
+        .ok_or_else(|| not_owned(&contract.0))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TransferRequest {
+    owner: String,
+    /// Replaces the collaborators when set, kept otherwise.
+    collaborators: Option<std::collections::BTreeSet<String>>,
+}
+
+/// Claims a contract for an identity, or transfers it to another one.
+#[tracing::instrument(skip(state, headers))]
+async fn transfer_contract(
+    State(state): State<RouterCtx>,
+    Path(contract): Path<ContractName>,
+    headers: HeaderMap,
+    Json(request): Json<TransferRequest>,
+) -> Result<Json<ContractOwnership>, AppError> {
+    let actor = authorize(&state, &headers, Operation::Admin, None).await?;
+    require_writable(&state)?;
+    validate_owned_contract(&contract)?;
+    validate_identity(&request.owner).map_err(bad_request)?;
+    for collaborator in request.collaborators.iter().flatten() {
+        validate_identity(collaborator).map_err(bad_request)?;
+    }
+
+    let ownership = log_error!(
+        state
+            .registry
+            .update_ownership(&contract.0, |ownership| {
+                let collaborators = request
+                    .collaborators
+                    .or_else(|| ownership.take().map(|previous| previous.collaborators))
+                    .unwrap_or_default();
+                *ownership = Some(ContractOwnership {
+                    owner: request.owner,
+                    collaborators,
+                    claimed_at: chrono::Utc::now().to_rfc3339(),
+                });
+            })
+            .await,
+        "Transferring contract"
+    )
+    .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+    .ok_or_else(|| not_owned(&contract.0))?;
+    tracing::info!(
+        "Contract {} transferred to {} by {}",
+        contract.0,
+        ownership.owner,
+        actor.name
+    );
+    Ok(Json(ownership))
+}
+
+/// Releases a contract: the next upload claims it again.
+#[tracing::instrument(skip(state, headers))]
+async fn release_contract(
+    State(state): State<RouterCtx>,
+    Path(contract): Path<ContractName>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let actor = authorize(&state, &headers, Operation::Admin, None).await?;
+    require_writable(&state)?;
+    validate_owned_contract(&contract)?;
+
+    let mut released = false;
+    state
+        .registry
+        .update_ownership(&contract.0, |ownership| {
+            released = ownership.take().is_some();
+        })
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    if !released {
+        return Err(not_owned(&contract.0));
+    }
+    tracing::info!("Contract {} released by {}", contract.0, actor.name);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn add_collaborator(
+    State(state): State<RouterCtx>,
+    Path((contract, identity)): Path<(ContractName, String)>,
+    headers: HeaderMap,
+) -> Result<Json<ContractOwnership>, AppError> {
+    validate_owned_contract(&contract)?;
+    validate_identity(&identity).map_err(bad_request)?;
+    authorize_owner(&state, &headers, &contract.0).await?;
+    require_writable(&state)?;
+
+    state
+        .registry
+        .update_ownership(&contract.0, |ownership| {
+            if let Some(ownership) = ownership {
+                ownership.collaborators.insert(identity);
+            }
+        })
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+        .map(Json)
+        .ok_or_else(|| not_owned(&contract.0))
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn remove_collaborator(
+    State(state): State<RouterCtx>,
+    Path((contract, identity)): Path<(ContractName, String)>,
+    headers: HeaderMap,
+) -> Result<Json<ContractOwnership>, AppError> {
+    validate_owned_contract(&contract)?;
+    authorize_owner(&state, &headers, &contract.0).await?;
+    require_writable(&state)?;
+
+    state
+        .registry
+        .update_ownership(&contract.0, |ownership| {
+            if let Some(ownership) = ownership {
+                ownership.collaborators.remove(&identity);
+            }
+        })
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+        .map(Json)
+        .ok_or_else(|| not_owned(&contract.0))
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct Visibility {
+    private: bool,
+}
+
+/// Makes a contract private, hiding it from listings and requiring the
+/// `read` operation for its programs, or public again.
+#[tracing::instrument(skip(state, headers))]
+async fn set_contract_visibility(
+    State(state): State<RouterCtx>,
+    Path(contract): Path<ContractName>,
+    headers: HeaderMap,
+    Json(visibility): Json<Visibility>,
+) -> Result<Json<Visibility>, AppError> {
+    let actor = authorize_owner(&state, &headers, &contract.0).await?;
+    require_writable(&state)?;
+    validate_owned_contract(&contract)?;
+
+    let changed = log_error!(
+        state
+            .registry
+            .set_private(&contract.0, visibility.private)
+            .await,
+        "Changing contract visibility"
+    )
+    .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    if changed {
+        tracing::info!(
+            "Contract {} made {} by {}",
+            contract.0,
+            if visibility.private {
+                "private"
+            } else {
+                "public"
+            },
+            actor.name
+        );
+    }
+    Ok(Json(visibility))
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn list_api_keys(
+    State(state): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ApiKeyInfo>>, AppError> {
+    authorize(&state, &headers, Operation::Admin, None).await?;
+    Ok(Json(state.api_keys.list().await))
+}
+
+/// Creates an API key; its secret is only returned in this response.
+#[tracing::instrument(skip(state, headers, request))]
+async fn create_api_key(
+    State(state): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<NewApiKey>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), AppError> {
+    let actor = authorize(&state, &headers, Operation::Admin, None).await?;
+    let created = log_error!(
+        state.api_keys.create(request, &actor).await,
+        "Creating API key"
+    )
+    .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    match created {
+        CreateApiKey::Created(created) => Ok((StatusCode::CREATED, Json(created))),
+        CreateApiKey::Rejected(reason) => Err(bad_request(reason)),
+    }
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn revoke_api_key(
+    State(state): State<RouterCtx>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiKeyInfo>, AppError> {
+    let actor = authorize(&state, &headers, Operation::Admin, None).await?;
+    state
+        .api_keys
+        .revoke(&id, &actor)
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+        .map(Json)
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow::anyhow!("API key not found")))
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct ExpireApiKeyRequest {
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Sets the expiry date of an API key, immediately when none is given.
+#[tracing::instrument(skip(state, headers, request))]
+async fn expire_api_key(
+    State(state): State<RouterCtx>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    request: Option<Json<ExpireApiKeyRequest>>,
+) -> Result<Json<ApiKeyInfo>, AppError> {
+    let actor = authorize(&state, &headers, Operation::Admin, None).await?;
+    let Json(request) = request.unwrap_or_default();
+    state
+        .api_keys
+        .expire(&id, request.expires_at, &actor)
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?
+        .map(Json)
+        .ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow::anyhow!("API key not found")))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BackupExportQuery {
+    compression: Option<Compression>,
+}
+
+#[tracing::instrument(skip(state, headers))]
+async fn export_backup(
+    State(state): State<RouterCtx>,
+    Query(query): Query<BackupExportQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    authorize(&state, &headers, Operation::Admin, None).await?;
+
+    let compression = query.compression.unwrap_or_default();
+    let registry = state.registry.clone();
+    let runtime = tokio::runtime::Handle::current();
+    let stream = spawn_archive_stream(move |writer| {
+        export_archive(&registry, &runtime, compression, writer).map(|_| ())
+    });
+    Ok(archive_response(
+        &format!("registry-backup.{}", archive_extension(compression)),
+        Body::from_stream(stream),
+    ))
+}
+
+fn archive_extension(compression: Compression) -> &'static str {
+    match compression {
+        Compression::Zstd => "tar.zst",
+        Compression::None => "tar",
+    }
+}
+
+fn archive_response(file_name: &str, body: Body) -> Response {
+    (
+        [
+            (
+                axum::http::header::CONTENT_TYPE,
+                "application/octet-stream".to_string(),
+            ),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BackupImportQuery {
+    mode: Option<ImportMode>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Restores an archive produced by `GET /api/backup`, spooled to a temporary
+/// file first since it is validated before anything is written.
+#[tracing::instrument(skip(state, headers, body))]
+async fn import_backup(
+    State(state): State<RouterCtx>,
+    Query(query): Query<BackupImportQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<ImportReport>, AppError> {
+    authorize(&state, &headers, Operation::Admin, None).await?;
+    require_writable(&state)?;
+
+    let spool = tempfile::NamedTempFile::new()?;
+    let mut file = tokio::fs::File::from_std(spool.reopen()?);
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| AppError(StatusCode::BAD_REQUEST, err.into()))?;
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+
+    let plan = plan_import(
+        &state.registry,
+        spool.path(),
+        query.mode.unwrap_or_default(),
+    )
+    .await
+    .map_err(|err| AppError(StatusCode::BAD_REQUEST, err))?;
+    if query.dry_run {
+        return Ok(Json(plan.report(true)));
+    }
+    let report = apply_import(&state.registry, plan)
+        .await
+        .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    Ok(Json(report))
+}
+
+fn bad_request(err: String) -> AppError {
+    AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorageBackend;
+    use hyli_modules::bus::command_response::CmdRespClient;
+
+    module_bus_client! {
+    struct TestBusClient {
+        sender(BusQuery<GetProgramElf, Option<Bytes>>),
+        sender(BusQuery<ListContractPrograms, Option<Vec<ProgramInfo>>>),
+    }
+    }
+
+    async fn test_registry(temp_dir: &tempfile::TempDir) -> Arc<RegistryService> {
+        let config = Conf {
+            binary_cache_max_bytes: 1024,
+            event_log_capacity: 16,
+            ..Default::default()
+        };
+        let app = RegistryBuilder::new(config)
+            .storage(Arc::new(LocalStorageBackend::new(
+                temp_dir.path().to_path_buf(),
+            )))
+            .register_metrics(false)
+            .build()
+            .await
+            .expect("build");
+        app.registry().clone()
+    }
+
+    #[tokio::test]
+    async fn bus_queries_are_answered_from_the_registry() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let registry = test_registry(&temp_dir).await;
+        registry
+            .upload(
+                "orders",
+                "p1",
+                ProgramMetadata {
+                    toolchain: None,
+                    commit: None,
+                    zkvm: "sp1".to_string(),
+                },
+                Bytes::from_static(b"elf"),
+            )
+            .await
+            .expect("upload");
+
+        let bus = SharedMessageBus::new();
+        let mut module = AppModule {
+            bus: AppModuleBusClient::new_from_bus(bus.new_handle()).await,
+            events: registry.subscribe(),
+            registry,
+        };
+        tokio::spawn(async move { module.run().await });
+        let mut client = TestBusClient::new_from_bus(bus.new_handle()).await;
+
+        let elf = client
+            .request(GetProgramElf {
+                contract: "orders".to_string(),
+                program_id: "p1".to_string(),
+            })
+            .await
+            .expect("elf query");
+        assert_eq!(elf.as_deref(), Some(&b"elf"[..]));
+        let missing = client
+            .request(GetProgramElf {
+                contract: "orders".to_string(),
+                program_id: "p2".to_string(),
+            })
+            .await
+            .expect("elf query");
+        assert!(missing.is_none());
+
+        let programs = client
+            .request(ListContractPrograms {
+                contract: "orders".to_string(),
+            })
+            .await
+            .expect("listing query")
+            .expect("known contract");
+        assert_eq!(
+            programs
+                .iter()
+                .map(|program| program.program_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["p1"]
+        );
+        let unknown = client
+            .request(ListContractPrograms {
+                contract: "wallet".to_string(),
+            })
+            .await
+            .expect("listing query");
+        assert!(unknown.is_none());
+    }
+
+    /// `multipart/form-data` body uploading `elf` as `program_id`, with its
+    /// signature if any, and its content type.
+    fn upload_form(
+        program_id: &str,
+        elf: &[u8],
+        signature: Option<&UploadSignature>,
+    ) -> (String, Vec<u8>) {
+        let boundary = "registry-test-boundary";
+        let mut fields = vec![
+            ("program_id", program_id.as_bytes().to_vec()),
+            ("metadata", br#"{"zkvm":"sp1"}"#.to_vec()),
+            ("file", elf.to_vec()),
+        ];
+        if let Some(signature) = signature {
+            fields.push(("public_key", signature.public_key.as_bytes().to_vec()));
+            fields.push(("signature", signature.signature.as_bytes().to_vec()));
+        }
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!("--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n")
+                    .as_bytes(),
+            );
+            body.extend_from_slice(&value);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+        (format!("multipart/form-data; boundary={boundary}"), body)
+    }
+
+    #[tokio::test]
+    async fn uploads_to_contracts_with_publishers_must_be_signed_by_one() {
+        use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+        let secp = Secp256k1::new();
+        let publisher = SecretKey::from_slice(&[1; 32]).expect("key");
+        let stranger = SecretKey::from_slice(&[2; 32]).expect("key");
+        let sign = |key: &SecretKey, elf: &[u8]| UploadSignature {
+            public_key: hex::encode(PublicKey::from_secret_key(&secp, key).serialize()),
+            signature: hex::encode(
+                secp.sign_ecdsa(
+                    &Message::from_digest(crate::signing::upload_digest("orders", "p1", elf)),
+                    key,
+                )
+                .serialize_compact(),
+            ),
+        };
+
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let app = RegistryBuilder::new(Conf {
+            api_key: "dev".to_string(),
+            publishers: vec![crate::conf::PublisherConf {
+                contract: "orders".to_string(),
+                public_keys: vec![sign(&publisher, b"elf").public_key],
+            }],
+            ..Default::default()
+        })
+        .storage(Arc::new(LocalStorageBackend::new(
+            temp_dir.path().to_path_buf(),
+        )))
+        .register_metrics(false)
+        .build()
+        .await
+        .expect("build");
+        let server = app.spawn().await.expect("spawn");
+        let client = reqwest::Client::new();
+
+        for (signature, status) in [
+            (None, StatusCode::UNAUTHORIZED),
+            // Signed by the publisher, but over another ELF.
+            (Some(sign(&publisher, b"other")), StatusCode::UNAUTHORIZED),
+            (Some(sign(&stranger, b"elf")), StatusCode::FORBIDDEN),
+            (Some(sign(&publisher, b"elf")), StatusCode::OK),
+        ] {
+            let (content_type, body) = upload_form("p1", b"elf", signature.as_ref());
+            let response = client
+                .post(format!("{}/api/elfs/orders", server.url()))
+                .header(API_KEY_HEADER, "dev")
+                .header(axum::http::header::CONTENT_TYPE, content_type)
+                .body(body)
+                .send()
+                .await
+                .expect("upload");
+            assert_eq!(response.status().as_u16(), status.as_u16());
+            if status != StatusCode::OK {
+                assert!(app.registry().list_contract("orders").await.is_none());
+            }
+        }
+        let response = client
+            .put(format!("{}/api/elfs/orders/p1/artifacts/vk", server.url()))
+            .header(API_KEY_HEADER, "dev")
+            .body("vk")
+            .send()
+            .await
+            .expect("put artifact");
+        assert_eq!(response.status().as_u16(), 403);
+
+        let stored = app
+            .registry()
+            .list_contract("orders")
+            .await
+            .expect("orders");
+        assert_eq!(
+            stored[0]
+                .signature
+                .as_ref()
+                .map(|signature| &signature.public_key),
+            Some(&sign(&publisher, b"elf").public_key)
+        );
+    }
+
+    #[tokio::test]
+    async fn browsers_may_send_put_requests() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let app = RegistryBuilder::new(Conf::default())
+            .storage(Arc::new(LocalStorageBackend::new(
+                temp_dir.path().to_path_buf(),
+            )))
+            .register_metrics(false)
+            .build()
+            .await
+            .expect("build");
+        let server = app.spawn().await.expect("spawn");
+        let response = reqwest::Client::new()
+            .request(
+                Method::OPTIONS,
+                format!("{}/api/contracts/orders/visibility", server.url()),
+            )
+            .header("origin", "https://explorer.example")
+            .header("access-control-request-method", "PUT")
+            .send()
+            .await
+            .expect("preflight");
+        assert!(response.status().is_success());
+        let allowed = response.headers()["access-control-allow-methods"]
+            .to_str()
+            .expect("methods");
+        assert!(allowed.split(',').any(|method| method.trim() == "PUT"));
+    }
+
+    #[tokio::test]
+    async fn artifacts_are_downloaded_as_attachments() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let app = RegistryBuilder::new(Conf::default())
+            .storage(Arc::new(LocalStorageBackend::new(
+                temp_dir.path().to_path_buf(),
+            )))
+            .register_metrics(false)
+            .build()
+            .await
+            .expect("build");
+        app.registry()
+            .upload_with(
+                "orders",
+                "p1",
+                ProgramMetadata {
+                    toolchain: None,
+                    commit: None,
+                    zkvm: "sp1".to_string(),
+                },
+                UploadAttachments {
+                    artifacts: vec![
+                        ArtifactUpload::new(
+                            "page",
+                            Some("text/html"),
+                            Bytes::from_static(b"<script>"),
+                        )
+                        .expect("artifact"),
+                        ArtifactUpload::new("notes", Some("text/plain"), Bytes::from_static(b"ok"))
+                            .expect("artifact"),
+                    ],
+                    ..Default::default()
+                },
+                Bytes::from_static(b"elf"),
+            )
+            .await
+            .expect("upload");
+        let server = app.spawn().await.expect("spawn");
+
+        for (name, content_type) in [
+            ("page", "application/octet-stream"),
+            ("notes", "text/plain"),
+        ] {
+            let response = reqwest::get(format!(
+                "{}/api/elfs/orders/p1/artifacts/{name}",
+                server.url()
+            ))
+            .await
+            .expect("download");
+            assert!(response.status().is_success());
+            let headers = response.headers();
+            assert_eq!(headers["content-type"], content_type);
+            assert_eq!(
+                headers["content-disposition"],
+                format!("attachment; filename=\"{name}\"").as_str()
+            );
+            assert_eq!(headers["x-content-type-options"], "nosniff");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_owners_collaborators_and_admins_delete_from_a_contract() {
+        use sha2::{Digest, Sha256};
+
+        let team_key = |name: &str| crate::conf::ApiKeyConf {
+            name: name.to_string(),
+            key_sha256: hex::encode(Sha256::digest(name.as_bytes())),
+            contracts: vec!["*".to_string()],
+            operations: vec![Operation::Upload, Operation::Delete, Operation::Read],
+        };
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let app = RegistryBuilder::new(Conf {
+            admin_key: "root".to_string(),
+            api_keys: vec![team_key("orders-team"), team_key("wallet-team")],
+            ..Default::default()
+        })
+        .storage(Arc::new(LocalStorageBackend::new(
+            temp_dir.path().to_path_buf(),
+        )))
+        .register_metrics(false)
+        .build()
+        .await
+        .expect("build");
+        let server = app.spawn().await.expect("spawn");
+        let client = reqwest::Client::new();
+
+        let (content_type, body) = upload_form("p1", b"elf", None);
+        let response = client
+            .post(format!("{}/api/elfs/orders", server.url()))
+            .header(API_KEY_HEADER, "orders-team")
+            .header(axum::http::header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .await
+            .expect("upload");
+        assert!(response.status().is_success());
+
+        for path in ["/api/elfs/orders/p1", "/api/elfs/orders"] {
+            let response = client
+                .delete(format!("{}{path}", server.url()))
+                .header(API_KEY_HEADER, "wallet-team")
+                .send()
+                .await
+                .expect("delete");
+            assert_eq!(response.status().as_u16(), 403);
+        }
+        let response = client
+            .delete(format!("{}/api/elfs/orders", server.url()))
+            .header(API_KEY_HEADER, "root")
+            .send()
+            .await
+            .expect("delete");
+        assert_eq!(response.status().as_u16(), 204);
+        assert!(app
+            .registry()
+            .list_contract("orders")
+            .await
+            .is_none_or(|programs| programs.is_empty()));
+        let ownership = app.registry().ownership("orders").await.expect("owned");
+        assert_eq!(ownership.owner, "orders-team");
+
+        // A failed upload does not keep the contract it claimed.
+        let response = client
+            .put(format!(
+                "{}/api/elfs/wallet/missing/artifacts/vk",
+                server.url()
+            ))
+            .header(API_KEY_HEADER, "wallet-team")
+            .body("vk")
+            .send()
+            .await
+            .expect("put artifact");
+        assert_eq!(response.status().as_u16(), 404);
+        assert!(app.registry().ownership("wallet").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn private_contracts_look_unknown_to_other_callers() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let app = RegistryBuilder::new(Conf {
+            admin_key: "root".to_string(),
+            binary_cache_max_bytes: 1024,
+            ..Default::default()
+        })
+        .storage(Arc::new(LocalStorageBackend::new(
+            temp_dir.path().to_path_buf(),
+        )))
+        .register_metrics(false)
+        .build()
+        .await
+        .expect("build");
+        for contract in ["orders", "wallet"] {
+            app.registry()
+                .upload(
+                    contract,
+                    "p1",
+                    ProgramMetadata {
+                        toolchain: None,
+                        commit: None,
+                        zkvm: "sp1".to_string(),
+                    },
+                    Bytes::from_static(b"elf"),
+                )
+                .await
+                .expect("upload");
+        }
+        app.registry()
+            .set_private("orders", true)
+            .await
+            .expect("hide");
+        app.registry()
+            .claim_for_upload("orders", "alice")
+            .await
+            .expect("claim");
+        for contract in ["orders", "wallet"] {
+            app.registry()
+                .download(contract, "p1")
+                .await
+                .expect("download")
+                .expect("elf");
+        }
+        let server = app.spawn().await.expect("spawn");
+        let client = reqwest::Client::new();
+        let get = |path: &str, key: Option<&str>| {
+            let mut request = client.get(format!("{}{path}", server.url()));
+            if let Some(key) = key {
+                request = request.header(API_KEY_HEADER, key);
+            }
+            request.send()
+        };
+
+        for key in [None, Some("wrong")] {
+            for contract in ["orders", "unknown"] {
+                for path in [
+                    format!("/api/elfs/{contract}"),
+                    format!("/api/elfs/{contract}/p1"),
+                    format!("/api/transparency/proof/{contract}/p1"),
+                    format!("/api/contracts/{contract}/owner"),
+                ] {
+                    let response = get(&path, key).await.expect("get");
+                    assert_eq!(response.status().as_u16(), 404, "{path}");
+                }
+            }
+        }
+        let response = get("/api/transparency/proof/orders/p1", Some("root"))
+            .await
+            .expect("proof");
+        assert!(response.status().is_success());
+        let response = get("/api/contracts/orders/owner", Some("root"))
+            .await
+            .expect("owner");
+        assert!(response.status().is_success());
+
+        let stats = |key| async move {
+            get("/api/stats", key)
+                .await
+                .expect("stats")
+                .json::<serde_json::Value>()
+                .await
+                .expect("stats")
+        };
+        let public = stats(None).await;
+        assert_eq!(public["contracts"], 1);
+        assert_eq!(public["programs"], 1);
+        assert_eq!(public["total_bytes"], 3);
+        assert_eq!(public["per_zkvm"]["sp1"]["programs"], 1);
+        assert!(public["per_contract"].get("orders").is_none());
+        assert_eq!(public["cache"]["entries"], 1);
+        assert_eq!(public["cache"]["programs"][0]["contract"], "wallet");
+        let read = stats(Some("root")).await;
+        assert_eq!(read["contracts"], 2);
+        assert_eq!(read["per_zkvm"]["sp1"]["programs"], 2);
+        assert_eq!(read["cache"]["entries"], 2);
+
+        let leaves = |key| async move {
+            get("/api/transparency/leaves", key)
+                .await
+                .expect("leaves")
+                .json::<Vec<ListedLeaf>>()
+                .await
+                .expect("leaves")
+        };
+        let public = leaves(None).await;
+        assert!(matches!(&public[0], ListedLeaf::Redacted { .. }));
+        assert!(matches!(&public[1], ListedLeaf::Leaf(leaf) if leaf.contract == "wallet"));
+        let read = leaves(Some("root")).await;
+        assert!(matches!(&read[0], ListedLeaf::Leaf(leaf) if leaf.contract == "orders"));
+        assert_eq!(
+            public[0],
+            ListedLeaf::Redacted {
+                leaf_hash: match &read[0] {
+                    ListedLeaf::Leaf(leaf) => hex::encode(leaf.hash()),
+                    redacted => panic!("unexpected {redacted:?}"),
+                }
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn event_streams_close_once_their_key_is_revoked() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let app = RegistryBuilder::new(Conf {
+            admin_key: "root".to_string(),
+            ..Default::default()
+        })
+        .storage(Arc::new(LocalStorageBackend::new(
+            temp_dir.path().to_path_buf(),
+        )))
+        .register_metrics(false)
+        .build()
+        .await
+        .expect("build");
+        let server = app.spawn().await.expect("spawn");
+        let client = reqwest::Client::new();
+
+        let created: serde_json::Value = client
+            .post(format!("{}/api/keys", server.url()))
+            .header(API_KEY_HEADER, "root")
+            .json(&serde_json::json!({
+                "name": "watcher",
+                "contracts": ["*"],
+                "operations": ["read"],
+            }))
+            .send()
+            .await
+            .expect("create key")
+            .json()
+            .await
+            .expect("created key");
+        let mut events = client
+            .get(format!("{}/api/events", server.url()))
+            .header(API_KEY_HEADER, created["key"].as_str().expect("key"))
+            .send()
+            .await
+            .expect("subscribe");
+        assert!(events.status().is_success());
+
+        let response = client
+            .delete(format!(
+                "{}/api/keys/{}",
+                server.url(),
+                created["id"].as_str().expect("id")
+            ))
+            .header(API_KEY_HEADER, "root")
+            .send()
+            .await
+            .expect("revoke");
+        assert!(response.status().is_success());
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while events.chunk().await.expect("chunk").is_some() {}
+        })
+        .await;
+        assert!(closed.is_ok(), "stream still open");
+    }
 }
@@ -0,0 +1,158 @@
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Most artifacts attached to one program.
+pub const MAX_ARTIFACTS_PER_PROGRAM: usize = 16;
+const MAX_NAME_LEN: usize = 64;
+const MAX_CONTENT_TYPE_LEN: usize = 128;
+pub const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+/// Content types artifacts are served with; others are served as
+/// [`DEFAULT_CONTENT_TYPE`] so that browsers never render them.
+const SERVED_CONTENT_TYPES: &[&str] = &[
+    DEFAULT_CONTENT_TYPE,
+    "application/gzip",
+    "application/json",
+    "application/x-tar",
+    "application/zip",
+    "application/zstd",
+    "text/plain",
+];
+
+/// File attached to a program next to its ELF (verification key, sources, ...).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactEntry {
+    /// Hex SHA-256 of the artifact.
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub content_type: String,
+}
+
+/// Artifact sent with an upload, validated by the caller.
+#[derive(Debug, Clone)]
+pub struct ArtifactUpload {
+    pub name: String,
+    pub content_type: String,
+    pub bytes: Bytes,
+}
+
+impl ArtifactUpload {
+    /// Validates the name and content type of an artifact.
+    pub fn new(name: &str, content_type: Option<&str>, bytes: Bytes) -> Result<Self, String> {
+        validate_artifact_name(name)?;
+        let content_type = content_type
+            .map(str::trim)
+            .filter(|content_type| !content_type.is_empty())
+            .unwrap_or(DEFAULT_CONTENT_TYPE);
+        validate_content_type(content_type)?;
+        Ok(Self {
+            name: name.to_string(),
+            content_type: content_type.to_string(),
+            bytes,
+        })
+    }
+
+    pub fn entry(&self) -> ArtifactEntry {
+        ArtifactEntry {
+            sha256: hex::encode(Sha256::digest(&self.bytes)),
+            size_bytes: self.bytes.len() as u64,
+            content_type: self.content_type.clone(),
+        }
+    }
+}
+
+/// Outcome of attaching an artifact to a stored program.
+#[derive(Debug)]
+pub enum PutArtifact {
+    Stored(ArtifactEntry),
+    ProgramNotFound,
+    /// The program already has [`MAX_ARTIFACTS_PER_PROGRAM`] other artifacts.
+    TooMany,
+}
+
+/// Content type an artifact stored as `content_type` is served with: its
+/// media type if allowed, without parameters.
+pub fn served_content_type(content_type: &str) -> &'static str {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    SERVED_CONTENT_TYPES
+        .iter()
+        .find(|served| **served == media_type)
+        .copied()
+        .unwrap_or(DEFAULT_CONTENT_TYPE)
+}
+
+/// Artifact names are used in object paths: lowercase ASCII letters, digits,
+/// `.`, `_` and `-`, not starting with a `.`.
+pub fn validate_artifact_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "Artifact name must be 1 to {MAX_NAME_LEN} characters long"
+        ));
+    }
+    if name.starts_with('.')
+        || !name.bytes().all(|byte| {
+            byte.is_ascii_lowercase() || byte.is_ascii_digit() || b"._-".contains(&byte)
+        })
+    {
+        return Err(format!(
+            "Invalid artifact name {name}: use lowercase letters, digits, '.', '_' and '-'"
+        ));
+    }
+    Ok(())
+}
+
+fn validate_content_type(content_type: &str) -> Result<(), String> {
+    let valid = content_type.len() <= MAX_CONTENT_TYPE_LEN
+        && content_type.contains('/')
+        && content_type
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() || byte == b' ');
+    if !valid {
+        return Err(format!("Invalid artifact content type {content_type}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_artifact_names_and_content_types() {
+        for name in ["vk", "source.tar.gz", "sample_proof", "proof-1"] {
+            assert!(validate_artifact_name(name).is_ok(), "{name} rejected");
+        }
+        for name in [
+            "",
+            ".hidden",
+            "Vk",
+            "a/b",
+            "../vk",
+            "with space",
+            &"a".repeat(65),
+        ] {
+            assert!(validate_artifact_name(name).is_err(), "{name} accepted");
+        }
+
+        let artifact = ArtifactUpload::new("vk", None, Bytes::from_static(b"vk")).expect("vk");
+        assert_eq!(artifact.content_type, DEFAULT_CONTENT_TYPE);
+        assert_eq!(artifact.entry().size_bytes, 2);
+        assert!(ArtifactUpload::new("vk", Some("application/gzip"), Bytes::new()).is_ok());
+        assert!(ArtifactUpload::new("vk", Some("gzip"), Bytes::new()).is_err());
+        assert!(ArtifactUpload::new("vk", Some("text/\u{7f}plain"), Bytes::new()).is_err());
+
+        assert_eq!(served_content_type("application/gzip"), "application/gzip");
+        assert_eq!(
+            served_content_type("Text/Plain; charset=utf-8"),
+            "text/plain"
+        );
+        for content_type in ["text/html", "image/svg+xml", "application/javascript"] {
+            assert_eq!(served_content_type(content_type), DEFAULT_CONTENT_TYPE);
+        }
+    }
+}
@@ -0,0 +1,850 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::conf::Conf;
+use crate::query::glob_match;
+use crate::registry::INTERNAL_PREFIX;
+use crate::storage::StorageBackend;
+
+/// Name the `api_key` of the config is recorded under.
+pub const LEGACY_UPLOAD_KEY: &str = "api_key";
+/// Name the `admin_key` of the config is recorded under.
+pub const LEGACY_ADMIN_KEY: &str = "admin_key";
+
+/// Prefix of the secrets of managed keys, followed by `<id>_<random hex>`.
+const SECRET_PREFIX: &str = "hrk_";
+const MAX_KEY_NAME_LEN: usize = 64;
+/// Last-used timestamps of managed keys are persisted at most this often.
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+/// Action an API key may be allowed to perform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Uploading programs and attaching artifacts.
+    Upload,
+    /// Deleting programs, contracts and artifacts.
+    Delete,
+    /// Listing and downloading the programs of private contracts.
+    Read,
+    /// Registry-wide actions: backups, cache warm-up, webhook deliveries.
+    Admin,
+}
+
+impl Operation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Operation::Upload => "upload",
+            Operation::Delete => "delete",
+            Operation::Read => "read",
+            Operation::Admin => "admin",
+        }
+    }
+}
+
+/// Key that performed an authorized action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    /// Name of the key, or `oidc:<policy>` for OIDC tokens.
+    pub name: String,
+    /// Claims of the OIDC token, empty for API keys.
+    pub claims: BTreeMap<String, String>,
+}
+
+/// Why a request was refused.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthError {
+    Missing,
+    /// The key matches none of the configured or active managed keys.
+    Invalid,
+    /// The key exists but its expiry date has passed.
+    Expired,
+    /// The OIDC token could not be verified.
+    InvalidToken(String),
+    /// The key is valid but not allowed to perform this operation here.
+    Forbidden {
+        key: String,
+    },
+}
+
+/// Verified API key or OIDC token, with everything it is allowed.
+#[derive(Debug, Clone)]
+pub struct Credential {
+    /// Named in refusals when no scope applies.
+    name: String,
+    claims: BTreeMap<String, String>,
+    /// Scopes granted, each with the name actions under it are recorded as.
+    scopes: Vec<(String, KeyScope)>,
+}
+
+impl Credential {
+    pub(crate) fn new(
+        name: String,
+        claims: BTreeMap<String, String>,
+        scopes: Vec<(String, KeyScope)>,
+    ) -> Self {
+        Self {
+            name,
+            claims,
+            scopes,
+        }
+    }
+
+    /// Checks that the credential may perform `operation` on `contract`, or
+    /// on the whole registry when `contract` is `None`.
+    pub fn authorize(
+        &self,
+        operation: Operation,
+        contract: Option<&str>,
+    ) -> Result<Actor, AuthError> {
+        let Some((name, _)) = self
+            .scopes
+            .iter()
+            .find(|(_, scope)| scope.allows(operation, contract))
+        else {
+            return Err(AuthError::Forbidden {
+                key: match self.scopes.first() {
+                    Some((name, _)) => name.clone(),
+                    None => self.name.clone(),
+                },
+            });
+        };
+        if self.claims.is_empty() {
+            info!(
+                key = %name,
+                operation = operation.as_str(),
+                contract = contract.unwrap_or("*"),
+                "API key authorized"
+            );
+        } else {
+            info!(
+                actor = %name,
+                operation = operation.as_str(),
+                contract = contract.unwrap_or("*"),
+                claims = ?self.claims,
+                "OIDC token authorized"
+            );
+        }
+        Ok(Actor {
+            name: name.clone(),
+            claims: self.claims.clone(),
+        })
+    }
+
+    /// Like [`Credential::authorize`], without logging.
+    pub fn allows(&self, operation: Operation, contract: Option<&str>) -> bool {
+        self.scopes
+            .iter()
+            .any(|(_, scope)| scope.allows(operation, contract))
+    }
+}
+
+/// Contract patterns and operations a key is allowed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyScope {
+    /// Contract patterns (`*` wildcard); registry-wide operations need `*`.
+    pub contracts: Vec<String>,
+    pub operations: Vec<Operation>,
+}
+
+impl KeyScope {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.contracts.is_empty() || self.operations.is_empty() {
+            return Err("contracts and operations must not be empty".to_string());
+        }
+        if self
+            .contracts
+            .iter()
+            .any(|pattern| pattern.trim().is_empty())
+        {
+            return Err("contract patterns must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// Registry-wide operations need a key covering every contract.
+    pub(crate) fn allows(&self, operation: Operation, contract: Option<&str>) -> bool {
+        if !self.operations.contains(&operation) {
+            return false;
+        }
+        match contract {
+            Some(contract) => self
+                .contracts
+                .iter()
+                .any(|pattern| glob_match(pattern, contract)),
+            None => self.contracts.iter().any(|pattern| pattern == "*"),
+        }
+    }
+}
+
+/// Key declared in the config, always active.
+struct BootstrapKey {
+    name: String,
+    sha256: [u8; 32],
+    scope: KeyScope,
+}
+
+/// Key created through the API, stored with a salted hash of its secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManagedKey {
+    id: String,
+    name: String,
+    /// Hex random salt, prepended to the secret before hashing.
+    salt: String,
+    /// Hex SHA-256 of the salt followed by the secret.
+    hash: String,
+    #[serde(flatten)]
+    scope: KeyScope,
+    created_at: DateTime<Utc>,
+    created_by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ManagedKey {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    fn info(&self, now: DateTime<Utc>) -> ApiKeyInfo {
+        ApiKeyInfo {
+            id: Some(self.id.clone()),
+            name: self.name.clone(),
+            scope: self.scope.clone(),
+            source: KeySource::Managed,
+            created_at: Some(self.created_at),
+            created_by: Some(self.created_by.clone()),
+            expires_at: self.expires_at,
+            revoked_at: self.revoked_at,
+            last_used_at: self.last_used_at,
+            active: self.is_active(now),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// Declared in the config (`api_key`, `admin_key`, `api_keys`).
+    Config,
+    /// Created through `POST /api/keys`.
+    Managed,
+}
+
+/// Key as listed by the management API, without its hash.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyInfo {
+    /// Set for managed keys, which are revoked and expired by id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(flatten)]
+    pub scope: KeyScope,
+    pub source: KeySource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub active: bool,
+}
+
+/// Newly created key, along with its secret which is not stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+    pub key: String,
+}
+
+/// Outcome of a key creation request.
+#[derive(Debug)]
+pub enum CreateApiKey {
+    Created(CreatedApiKey),
+    /// Invalid request, or name already used by an active key.
+    Rejected(String),
+}
+
+/// Request to create a managed key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    #[serde(flatten)]
+    pub scope: KeyScope,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PersistedKeys {
+    keys: Vec<ManagedKey>,
+}
+
+fn keys_object_path() -> String {
+    format!("{INTERNAL_PREFIX}/api_keys.json")
+}
+
+/// API keys accepted by the registry: the config keys (`api_key`,
+/// `admin_key` and `api_keys`, kept as bootstrap keys) and the keys managed
+/// through the API, persisted under the internal prefix.
+pub struct ApiKeys {
+    bootstrap: Vec<BootstrapKey>,
+    /// Last use of the bootstrap keys since startup, by name.
+    bootstrap_last_used: std::sync::Mutex<HashMap<String, DateTime<Utc>>>,
+    storage: Arc<dyn StorageBackend>,
+    managed: Mutex<Vec<ManagedKey>>,
+    /// Bumped under the `managed` lock on every change, so that a snapshot
+    /// persisted after the lock is released never overwrites a newer one.
+    version: AtomicU64,
+    /// Version last written, held while writing.
+    written: Mutex<u64>,
+}
+
+impl ApiKeys {
+    pub async fn load(config: &Conf, storage: Arc<dyn StorageBackend>) -> Result<Self> {
+        let mut bootstrap = Vec::new();
+        let legacy = [
+            (
+                LEGACY_UPLOAD_KEY,
+                &config.api_key,
+                vec![Operation::Upload, Operation::Read],
+            ),
+            (
+                LEGACY_ADMIN_KEY,
+                &config.admin_key,
+                vec![Operation::Delete, Operation::Read, Operation::Admin],
+            ),
+        ];
+        for (name, key, operations) in legacy {
+            if key.trim().is_empty() {
+                continue;
+            }
+            bootstrap.push(BootstrapKey {
+                name: name.to_string(),
+                sha256: Sha256::digest(key.as_bytes()).into(),
+                scope: KeyScope {
+                    contracts: vec!["*".to_string()],
+                    operations,
+                },
+            });
+        }
+
+        for key in &config.api_keys {
+            if key.name.trim().is_empty() {
+                bail!("api_keys entries must have a name");
+            }
+            if bootstrap.iter().any(|existing| existing.name == key.name) {
+                bail!("API key name {} is used twice", key.name);
+            }
+            let sha256 = parse_key_hash(&key.key_sha256)
+                .with_context(|| format!("key_sha256 of API key {}", key.name))?;
+            let scope = KeyScope {
+                contracts: key.contracts.clone(),
+                operations: key.operations.clone(),
+            };
+            scope
+                .validate()
+                .map_err(|err| anyhow!("API key {}: {err}", key.name))?;
+            bootstrap.push(BootstrapKey {
+                name: key.name.clone(),
+                sha256,
+                scope,
+            });
+        }
+
+        let managed = match storage.read_object(&keys_object_path()).await? {
+            Some(bytes) => {
+                serde_json::from_slice::<PersistedKeys>(&bytes)
+                    .context("parsing API keys")?
+                    .keys
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            bootstrap,
+            bootstrap_last_used: Default::default(),
+            storage,
+            managed: Mutex::new(managed),
+            version: AtomicU64::new(0),
+            written: Mutex::new(0),
+        })
+    }
+
+    /// Checks that `key` may perform `operation` on `contract`, or on the
+    /// whole registry when `contract` is `None`, and records its use.
+    pub async fn authorize(
+        &self,
+        key: Option<&str>,
+        operation: Operation,
+        contract: Option<&str>,
+    ) -> Result<Actor, AuthError> {
+        self.authenticate(key).await?.authorize(operation, contract)
+    }
+
+    /// Checks that `key` is a valid key, whatever it may do, and records its
+    /// use.
+    pub async fn authenticate(&self, key: Option<&str>) -> Result<Credential, AuthError> {
+        let key = key
+            .filter(|key| !key.is_empty())
+            .ok_or(AuthError::Missing)?;
+        let now = Utc::now();
+        let (name, scope) = match parse_managed_secret(key) {
+            Some((id, _)) => self.authenticate_managed(id, key, now).await?,
+            None => self.authenticate_bootstrap(key, now)?,
+        };
+        Ok(Credential {
+            name: name.clone(),
+            claims: BTreeMap::new(),
+            scopes: vec![(name, scope)],
+        })
+    }
+
+    fn authenticate_bootstrap(
+        &self,
+        key: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(String, KeyScope), AuthError> {
+        let sha256: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        let bootstrap = self
+            .bootstrap
+            .iter()
+            .find(|bootstrap| bool::from(bootstrap.sha256.ct_eq(&sha256)))
+            .ok_or(AuthError::Invalid)?;
+        if let Ok(mut last_used) = self.bootstrap_last_used.lock() {
+            last_used.insert(bootstrap.name.clone(), now);
+        }
+        Ok((bootstrap.name.clone(), bootstrap.scope.clone()))
+    }
+
+    async fn authenticate_managed(
+        &self,
+        id: &str,
+        secret: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(String, KeyScope), AuthError> {
+        let mut managed = self.managed.lock().await;
+        let key = managed
+            .iter_mut()
+            .find(|key| key.id == id && key.revoked_at.is_none())
+            .ok_or(AuthError::Invalid)?;
+        let expected = hex::decode(&key.hash).map_err(|_| AuthError::Invalid)?;
+        if !bool::from(salted_hash(&key.salt, secret).ct_eq(expected.as_slice())) {
+            return Err(AuthError::Invalid);
+        }
+        if key.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(AuthError::Expired);
+        }
+        let authenticated = (key.name.clone(), key.scope.clone());
+
+        let stale = key.last_used_at.is_none_or(|last_used| {
+            now - last_used >= chrono::Duration::seconds(LAST_USED_RESOLUTION_SECS)
+        });
+        if stale {
+            key.last_used_at = Some(now);
+            // Written without holding the lock, which every request needs.
+            let version = self.next_version();
+            let snapshot = managed.clone();
+            drop(managed);
+            if let Err(err) = self.persist(version, &snapshot).await {
+                warn!("Recording last use of API key {}: {err:#}", authenticated.0);
+            }
+        }
+        Ok(authenticated)
+    }
+
+    /// Every key, config keys first, then managed keys by creation date.
+    pub async fn list(&self) -> Vec<ApiKeyInfo> {
+        let now = Utc::now();
+        let bootstrap_last_used = self
+            .bootstrap_last_used
+            .lock()
+            .map(|last_used| last_used.clone())
+            .unwrap_or_default();
+        let mut keys: Vec<ApiKeyInfo> = self
+            .bootstrap
+            .iter()
+            .map(|bootstrap| ApiKeyInfo {
+                id: None,
+                name: bootstrap.name.clone(),
+                scope: bootstrap.scope.clone(),
+                source: KeySource::Config,
+                created_at: None,
+                created_by: None,
+                expires_at: None,
+                revoked_at: None,
+                last_used_at: bootstrap_last_used.get(&bootstrap.name).copied(),
+                active: true,
+            })
+            .collect();
+        keys.extend(self.managed.lock().await.iter().map(|key| key.info(now)));
+        keys
+    }
+
+    /// Creates a key and returns its secret, of which only a salted hash is kept.
+    pub async fn create(&self, request: NewApiKey, created_by: &Actor) -> Result<CreateApiKey> {
+        let now = Utc::now();
+        let name = request.name.trim().to_string();
+        if name.is_empty() || name.len() > MAX_KEY_NAME_LEN || name.chars().any(char::is_control) {
+            return Ok(CreateApiKey::Rejected(format!(
+                "Key name must be 1 to {MAX_KEY_NAME_LEN} printable characters"
+            )));
+        }
+        if let Err(err) = request.scope.validate() {
+            return Ok(CreateApiKey::Rejected(err));
+        }
+        if request
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return Ok(CreateApiKey::Rejected(
+                "expires_at must be in the future".to_string(),
+            ));
+        }
+
+        let mut managed = self.managed.lock().await;
+        if self.bootstrap.iter().any(|key| key.name == name)
+            || managed
+                .iter()
+                .any(|key| key.name == name && key.is_active(now))
+        {
+            return Ok(CreateApiKey::Rejected(format!(
+                "An active key is already named {name}"
+            )));
+        }
+
+        let id = hex::encode(random_bytes::<8>()?);
+        let salt = hex::encode(random_bytes::<16>()?);
+        let secret = format!("{SECRET_PREFIX}{id}_{}", hex::encode(random_bytes::<32>()?));
+        let key = ManagedKey {
+            hash: hex::encode(salted_hash(&salt, &secret)),
+            id,
+            name,
+            salt,
+            scope: request.scope,
+            created_at: now,
+            created_by: created_by.name.clone(),
+            expires_at: request.expires_at,
+            revoked_at: None,
+            last_used_at: None,
+        };
+        managed.push(key.clone());
+        if let Err(err) = self.persist(self.next_version(), &managed).await {
+            managed.pop();
+            return Err(err);
+        }
+        info!("API key {} created by {}", key.name, created_by.name);
+        Ok(CreateApiKey::Created(CreatedApiKey {
+            info: key.info(now),
+            key: secret,
+        }))
+    }
+
+    /// Revokes a managed key; `None` when no key has this id.
+    pub async fn revoke(&self, id: &str, revoked_by: &Actor) -> Result<Option<ApiKeyInfo>> {
+        self.update(id, |key, now| {
+            key.revoked_at.get_or_insert(now);
+        })
+        .await
+        .inspect(|info| {
+            if let Some(info) = info {
+                info!("API key {} revoked by {}", info.name, revoked_by.name);
+            }
+        })
+    }
+
+    /// Sets the expiry date of a managed key, now when `expires_at` is `None`.
+    pub async fn expire(
+        &self,
+        id: &str,
+        expires_at: Option<DateTime<Utc>>,
+        expired_by: &Actor,
+    ) -> Result<Option<ApiKeyInfo>> {
+        self.update(id, |key, now| {
+            key.expires_at = Some(expires_at.unwrap_or(now));
+        })
+        .await
+        .inspect(|info| {
+            if let Some(info) = info {
+                info!(
+                    "API key {} set to expire at {:?} by {}",
+                    info.name, info.expires_at, expired_by.name
+                );
+            }
+        })
+    }
+
+    async fn update(
+        &self,
+        id: &str,
+        change: impl FnOnce(&mut ManagedKey, DateTime<Utc>),
+    ) -> Result<Option<ApiKeyInfo>> {
+        let now = Utc::now();
+        let mut managed = self.managed.lock().await;
+        let Some(position) = managed.iter().position(|key| key.id == id) else {
+            return Ok(None);
+        };
+        let previous = managed[position].clone();
+        change(&mut managed[position], now);
+        if let Err(err) = self.persist(self.next_version(), &managed).await {
+            managed[position] = previous;
+            return Err(err);
+        }
+        Ok(Some(managed[position].info(now)))
+    }
+
+    /// Version of a change made under the `managed` lock.
+    fn next_version(&self) -> u64 {
+        self.version.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Writes the keys as of `version`, unless a newer version is stored.
+    async fn persist(&self, version: u64, keys: &[ManagedKey]) -> Result<()> {
+        let mut written = self.written.lock().await;
+        if *written >= version {
+            return Ok(());
+        }
+        let bytes = serde_json::to_vec(&PersistedKeys {
+            keys: keys.to_vec(),
+        })
+        .context("serializing API keys")?;
+        self.storage
+            .write_object(&keys_object_path(), &bytes)
+            .await
+            .context("writing API keys")?;
+        *written = version;
+        Ok(())
+    }
+}
+
+/// Splits a managed key secret `hrk_<id>_<secret>` into its id and secret part.
+fn parse_managed_secret(key: &str) -> Option<(&str, &str)> {
+    key.strip_prefix(SECRET_PREFIX)?.split_once('_')
+}
+
+fn salted_hash(salt: &str, secret: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(secret.as_bytes());
+    hasher.finalize().into()
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|err| anyhow!("generating random bytes: {err}"))?;
+    Ok(bytes)
+}
+
+fn parse_key_hash(raw: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(raw.trim()).context("decoding hex")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("expected the hex SHA-256 of the key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::ApiKeyConf;
+    use crate::storage::LocalStorageBackend;
+
+    fn temp_storage(temp_dir: &tempfile::TempDir) -> Arc<dyn StorageBackend> {
+        Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf()))
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_by_contract_and_operation() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let config = Conf {
+            api_key: "uploader".to_string(),
+            admin_key: String::new(),
+            api_keys: vec![ApiKeyConf {
+                name: "orders-team".to_string(),
+                key_sha256: hex::encode(Sha256::digest(b"orders-secret")),
+                contracts: vec!["orders-*".to_string()],
+                operations: vec![Operation::Upload, Operation::Delete],
+            }],
+            ..Conf::default()
+        };
+        let keys = ApiKeys::load(&config, temp_storage(&temp_dir))
+            .await
+            .expect("keys");
+
+        let actor = keys
+            .authorize(Some("orders-secret"), Operation::Delete, Some("orders-v2"))
+            .await
+            .expect("in scope");
+        assert_eq!(actor.name, "orders-team");
+        assert_eq!(
+            keys.authorize(Some("orders-secret"), Operation::Upload, Some("wallet"))
+                .await,
+            Err(AuthError::Forbidden {
+                key: "orders-team".to_string()
+            })
+        );
+        assert!(keys
+            .authorize(Some("orders-secret"), Operation::Admin, None)
+            .await
+            .is_err());
+
+        assert_eq!(
+            keys.authorize(Some("uploader"), Operation::Upload, Some("wallet"))
+                .await
+                .map(|actor| actor.name),
+            Ok(LEGACY_UPLOAD_KEY.to_string())
+        );
+        assert!(keys
+            .authorize(Some("uploader"), Operation::Delete, Some("wallet"))
+            .await
+            .is_err());
+        // The legacy keys can read private contracts.
+        assert!(keys
+            .authorize(Some("uploader"), Operation::Read, Some("wallet"))
+            .await
+            .is_ok());
+        assert_eq!(
+            keys.authorize(Some("unknown"), Operation::Upload, Some("wallet"))
+                .await,
+            Err(AuthError::Invalid)
+        );
+        // An empty admin_key disables it rather than accepting empty keys.
+        assert_eq!(
+            keys.authorize(Some(""), Operation::Admin, None).await,
+            Err(AuthError::Missing)
+        );
+
+        let duplicated = Conf {
+            api_keys: vec![config.api_keys[0].clone(), config.api_keys[0].clone()],
+            ..Conf::default()
+        };
+        assert!(ApiKeys::load(&duplicated, temp_storage(&temp_dir))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn managed_keys_are_created_revoked_and_expired() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let config = Conf {
+            admin_key: "admin".to_string(),
+            ..Conf::default()
+        };
+        let keys = ApiKeys::load(&config, temp_storage(&temp_dir))
+            .await
+            .expect("keys");
+        let admin = Actor {
+            name: LEGACY_ADMIN_KEY.to_string(),
+            claims: BTreeMap::new(),
+        };
+        let request = NewApiKey {
+            name: "ci".to_string(),
+            scope: KeyScope {
+                contracts: vec!["wallet".to_string()],
+                operations: vec![Operation::Upload],
+            },
+            expires_at: None,
+        };
+        let CreateApiKey::Created(created) = keys.create(request.clone(), &admin).await.unwrap()
+        else {
+            panic!("key not created");
+        };
+        assert!(matches!(
+            keys.create(request.clone(), &admin).await.unwrap(),
+            CreateApiKey::Rejected(_)
+        ));
+
+        let actor = keys
+            .authorize(Some(&created.key), Operation::Upload, Some("wallet"))
+            .await
+            .expect("valid key");
+        assert_eq!(actor.name, "ci");
+        let mut tampered = created.key.clone();
+        tampered.pop();
+        assert_eq!(
+            keys.authorize(Some(&tampered), Operation::Upload, Some("wallet"))
+                .await,
+            Err(AuthError::Invalid)
+        );
+
+        // Only the salted hash is stored, along with the last use.
+        let stored =
+            std::fs::read_to_string(temp_dir.path().join(keys_object_path())).expect("stored keys");
+        assert!(!stored.contains(&created.key));
+        let reloaded = ApiKeys::load(&config, temp_storage(&temp_dir))
+            .await
+            .expect("reload");
+        let listed = reloaded.list().await;
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].source, KeySource::Config);
+        assert!(listed[1].last_used_at.is_some());
+
+        let id = created.info.id.clone().expect("managed id");
+        let expired = reloaded
+            .expire(&id, None, &admin)
+            .await
+            .unwrap()
+            .expect("known key");
+        assert!(!expired.active);
+        assert_eq!(
+            reloaded
+                .authorize(Some(&created.key), Operation::Upload, Some("wallet"))
+                .await,
+            Err(AuthError::Expired)
+        );
+
+        // Once the previous key is no longer active its name can be reused.
+        let CreateApiKey::Created(rotated) = reloaded.create(request, &admin).await.unwrap() else {
+            panic!("key not rotated");
+        };
+        let rotated_id = rotated.info.id.expect("managed id");
+        assert!(reloaded
+            .revoke(&rotated_id, &admin)
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            reloaded
+                .authorize(Some(&rotated.key), Operation::Upload, Some("wallet"))
+                .await,
+            Err(AuthError::Invalid)
+        );
+        assert!(reloaded.revoke("unknown", &admin).await.unwrap().is_none());
+
+        // A last use recorded before the revocation, persisted after it.
+        let mut stale = reloaded.managed.lock().await.clone();
+        for key in &mut stale {
+            key.revoked_at = None;
+        }
+        let stale_version = reloaded.version.load(Ordering::SeqCst) - 1;
+        reloaded
+            .persist(stale_version, &stale)
+            .await
+            .expect("persist");
+        let reloaded = ApiKeys::load(&config, temp_storage(&temp_dir))
+            .await
+            .expect("reload");
+        assert_eq!(
+            reloaded
+                .authorize(Some(&rotated.key), Operation::Upload, Some("wallet"))
+                .await,
+            Err(AuthError::Invalid)
+        );
+    }
+}
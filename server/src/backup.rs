@@ -0,0 +1,1133 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use chrono::Utc;
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::artifacts::validate_artifact_name;
+use crate::registry::{
+    artifact_object_path, binary_object_path, is_reserved_contract, metadata_object_path,
+    program_id_digest, provenance_object_path, ContractSettings, IndexFile, ProgramEntry,
+    ProgramInfo, ProgramMetadata, RegistryService, INDEX_FILE_NAME, PROVENANCE_SUFFIX,
+};
+
+/// Written last, with the digest of every other file of the archive.
+pub const MANIFEST_PATH: &str = "manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const ZSTD_LEVEL: i32 = 3;
+/// Size of the chunks sent to the HTTP body when streaming an archive.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Zstd,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Add the archived programs, overwriting the ones already registered.
+    #[default]
+    Merge,
+    /// Make the registry hold exactly the archived programs.
+    Replace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: String,
+    pub programs: usize,
+    pub files: BTreeMap<String, FileDigest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDigest {
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct ProgramKey {
+    pub contract: String,
+    pub program_id: String,
+}
+
+impl ProgramKey {
+    fn of(entry: &ProgramEntry) -> Self {
+        Self {
+            contract: entry.contract.clone(),
+            program_id: entry.program_id.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub dry_run: bool,
+    pub added: Vec<ProgramKey>,
+    pub overwritten: Vec<ProgramKey>,
+    pub removed: Vec<ProgramKey>,
+}
+
+/// Validated archive content and the changes importing it makes.
+pub struct ImportPlan {
+    mode: ImportMode,
+    archive: PathBuf,
+    /// Archived programs, keyed by the path of their ELF in the archive.
+    programs: HashMap<String, ProgramEntry>,
+    /// Ownership and visibility of the archived contracts.
+    settings: BTreeMap<String, ContractSettings>,
+    added: Vec<ProgramKey>,
+    overwritten: Vec<ProgramKey>,
+    removed: Vec<ProgramKey>,
+}
+
+impl ImportPlan {
+    pub fn report(&self, dry_run: bool) -> ImportReport {
+        ImportReport {
+            mode: self.mode,
+            dry_run,
+            added: self.added.clone(),
+            overwritten: self.overwritten.clone(),
+            removed: self.removed.clone(),
+        }
+    }
+}
+
+/// Writes a backup archive of the registry: each program's ELF and metadata
+/// under their storage paths, then `index.json` and the manifest. An archive
+/// extracted into an empty storage root is a working registry.
+///
+/// Blocking: run it on a blocking thread, `runtime` is used for storage reads.
+pub fn export_archive(
+    registry: &RegistryService,
+    runtime: &Handle,
+    compression: Compression,
+    writer: impl Write,
+) -> Result<BackupManifest> {
+    write_compressed(compression, writer, |writer| {
+        write_backup(registry, runtime, writer)
+    })
+}
+
+/// Runs `write` on `writer`, through a zstd encoder if asked, and flushes.
+pub fn write_compressed<T>(
+    compression: Compression,
+    mut writer: impl Write,
+    write: impl FnOnce(&mut dyn Write) -> Result<T>,
+) -> Result<T> {
+    let output = match compression {
+        Compression::Zstd => {
+            let mut encoder =
+                zstd::Encoder::new(&mut writer, ZSTD_LEVEL).context("starting zstd")?;
+            let output = write(&mut encoder)?;
+            encoder.finish().context("finishing zstd")?;
+            output
+        }
+        Compression::None => write(&mut writer)?,
+    };
+    writer.flush().context("flushing archive")?;
+    Ok(output)
+}
+
+fn write_backup(
+    registry: &RegistryService,
+    runtime: &Handle,
+    writer: &mut dyn Write,
+) -> Result<BackupManifest> {
+    let index = runtime.block_on(registry.index_snapshot());
+    let storage = registry.storage();
+    let mut entries = index
+        .contracts
+        .values()
+        .flat_map(|contract_entry| contract_entry.programs.values())
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| (&a.contract, &a.program_id).cmp(&(&b.contract, &b.program_id)));
+
+    let mut archive = ArchiveWriter::new(writer);
+    let mut exported = IndexFile::default();
+    for entry in entries {
+        // Deleted since the index snapshot was taken.
+        let Some(elf) = runtime.block_on(storage.read_object(&entry.object_path))? else {
+            warn!(
+                "Skipping {}/{} in backup: ELF not found in storage",
+                entry.contract, entry.program_id
+            );
+            continue;
+        };
+        let mut entry = entry.clone();
+        let provenance_path = provenance_object_path(&entry.contract, &entry.program_id);
+        let provenance = match entry.provenance {
+            Some(_) => runtime.block_on(storage.read_object(&provenance_path))?,
+            None => None,
+        };
+        if entry.provenance.is_some() && provenance.is_none() {
+            warn!(
+                "Provenance of {}/{} not found in storage, exported without it",
+                entry.contract, entry.program_id
+            );
+            entry.provenance = None;
+        }
+        let mut artifacts = Vec::new();
+        let mut missing = Vec::new();
+        for name in entry.artifacts.keys() {
+            let artifact_path = artifact_object_path(&entry.contract, &entry.program_id, name);
+            match runtime.block_on(storage.read_object(&artifact_path))? {
+                Some(bytes) => artifacts.push((artifact_path, bytes)),
+                None => {
+                    warn!(
+                        "Artifact {name} of {}/{} not found in storage, exported without it",
+                        entry.contract, entry.program_id
+                    );
+                    missing.push(name.clone());
+                }
+            }
+        }
+        for name in missing {
+            entry.artifacts.remove(&name);
+        }
+        let metadata = serde_json::to_vec(&entry).context("serializing metadata")?;
+        archive.append(&entry.object_path, &elf)?;
+        archive.append(&entry.metadata_path, &metadata)?;
+        if let Some(provenance) = provenance {
+            archive.append(&provenance_path, &provenance)?;
+        }
+        for (artifact_path, bytes) in &artifacts {
+            archive.append(artifact_path, bytes)?;
+        }
+        exported
+            .contracts
+            .entry(entry.contract.clone())
+            .or_default()
+            .programs
+            .insert(entry.program_id.clone(), entry);
+    }
+
+    // Contracts keep their owner and visibility, with or without programs.
+    for (contract, settings) in index.settings() {
+        let contract_entry = exported.contracts.entry(contract).or_default();
+        contract_entry.ownership = settings.ownership;
+        contract_entry.private = settings.private;
+    }
+    let index_bytes = serde_json::to_vec(&exported).context("serializing index")?;
+    archive.append(INDEX_FILE_NAME, &index_bytes)?;
+    let manifest = BackupManifest {
+        version: MANIFEST_VERSION,
+        created_at: Utc::now().to_rfc3339(),
+        programs: exported
+            .contracts
+            .values()
+            .map(|contract_entry| contract_entry.programs.len())
+            .sum(),
+        files: archive.digests.clone(),
+    };
+    archive.finish_with(
+        MANIFEST_PATH,
+        &serde_json::to_vec_pretty(&manifest).context("serializing manifest")?,
+    )?;
+    Ok(manifest)
+}
+
+/// Manifest of a contract archive, written after the binaries it describes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractArchiveManifest {
+    pub contract: String,
+    pub created_at: String,
+    pub programs: Vec<ArchivedProgram>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedProgram {
+    pub program_id: String,
+    /// Path of the ELF in the archive.
+    pub file: String,
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub uploaded_at: String,
+    pub metadata: ProgramMetadata,
+}
+
+/// Writes the ELFs of `programs` as `:program_hash.elf`, then
+/// [`MANIFEST_PATH`] mapping them to their program ids, metadata and digests.
+/// Programs deleted in the meantime are left out.
+///
+/// Blocking: run it on a blocking thread, `runtime` is used for storage reads.
+pub fn export_contract_archive(
+    registry: &RegistryService,
+    runtime: &Handle,
+    contract: &str,
+    programs: Vec<ProgramInfo>,
+    compression: Compression,
+    writer: impl Write,
+) -> Result<ContractArchiveManifest> {
+    // Read from storage, like full backups, so that exports neither fill the
+    // cache nor count as downloads.
+    let storage = registry.storage();
+    write_compressed(compression, writer, |writer| {
+        let mut archive = ArchiveWriter::new(writer);
+        let mut archived = Vec::with_capacity(programs.len());
+        for program in programs {
+            let object_path = binary_object_path(contract, &program.program_id);
+            let Some(elf) = runtime.block_on(storage.read_object(&object_path))? else {
+                warn!(
+                    "Skipping {contract}/{} in archive: ELF not found",
+                    program.program_id
+                );
+                continue;
+            };
+            let file = format!("{}.elf", program_id_digest(&program.program_id));
+            archive.append(&file, &elf)?;
+            let digest = file_digest(&elf);
+            archived.push(ArchivedProgram {
+                program_id: program.program_id,
+                file,
+                sha256: digest.sha256,
+                size_bytes: digest.size,
+                uploaded_at: program.uploaded_at,
+                metadata: program.metadata,
+            });
+        }
+        let manifest = ContractArchiveManifest {
+            contract: contract.to_string(),
+            created_at: Utc::now().to_rfc3339(),
+            programs: archived,
+        };
+        archive.finish_with(
+            MANIFEST_PATH,
+            &serde_json::to_vec_pretty(&manifest).context("serializing manifest")?,
+        )?;
+        Ok(manifest)
+    })
+}
+
+/// Tar writer recording the digest of every file it appends.
+pub struct ArchiveWriter<W: Write> {
+    builder: tar::Builder<W>,
+    pub digests: BTreeMap<String, FileDigest>,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            builder: tar::Builder::new(writer),
+            digests: BTreeMap::new(),
+        }
+    }
+
+    pub fn append(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        self.append_untracked(path, data)?;
+        self.digests.insert(path.to_string(), file_digest(data));
+        Ok(())
+    }
+
+    fn append_untracked(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Utc::now().timestamp().max(0) as u64);
+        self.builder
+            .append_data(&mut header, path, data)
+            .with_context(|| format!("adding {path} to archive"))
+    }
+
+    /// Appends a last file, not recorded in the digests, and closes the archive.
+    pub fn finish_with(
+        mut self,
+        path: &str,
+        data: &[u8],
+    ) -> Result<(BTreeMap<String, FileDigest>, W)> {
+        self.append_untracked(path, data)?;
+        let writer = self.builder.into_inner().context("closing archive")?;
+        Ok((self.digests, writer))
+    }
+}
+
+pub fn file_digest(data: &[u8]) -> FileDigest {
+    FileDigest {
+        sha256: hex::encode(Sha256::digest(data)),
+        size: data.len() as u64,
+    }
+}
+
+/// Streams the bytes an archive writer produces on a blocking thread, so
+/// archives are sent as they are built instead of being buffered.
+pub fn spawn_archive_stream<F>(write: F) -> impl Stream<Item = std::io::Result<Bytes>>
+where
+    F: FnOnce(ChunkWriter) -> Result<()> + Send + 'static,
+{
+    let (sender, mut receiver) = mpsc::channel(16);
+    let errors = sender.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = write(ChunkWriter::new(sender)) {
+            warn!("Streaming archive failed: {err:#}");
+            // Makes the response fail rather than end with a truncated archive.
+            let _ = errors.blocking_send(Err(std::io::Error::other(format!("{err:#}"))));
+        }
+    });
+    async_stream::stream! {
+        while let Some(chunk) = receiver.recv().await {
+            yield chunk;
+        }
+    }
+}
+
+/// Blocking writer sending its output in chunks over a channel.
+pub struct ChunkWriter {
+    sender: mpsc::Sender<std::io::Result<Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl ChunkWriter {
+    fn new(sender: mpsc::Sender<std::io::Result<Bytes>>) -> Self {
+        Self {
+            sender,
+            buffer: Vec::with_capacity(STREAM_CHUNK_SIZE),
+        }
+    }
+
+    fn send_buffer(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(
+            &mut self.buffer,
+            Vec::with_capacity(STREAM_CHUNK_SIZE),
+        ));
+        self.sender
+            .blocking_send(Ok(chunk))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client went away"))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() >= STREAM_CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+/// Opens a tar archive, compressed with zstd or not.
+fn open_archive(path: &Path) -> Result<tar::Archive<Box<dyn Read>>> {
+    let mut file = BufReader::new(
+        File::open(path).with_context(|| format!("opening archive {}", path.display()))?,
+    );
+    let compressed = file.fill_buf()?.starts_with(&ZSTD_MAGIC);
+    let reader: Box<dyn Read> = if compressed {
+        Box::new(zstd::Decoder::with_buffer(file).context("starting zstd")?)
+    } else {
+        Box::new(file)
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+/// Checks every file of the archive against the manifest and returns the
+/// archived programs, keyed by the path of their ELF.
+fn scan_archive(
+    path: &Path,
+) -> Result<(
+    HashMap<String, ProgramEntry>,
+    BTreeMap<String, ContractSettings>,
+)> {
+    let mut archive = open_archive(path)?;
+    let mut digests = BTreeMap::new();
+    let mut metadata = Vec::new();
+    let mut manifest = None;
+    let mut settings = BTreeMap::new();
+    for file in archive.entries().context("reading archive")? {
+        let mut file = file.context("reading archive")?;
+        if !file.header().entry_type().is_file() {
+            continue;
+        }
+        let path = file.path()?.to_string_lossy().into_owned();
+        if path == MANIFEST_PATH {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            manifest =
+                Some(serde_json::from_slice::<BackupManifest>(&bytes).context("parsing manifest")?);
+            continue;
+        }
+
+        let is_metadata = path.ends_with(".json")
+            && !path.ends_with(PROVENANCE_SUFFIX)
+            && path != INDEX_FILE_NAME;
+        let digest = if path == INDEX_FILE_NAME {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            settings = serde_json::from_slice::<IndexFile>(&bytes)
+                .context("parsing index")?
+                .settings();
+            file_digest(&bytes)
+        } else if is_metadata {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            let entry: ProgramEntry = serde_json::from_slice(&bytes)
+                .with_context(|| format!("parsing metadata {path}"))?;
+            metadata.push((path.clone(), entry));
+            file_digest(&bytes)
+        } else {
+            let mut hasher = Sha256::new();
+            let size = std::io::copy(&mut file, &mut hasher)?;
+            FileDigest {
+                sha256: hex::encode(hasher.finalize()),
+                size,
+            }
+        };
+        digests.insert(path, digest);
+    }
+
+    let manifest = manifest.ok_or_else(|| anyhow!("archive has no {MANIFEST_PATH}"))?;
+    if manifest.version != MANIFEST_VERSION {
+        bail!("unsupported backup version {}", manifest.version);
+    }
+    for (path, digest) in &digests {
+        match manifest.files.get(path) {
+            Some(expected) if expected == digest => {}
+            Some(_) => bail!("digest mismatch for {path}"),
+            None => bail!("{path} is not listed in the manifest"),
+        }
+    }
+    if let Some(path) = manifest
+        .files
+        .keys()
+        .find(|path| !digests.contains_key(*path))
+    {
+        bail!("{path} is listed in the manifest but missing from the archive");
+    }
+
+    let mut programs = HashMap::new();
+    for (metadata_path, mut entry) in metadata {
+        sdk::ContractName(entry.contract.clone())
+            .validate()
+            .map_err(|err| anyhow!("invalid contract {}: {err}", entry.contract))?;
+        if is_reserved_contract(&entry.contract) {
+            bail!("contract name {} is reserved", entry.contract);
+        }
+        // The manifest only vouches for the files, not for what the metadata
+        // claims about them.
+        if metadata_path != metadata_object_path(&entry.contract, &entry.program_id)
+            || entry.object_path != binary_object_path(&entry.contract, &entry.program_id)
+        {
+            bail!(
+                "{metadata_path} does not match the path of {}/{}",
+                entry.contract,
+                entry.program_id
+            );
+        }
+        let Some(elf) = digests.get(&entry.object_path) else {
+            bail!(
+                "ELF of {}/{} is missing from the archive",
+                entry.contract,
+                entry.program_id
+            );
+        };
+        if elf.size != entry.size_bytes
+            || entry
+                .sha256
+                .as_ref()
+                .is_some_and(|sha256| !sha256.eq_ignore_ascii_case(&elf.sha256))
+        {
+            bail!(
+                "ELF of {}/{} does not match its metadata",
+                entry.contract,
+                entry.program_id
+            );
+        }
+        // Backups made before digests were recorded get theirs here.
+        entry.sha256 = Some(elf.sha256.clone());
+        if entry.provenance.is_some()
+            && !digests.contains_key(&provenance_object_path(&entry.contract, &entry.program_id))
+        {
+            bail!(
+                "provenance of {}/{} is missing from the archive",
+                entry.contract,
+                entry.program_id
+            );
+        }
+        for name in entry.artifacts.keys() {
+            validate_artifact_name(name)
+                .map_err(|err| anyhow!("{}/{}: {err}", entry.contract, entry.program_id))?;
+        }
+        if let Some(name) = entry.artifacts.keys().find(|name| {
+            !digests.contains_key(&artifact_object_path(
+                &entry.contract,
+                &entry.program_id,
+                name,
+            ))
+        }) {
+            bail!(
+                "artifact {name} of {}/{} is missing from the archive",
+                entry.contract,
+                entry.program_id
+            );
+        }
+        programs.insert(entry.object_path.clone(), entry);
+    }
+    for contract in settings.keys() {
+        sdk::ContractName(contract.clone())
+            .validate()
+            .map_err(|err| anyhow!("invalid contract {contract}: {err}"))?;
+        if is_reserved_contract(contract) {
+            bail!("contract name {contract} is reserved");
+        }
+    }
+    Ok((programs, settings))
+}
+
+/// Validates the archive and computes what importing it changes.
+pub async fn plan_import(
+    registry: &RegistryService,
+    archive: &Path,
+    mode: ImportMode,
+) -> Result<ImportPlan> {
+    let path = archive.to_path_buf();
+    let (programs, settings) = tokio::task::spawn_blocking(move || scan_archive(&path))
+        .await
+        .context("scanning archive")??;
+
+    let current = registry.index_snapshot().await;
+    let current = current
+        .contracts
+        .values()
+        .flat_map(|contract_entry| contract_entry.programs.values())
+        .map(ProgramKey::of)
+        .collect::<HashSet<_>>();
+    let archived = programs
+        .values()
+        .map(ProgramKey::of)
+        .collect::<HashSet<_>>();
+
+    let (mut overwritten, mut added): (Vec<_>, Vec<_>) = archived
+        .iter()
+        .cloned()
+        .partition(|key| current.contains(key));
+    let mut removed = match mode {
+        ImportMode::Merge => Vec::new(),
+        ImportMode::Replace => current.difference(&archived).cloned().collect(),
+    };
+    added.sort();
+    overwritten.sort();
+    removed.sort();
+
+    Ok(ImportPlan {
+        mode,
+        archive: archive.to_path_buf(),
+        programs,
+        settings,
+        added,
+        overwritten,
+        removed,
+    })
+}
+
+/// Stores the archived binaries, then updates the registry index.
+pub async fn apply_import(registry: &RegistryService, plan: ImportPlan) -> Result<ImportReport> {
+    let report = plan.report(false);
+    let mut targets = HashMap::new();
+    for (archived_path, entry) in &plan.programs {
+        targets.insert(
+            archived_path.clone(),
+            binary_object_path(&entry.contract, &entry.program_id),
+        );
+        if entry.provenance.is_some() {
+            let provenance_path = provenance_object_path(&entry.contract, &entry.program_id);
+            targets.insert(provenance_path.clone(), provenance_path);
+        }
+        for name in entry.artifacts.keys() {
+            let artifact_path = artifact_object_path(&entry.contract, &entry.program_id, name);
+            targets.insert(artifact_path.clone(), artifact_path);
+        }
+    }
+
+    let storage = registry.storage();
+    let runtime = Handle::current();
+    let archive = plan.archive.clone();
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let mut archive = open_archive(&archive)?;
+        for file in archive.entries().context("reading archive")? {
+            let mut file = file.context("reading archive")?;
+            let path = file.path()?.to_string_lossy().into_owned();
+            let Some(target) = targets.get(&path) else {
+                continue;
+            };
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            runtime
+                .block_on(storage.write_object(target, &bytes))
+                .with_context(|| format!("storing {target}"))?;
+        }
+        Ok(())
+    })
+    .await
+    .context("storing archived binaries")??;
+
+    let imported = plan
+        .programs
+        .into_values()
+        .map(|entry| ProgramEntry {
+            object_path: binary_object_path(&entry.contract, &entry.program_id),
+            metadata_path: metadata_object_path(&entry.contract, &entry.program_id),
+            ..entry
+        })
+        .collect();
+    let removed = plan
+        .removed
+        .into_iter()
+        .map(|key| (key.contract, key.program_id))
+        .collect();
+    registry
+        .apply_import(imported, removed, plan.settings)
+        .await?;
+    Ok(report)
+}
+
+/// Validates and imports an archive, or only reports the changes on `dry_run`.
+pub async fn import_archive(
+    registry: &RegistryService,
+    archive: &Path,
+    mode: ImportMode,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let plan = plan_import(registry, archive, mode).await?;
+    if dry_run {
+        return Ok(plan.report(true));
+    }
+    apply_import(registry, plan).await
+}
+
+/// Exports to a file from async code.
+pub async fn export_to_file(
+    registry: Arc<RegistryService>,
+    path: &Path,
+    compression: Compression,
+) -> Result<BackupManifest> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let runtime = Handle::current();
+    tokio::task::spawn_blocking(move || {
+        export_archive(
+            &registry,
+            &runtime,
+            compression,
+            std::io::BufWriter::new(file),
+        )
+    })
+    .await
+    .context("exporting archive")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::ArtifactUpload;
+    use crate::conf::Conf;
+    use crate::metrics::RegistryMetrics;
+    use crate::provenance::Provenance;
+    use crate::registry::UploadAttachments;
+    use crate::storage::LocalStorageBackend;
+
+    async fn make_registry(root: &Path) -> Arc<RegistryService> {
+        RegistryService::with_storage(
+            &Conf::default(),
+            Arc::new(LocalStorageBackend::new(root.to_path_buf())),
+            RegistryMetrics::unregistered().expect("metrics"),
+        )
+        .await
+        .expect("registry")
+    }
+
+    async fn upload(registry: &RegistryService, contract: &str, program_id: &str, elf: &[u8]) {
+        registry
+            .upload(
+                contract,
+                program_id,
+                ProgramMetadata {
+                    toolchain: None,
+                    commit: None,
+                    zkvm: "sp1".to_string(),
+                },
+                Bytes::copy_from_slice(elf),
+            )
+            .await
+            .expect("upload");
+    }
+
+    fn keys(keys: &[ProgramKey]) -> Vec<String> {
+        keys.iter()
+            .map(|key| format!("{}/{}", key.contract, key.program_id))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn export_then_import_merges_or_replaces() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let source = make_registry(&dir.path().join("source")).await;
+        upload(&source, "orders", "p1", b"elf-1").await;
+        let provenance = Provenance::parse(r#"{"source_repo": "repo", "source_ref": "main"}"#)
+            .expect("provenance");
+        source
+            .upload_with(
+                "wallet",
+                "p2",
+                ProgramMetadata {
+                    toolchain: None,
+                    commit: None,
+                    zkvm: "sp1".to_string(),
+                },
+                UploadAttachments {
+                    provenance: Some(provenance.clone()),
+                    artifacts: vec![
+                        ArtifactUpload::new("vk", None, Bytes::from_static(b"vk-2")).expect("vk")
+                    ],
+                    ..Default::default()
+                },
+                Bytes::from_static(b"elf-2"),
+            )
+            .await
+            .expect("upload with provenance");
+        let archive = dir.path().join("backup.tar.zst");
+        let manifest = export_to_file(source, &archive, Compression::Zstd)
+            .await
+            .expect("export");
+        assert_eq!(manifest.programs, 2);
+
+        let target = make_registry(&dir.path().join("target")).await;
+        upload(&target, "orders", "p1", b"old").await;
+        upload(&target, "tokens", "p3", b"elf-3").await;
+
+        let report = import_archive(&target, &archive, ImportMode::Replace, true)
+            .await
+            .expect("dry run");
+        assert_eq!(keys(&report.added), vec!["wallet/p2"]);
+        assert_eq!(keys(&report.overwritten), vec!["orders/p1"]);
+        assert_eq!(keys(&report.removed), vec!["tokens/p3"]);
+        assert!(target
+            .download("wallet", "p2")
+            .await
+            .expect("download")
+            .is_none());
+
+        import_archive(&target, &archive, ImportMode::Merge, false)
+            .await
+            .expect("merge");
+        assert_eq!(
+            &target
+                .download("orders", "p1")
+                .await
+                .expect("download")
+                .expect("elf")[..],
+            b"elf-1"
+        );
+        assert!(target.list_contract("tokens").await.is_some());
+
+        import_archive(&target, &archive, ImportMode::Replace, false)
+            .await
+            .expect("replace");
+        assert!(target.list_contract("tokens").await.is_none());
+        assert_eq!(
+            &target
+                .download("wallet", "p2")
+                .await
+                .expect("download")
+                .expect("elf")[..],
+            b"elf-2"
+        );
+        assert_eq!(
+            target.provenance("wallet", "p2").await.expect("provenance"),
+            Some(provenance)
+        );
+        let (_, vk) = target
+            .artifact("wallet", "p2", "vk")
+            .await
+            .expect("artifact")
+            .expect("vk");
+        assert_eq!(&vk[..], b"vk-2");
+    }
+
+    #[tokio::test]
+    async fn imported_programs_are_recorded_in_the_transparency_log() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let source = make_registry(&dir.path().join("source")).await;
+        upload(&source, "orders", "p1", b"elf-1").await;
+        let logged = source.transparency().leaves(0, 10).await;
+        let archive = dir.path().join("backup.tar.zst");
+        export_to_file(source, &archive, Compression::Zstd)
+            .await
+            .expect("export");
+
+        let target = make_registry(&dir.path().join("target")).await;
+        upload(&target, "tokens", "p3", b"elf-3").await;
+        import_archive(&target, &archive, ImportMode::Merge, false)
+            .await
+            .expect("import");
+        let proof = target
+            .transparency()
+            .inclusion_proof("orders", "p1", None)
+            .await
+            .expect("proof")
+            .expect("logged");
+        assert_eq!(proof.leaf, logged[0]);
+        assert_eq!(target.transparency().size().await, 2);
+
+        // Importing the same programs again logs nothing new.
+        import_archive(&target, &archive, ImportMode::Replace, false)
+            .await
+            .expect("import again");
+        assert_eq!(target.transparency().size().await, 2);
+    }
+
+    #[tokio::test]
+    async fn backups_keep_contract_ownership_and_visibility() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let source = make_registry(&dir.path().join("source")).await;
+        upload(&source, "orders", "p1", b"elf-1").await;
+        upload(&source, "wallet", "p2", b"elf-2").await;
+        source
+            .claim_for_upload("orders", "alice")
+            .await
+            .expect("claim");
+        source.set_private("orders", true).await.expect("hide");
+        source.set_private("hidden", true).await.expect("hide");
+        let archive = dir.path().join("backup.tar.zst");
+        export_to_file(source, &archive, Compression::Zstd)
+            .await
+            .expect("export");
+
+        let target = make_registry(&dir.path().join("target")).await;
+        upload(&target, "wallet", "p3", b"elf-3").await;
+        target
+            .claim_for_upload("wallet", "carol")
+            .await
+            .expect("claim");
+        target.set_private("wallet", true).await.expect("hide");
+        import_archive(&target, &archive, ImportMode::Replace, false)
+            .await
+            .expect("import");
+        assert!(target.is_private("orders").await);
+        assert!(target.is_private("hidden").await);
+        assert_eq!(
+            target.ownership("orders").await.map(|owner| owner.owner),
+            Some("alice".to_string())
+        );
+        // An import never makes a contract public nor changes its owner.
+        assert!(target.is_private("wallet").await);
+        assert_eq!(
+            target.ownership("wallet").await.map(|owner| owner.owner),
+            Some("carol".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn contract_archive_lists_selected_programs_with_digests() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let registry = make_registry(dir.path()).await;
+        upload(&registry, "orders", "p1", b"elf-1").await;
+        upload(&registry, "orders", "p2", b"elf-2").await;
+        upload(&registry, "orders", "p3", b"elf-3").await;
+        let programs = registry
+            .list_contract("orders")
+            .await
+            .expect("orders")
+            .into_iter()
+            .filter(|program| program.program_id != "p2")
+            .collect::<Vec<_>>();
+
+        let registry_ref = registry.clone();
+        let bytes = tokio::task::spawn_blocking(move || {
+            let mut bytes = Vec::new();
+            export_contract_archive(
+                &registry_ref,
+                &Handle::current(),
+                "orders",
+                programs,
+                Compression::Zstd,
+                &mut bytes,
+            )
+            .map(|_| bytes)
+        })
+        .await
+        .expect("join")
+        .expect("archive");
+
+        let decoded = zstd::decode_all(&bytes[..]).expect("zstd");
+        let mut files = HashMap::new();
+        for file in tar::Archive::new(&decoded[..]).entries().expect("entries") {
+            let mut file = file.expect("entry");
+            let path = file.path().expect("path").to_string_lossy().into_owned();
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).expect("read");
+            files.insert(path, content);
+        }
+        let manifest: ContractArchiveManifest =
+            serde_json::from_slice(&files[MANIFEST_PATH]).expect("manifest");
+        let mut archived = manifest
+            .programs
+            .iter()
+            .map(|program| program.program_id.as_str())
+            .collect::<Vec<_>>();
+        archived.sort();
+        assert_eq!(archived, vec!["p1", "p3"]);
+        for program in &manifest.programs {
+            let elf = &files[&program.file];
+            assert_eq!(file_digest(elf).sha256, program.sha256);
+            assert_eq!(elf.len() as u64, program.size_bytes);
+        }
+        assert_eq!(files.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn rejects_archives_not_matching_their_manifest() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let registry = make_registry(&dir.path().join("registry")).await;
+        let entry = ProgramEntry {
+            program_id: "p1".to_string(),
+            contract: "orders".to_string(),
+            object_path: binary_object_path("orders", "p1"),
+            metadata_path: metadata_object_path("orders", "p1"),
+            size_bytes: 3,
+            uploaded_at: Utc::now().to_rfc3339(),
+            metadata: ProgramMetadata {
+                toolchain: None,
+                commit: None,
+                zkvm: "sp1".to_string(),
+            },
+            sha256: None,
+            signature: None,
+            provenance: None,
+            artifacts: Default::default(),
+            uploaded_by: None,
+            oidc_claims: Default::default(),
+        };
+
+        let mut writer = ArchiveWriter::new(Vec::new());
+        writer
+            .append(
+                &entry.metadata_path,
+                &serde_json::to_vec(&entry).expect("json"),
+            )
+            .expect("append");
+        writer.append(&entry.object_path, b"elf").expect("append");
+        let mut files = writer.digests.clone();
+        files.insert(entry.object_path.clone(), file_digest(b"tampered"));
+        let manifest = BackupManifest {
+            version: MANIFEST_VERSION,
+            created_at: Utc::now().to_rfc3339(),
+            programs: 1,
+            files,
+        };
+        let (_, bytes) = writer
+            .finish_with(MANIFEST_PATH, &serde_json::to_vec(&manifest).expect("json"))
+            .expect("finish");
+        let archive = dir.path().join("backup.tar");
+        std::fs::write(&archive, bytes).expect("write");
+
+        let err = import_archive(&registry, &archive, ImportMode::Merge, false)
+            .await
+            .expect_err("digest mismatch");
+        assert!(format!("{err:#}").contains("digest mismatch"));
+        assert!(registry.list_contract("orders").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_metadata_not_matching_its_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let registry = make_registry(&dir.path().join("registry")).await;
+        let elf = b"elf";
+        let entry = ProgramEntry {
+            program_id: "p1".to_string(),
+            contract: "orders".to_string(),
+            object_path: binary_object_path("orders", "p1"),
+            metadata_path: metadata_object_path("orders", "p1"),
+            size_bytes: elf.len() as u64,
+            uploaded_at: Utc::now().to_rfc3339(),
+            metadata: ProgramMetadata {
+                toolchain: None,
+                commit: None,
+                zkvm: "sp1".to_string(),
+            },
+            sha256: Some(file_digest(elf).sha256),
+            signature: None,
+            provenance: None,
+            artifacts: Default::default(),
+            uploaded_by: None,
+            oidc_claims: Default::default(),
+        };
+        // Every archive is consistent with its own manifest.
+        let write_archive = |name: &str, entry: &ProgramEntry| {
+            let mut writer = ArchiveWriter::new(Vec::new());
+            writer
+                .append(
+                    &entry.metadata_path,
+                    &serde_json::to_vec(entry).expect("json"),
+                )
+                .expect("append");
+            writer.append(&entry.object_path, elf).expect("append");
+            let manifest = BackupManifest {
+                version: MANIFEST_VERSION,
+                created_at: Utc::now().to_rfc3339(),
+                programs: 1,
+                files: writer.digests.clone(),
+            };
+            let (_, bytes) = writer
+                .finish_with(MANIFEST_PATH, &serde_json::to_vec(&manifest).expect("json"))
+                .expect("finish");
+            let archive = dir.path().join(name);
+            std::fs::write(&archive, bytes).expect("write");
+            archive
+        };
+
+        let wrong_size = ProgramEntry {
+            size_bytes: 4,
+            ..entry.clone()
+        };
+        let wrong_sha256 = ProgramEntry {
+            sha256: Some(file_digest(b"other").sha256),
+            ..entry.clone()
+        };
+        // Metadata of "wallet", stored under the paths of "orders".
+        let other_contract = ProgramEntry {
+            contract: "wallet".to_string(),
+            ..entry.clone()
+        };
+        for (name, entry, error) in [
+            ("size.tar", &wrong_size, "does not match its metadata"),
+            ("sha.tar", &wrong_sha256, "does not match its metadata"),
+            ("moved.tar", &other_contract, "does not match the path"),
+        ] {
+            let archive = write_archive(name, entry);
+            let err = import_archive(&registry, &archive, ImportMode::Merge, true)
+                .await
+                .expect_err(name);
+            assert!(format!("{err:#}").contains(error), "{name}: {err:#}");
+        }
+        assert!(registry.list_contract("orders").await.is_none());
+        assert!(registry.list_contract("wallet").await.is_none());
+
+        let archive = write_archive("valid.tar", &entry);
+        let report = import_archive(&registry, &archive, ImportMode::Merge, false)
+            .await
+            .expect("import");
+        assert_eq!(report.added.len(), 1);
+    }
+}
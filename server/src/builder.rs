@@ -0,0 +1,196 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use axum::{extract::DefaultBodyLimit, Router};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::task::JoinHandle;
+
+use crate::app::{api_router, RouterCtx};
+use crate::chain::{spawn_chain_tracker, NodeClient};
+use crate::conf::Conf;
+use crate::metrics::RegistryMetrics;
+use crate::registry::{create_storage_backend, RegistryService};
+use crate::storage::StorageBackend;
+
+/// Builds a registry to embed in another process, either by mounting its
+/// routes on an existing router or by serving it on its own port.
+///
+/// `Conf::new(vec![])` gives the default configuration.
+pub struct RegistryBuilder {
+    config: Conf,
+    storage: Option<Arc<dyn StorageBackend>>,
+    register_metrics: bool,
+}
+
+impl RegistryBuilder {
+    pub fn new(config: Conf) -> Self {
+        Self {
+            config,
+            storage: None,
+            register_metrics: true,
+        }
+    }
+
+    /// Uses this backend instead of the one selected by `storage_backend`.
+    pub fn storage(mut self, storage: Arc<dyn StorageBackend>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Whether metrics are registered in the default prometheus registry
+    /// (the default). Disable it to run several registries in one process.
+    pub fn register_metrics(mut self, register: bool) -> Self {
+        self.register_metrics = register;
+        self
+    }
+
+    pub async fn build(self) -> Result<RegistryApp> {
+        let storage = match self.storage {
+            Some(storage) => storage,
+            None => create_storage_backend(&self.config).await?,
+        };
+        let metrics = if self.register_metrics {
+            RegistryMetrics::new()?
+        } else {
+            RegistryMetrics::unregistered()?
+        };
+        let registry = RegistryService::with_storage(&self.config, storage, metrics).await?;
+        let node_url = self
+            .config
+            .node_url
+            .as_deref()
+            .filter(|url| !url.trim().is_empty());
+        if let Some(node_url) = node_url {
+            let node = NodeClient::new(node_url)?;
+            registry.set_chain_programs(Default::default()).await;
+            spawn_chain_tracker(
+                registry.clone(),
+                node,
+                Duration::from_secs(self.config.chain_status_refresh_secs.max(1)),
+            );
+        }
+        let state = RouterCtx::new(registry.clone(), &self.config).await?;
+        Ok(RegistryApp {
+            registry,
+            state,
+            max_body_size: self.config.rest_server_max_body_size,
+        })
+    }
+}
+
+pub struct RegistryApp {
+    registry: Arc<RegistryService>,
+    state: RouterCtx,
+    max_body_size: usize,
+}
+
+impl RegistryApp {
+    pub fn registry(&self) -> &Arc<RegistryService> {
+        &self.registry
+    }
+
+    /// The registry routes, to serve or merge into another router.
+    pub fn router(&self) -> Router {
+        api_router(self.state.clone())
+    }
+
+    /// Adds the registry routes to `router`.
+    pub fn mount(&self, router: Router) -> Router {
+        router.merge(self.router())
+    }
+
+    /// Serves the registry on an ephemeral localhost port.
+    pub async fn spawn(&self) -> Result<SpawnedRegistry> {
+        self.spawn_on("127.0.0.1:0").await
+    }
+
+    /// Serves the registry on `addr`, applying `rest_server_max_body_size`
+    /// (0 means no limit).
+    pub async fn spawn_on(&self, addr: impl ToSocketAddrs) -> Result<SpawnedRegistry> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .context("binding registry listener")?;
+        let addr = listener.local_addr()?;
+        let body_limit = match self.max_body_size {
+            0 => DefaultBodyLimit::disable(),
+            max_body_size => DefaultBodyLimit::max(max_body_size),
+        };
+        let router = self.router().layer(body_limit);
+        let handle = tokio::spawn(async move {
+            axum::serve(listener, router)
+                .await
+                .context("serving registry")
+        });
+        Ok(SpawnedRegistry { addr, handle })
+    }
+}
+
+/// Registry served by a background task, stopped when dropped.
+pub struct SpawnedRegistry {
+    addr: SocketAddr,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl SpawnedRegistry {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+}
+
+impl Drop for SpawnedRegistry {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::ProgramMetadata;
+    use crate::storage::LocalStorageBackend;
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn spawned_registry_serves_uploaded_programs() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let config = Conf {
+            binary_cache_max_bytes: 1024,
+            event_log_capacity: 16,
+            ..Default::default()
+        };
+        let app = RegistryBuilder::new(config)
+            .storage(Arc::new(LocalStorageBackend::new(
+                temp_dir.path().to_path_buf(),
+            )))
+            .register_metrics(false)
+            .build()
+            .await
+            .expect("build");
+        app.registry()
+            .upload(
+                "orders",
+                "p1",
+                ProgramMetadata {
+                    toolchain: None,
+                    commit: None,
+                    zkvm: "sp1".to_string(),
+                },
+                Bytes::from_static(b"elf"),
+            )
+            .await
+            .expect("upload");
+
+        let server = app.spawn().await.expect("spawn");
+        let response = reqwest::get(format!("{}/api/elfs/orders/p1", server.url()))
+            .await
+            .expect("download");
+        assert!(response.status().is_success());
+        assert_eq!(&response.bytes().await.expect("body")[..], b"elf");
+    }
+}
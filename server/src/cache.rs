@@ -0,0 +1,299 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use serde::Serialize;
+use tracing::debug;
+
+/// In-memory cache of recently served binaries, bounded by the total size of
+/// the cached ELFs rather than by a number of entries.
+///
+/// Lookups only need `&self`: hit counts and recency are tracked with atomics
+/// so readers can share a lock, and the least recently used entries are
+/// evicted when an insert goes over budget.
+///
+/// Entries are queued by access time in `recency`. Lookups cannot move them
+/// in the queue, so eviction moves an entry accessed since it was queued to
+/// the back instead of evicting it; each lookup costs at most one such move.
+pub struct BinaryCache {
+    per_contract: HashMap<String, HashMap<String, CacheEntry>>,
+    recency: BTreeMap<u64, (String, String)>,
+    max_bytes: u64,
+    total_bytes: u64,
+    entries: usize,
+    clock: AtomicU64,
+}
+
+/// A cached binary and how often it was served from the cache.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CachedProgram {
+    pub contract: String,
+    pub program_id: String,
+    pub size_bytes: u64,
+    pub hits: u64,
+}
+
+struct CacheEntry {
+    bytes: Bytes,
+    hits: AtomicU64,
+    last_access: AtomicU64,
+    /// Key of the entry in `recency`.
+    queued_at: u64,
+}
+
+impl BinaryCache {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            per_contract: HashMap::new(),
+            recency: BTreeMap::new(),
+            max_bytes,
+            total_bytes: 0,
+            entries: 0,
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn get(&self, contract: &str, program_id: &str) -> Option<Bytes> {
+        let entry = self.per_contract.get(contract)?.get(program_id)?;
+        entry.hits.fetch_add(1, Ordering::Relaxed);
+        entry.last_access.store(self.tick(), Ordering::Relaxed);
+        Some(entry.bytes.clone())
+    }
+
+    /// Inserts a binary and evicts the least recently used entries until the
+    /// cache fits in its byte budget again. Returns the number of evictions.
+    /// Binaries larger than the whole budget are not cached.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self, bytes)))]
+    pub fn insert(&mut self, contract: &str, program_id: &str, bytes: Bytes) -> u64 {
+        self.remove_program(contract, program_id);
+        let size = bytes.len() as u64;
+        if size > self.max_bytes {
+            return 0;
+        }
+
+        let mut evictions = 0;
+        while self.total_bytes + size > self.max_bytes {
+            if !self.evict_least_recently_used() {
+                break;
+            }
+            evictions += 1;
+        }
+
+        let tick = self.tick();
+        let entry = CacheEntry {
+            bytes,
+            hits: AtomicU64::new(0),
+            last_access: AtomicU64::new(tick),
+            queued_at: tick,
+        };
+        self.recency
+            .insert(tick, (contract.to_string(), program_id.to_string()));
+        self.per_contract
+            .entry(contract.to_string())
+            .or_default()
+            .insert(program_id.to_string(), entry);
+        self.total_bytes += size;
+        self.entries += 1;
+        evictions
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn remove_program(&mut self, contract: &str, program_id: &str) {
+        let Some(programs) = self.per_contract.get_mut(contract) else {
+            return;
+        };
+        if let Some(entry) = programs.remove(program_id) {
+            self.recency.remove(&entry.queued_at);
+            self.total_bytes -= entry.bytes.len() as u64;
+            self.entries -= 1;
+        }
+        if programs.is_empty() {
+            self.per_contract.remove(contract);
+        }
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn remove_contract(&mut self, contract: &str) {
+        if let Some(programs) = self.per_contract.remove(contract) {
+            for entry in programs.values() {
+                self.recency.remove(&entry.queued_at);
+                self.total_bytes -= entry.bytes.len() as u64;
+            }
+            self.entries -= programs.len();
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Cached binaries with the hits each got since it was cached, most hit first.
+    pub fn entry_stats(&self) -> Vec<CachedProgram> {
+        let mut cached = self
+            .per_contract
+            .iter()
+            .flat_map(|(contract, programs)| {
+                programs.iter().map(|(program_id, entry)| CachedProgram {
+                    contract: contract.clone(),
+                    program_id: program_id.clone(),
+                    size_bytes: entry.bytes.len() as u64,
+                    hits: entry.hits.load(Ordering::Relaxed),
+                })
+            })
+            .collect::<Vec<_>>();
+        cached.sort_by(|a, b| {
+            b.hits
+                .cmp(&a.hits)
+                .then_with(|| (&a.contract, &a.program_id).cmp(&(&b.contract, &b.program_id)))
+        });
+        cached
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn evict_least_recently_used(&mut self) -> bool {
+        while let Some((queued_at, (contract, program_id))) = self.recency.pop_first() {
+            let Some(entry) = self
+                .per_contract
+                .get_mut(&contract)
+                .and_then(|programs| programs.get_mut(&program_id))
+            else {
+                continue;
+            };
+            let last_access = entry.last_access.load(Ordering::Relaxed);
+            if last_access > queued_at {
+                entry.queued_at = last_access;
+                self.recency.insert(last_access, (contract, program_id));
+                continue;
+            }
+            debug!(
+                contract = %contract,
+                program_id = %program_id,
+                size_bytes = entry.bytes.len(),
+                hits = entry.hits.load(Ordering::Relaxed),
+                "Evicting binary from cache"
+            );
+            self.remove_program(&contract, &program_id);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_when_over_budget() {
+        let mut cache = BinaryCache::new(10);
+        assert_eq!(cache.insert("orders", "a", Bytes::from_static(b"aaaa")), 0);
+        assert_eq!(cache.insert("orders", "b", Bytes::from_static(b"bbbb")), 0);
+        assert!(cache.get("orders", "a").is_some());
+
+        let evictions = cache.insert("wallet", "c", Bytes::from_static(b"cccc"));
+        assert_eq!(evictions, 1);
+        assert!(cache.get("orders", "b").is_none());
+        assert!(cache.get("orders", "a").is_some());
+        assert!(cache.get("wallet", "c").is_some());
+        assert_eq!(cache.total_bytes(), 8);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn recently_read_entries_survive_successive_evictions() {
+        let mut cache = BinaryCache::new(12);
+        for program_id in ["a", "b", "c"] {
+            cache.insert("orders", program_id, Bytes::from_static(b"xxxx"));
+        }
+        assert!(cache.get("orders", "a").is_some());
+        assert!(cache.get("orders", "b").is_some());
+
+        assert_eq!(cache.insert("orders", "d", Bytes::from_static(b"xxxx")), 1);
+        assert!(cache.get("orders", "c").is_none());
+        assert!(cache.get("orders", "a").is_some());
+
+        assert_eq!(cache.insert("orders", "e", Bytes::from_static(b"xxxx")), 1);
+        assert!(cache.get("orders", "b").is_none());
+        assert!(cache.get("orders", "a").is_some());
+        assert!(cache.get("orders", "d").is_some());
+        assert_eq!(cache.recency.len(), cache.len());
+    }
+
+    #[test]
+    fn reports_hits_per_entry() {
+        let mut cache = BinaryCache::new(100);
+        cache.insert("orders", "a", Bytes::from_static(b"aaaa"));
+        cache.insert("wallet", "b", Bytes::from_static(b"bb"));
+        for _ in 0..3 {
+            assert!(cache.get("wallet", "b").is_some());
+        }
+        assert!(cache.get("orders", "a").is_some());
+        assert!(cache.get("orders", "missing").is_none());
+
+        let hits = |cache: &BinaryCache| {
+            cache
+                .entry_stats()
+                .into_iter()
+                .map(|cached| (cached.program_id, cached.size_bytes, cached.hits))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hits(&cache),
+            vec![("b".to_string(), 2, 3), ("a".to_string(), 4, 1)]
+        );
+
+        // A new version starts counting again.
+        cache.insert("wallet", "b", Bytes::from_static(b"bbb"));
+        assert_eq!(
+            hits(&cache),
+            vec![("a".to_string(), 4, 1), ("b".to_string(), 3, 0)]
+        );
+    }
+
+    #[test]
+    fn skips_binaries_larger_than_budget() {
+        let mut cache = BinaryCache::new(4);
+        cache.insert("orders", "a", Bytes::from_static(b"aa"));
+        assert_eq!(
+            cache.insert("orders", "big", Bytes::from_static(b"toolarge")),
+            0
+        );
+        assert!(cache.get("orders", "big").is_none());
+        assert!(cache.get("orders", "a").is_some());
+        assert_eq!(cache.total_bytes(), 2);
+    }
+
+    #[test]
+    fn overwrite_and_removals_keep_byte_count_in_sync() {
+        let mut cache = BinaryCache::new(100);
+        cache.insert("orders", "a", Bytes::from_static(b"first"));
+        cache.insert("orders", "a", Bytes::from_static(b"second"));
+        cache.insert("orders", "b", Bytes::from_static(b"beta"));
+        cache.insert("wallet", "c", Bytes::from_static(b"gamma"));
+        assert_eq!(cache.total_bytes(), 15);
+
+        cache.remove_program("orders", "a");
+        assert_eq!(cache.total_bytes(), 9);
+
+        cache.remove_contract("orders");
+        assert_eq!(cache.total_bytes(), 5);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("wallet", "c").is_some());
+    }
+}
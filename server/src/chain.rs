@@ -0,0 +1,281 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::registry::RegistryService;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a stored program relates to the program registered on chain for its contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainStatus {
+    /// The program id currently registered on chain.
+    Active,
+    /// The contract is on chain with another program id.
+    Historical,
+    /// The contract's on-chain program is not known.
+    Unknown,
+}
+
+/// Program currently registered on chain for a contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OnChainProgram {
+    /// Lowercase hex, without `0x`.
+    pub program_id: String,
+    pub verifier: String,
+}
+
+impl OnChainProgram {
+    pub fn matches(&self, program_id: &str) -> bool {
+        normalize_program_id(program_id) == self.program_id
+    }
+}
+
+pub fn program_status(on_chain: Option<&OnChainProgram>, program_id: &str) -> ChainStatus {
+    match on_chain {
+        Some(on_chain) if on_chain.matches(program_id) => ChainStatus::Active,
+        Some(_) => ChainStatus::Historical,
+        None => ChainStatus::Unknown,
+    }
+}
+
+fn normalize_program_id(program_id: &str) -> String {
+    let program_id = program_id.trim();
+    program_id
+        .strip_prefix("0x")
+        .unwrap_or(program_id)
+        .to_lowercase()
+}
+
+/// On-chain state of a registry contract, shown in contract summaries.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContractChainInfo {
+    #[serde(flatten)]
+    pub program: OnChainProgram,
+    /// The on-chain program id has no ELF in the registry.
+    pub missing_elf: bool,
+}
+
+/// Contract as returned by the node, which serializes program ids either as
+/// a byte array or as a hex string.
+#[derive(Debug, Deserialize)]
+struct NodeContract {
+    program_id: NodeProgramId,
+    verifier: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum NodeProgramId {
+    Bytes(Vec<u8>),
+    Hex(String),
+}
+
+/// Minimal client of the Hyli node REST API.
+#[derive(Clone)]
+pub struct NodeClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl NodeClient {
+    pub fn new(base_url: &str) -> Result<Self> {
+        reqwest::Url::parse(base_url).with_context(|| format!("invalid node url {base_url}"))?;
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .context("building node http client")?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Program registered for `contract`, `None` when the contract is not on chain.
+    pub async fn contract_program(&self, contract: &str) -> Result<Option<OnChainProgram>> {
+        let response = self
+            .client
+            .get(format!("{}/v1/contract/{contract}", self.base_url))
+            .send()
+            .await
+            .context("querying node")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("node answered HTTP {}", response.status());
+        }
+        let contract: NodeContract = response.json().await.context("parsing node contract")?;
+        let program_id = match contract.program_id {
+            NodeProgramId::Bytes(bytes) => hex::encode(bytes),
+            NodeProgramId::Hex(program_id) => normalize_program_id(&program_id),
+        };
+        Ok(Some(OnChainProgram {
+            program_id,
+            verifier: contract.verifier,
+        }))
+    }
+}
+
+/// Queries the node for every contract of the registry and stores the
+/// result. Contracts that fail to resolve keep their previous status.
+pub async fn refresh_chain_status(registry: &RegistryService, node: &NodeClient) {
+    let mut programs = registry.chain_programs();
+    let contracts = registry.contract_names().await;
+    programs.retain(|contract, _| contracts.contains(contract));
+    for contract in contracts {
+        match node.contract_program(&contract).await {
+            Ok(Some(program)) => {
+                programs.insert(contract, program);
+            }
+            Ok(None) => {
+                programs.remove(&contract);
+            }
+            Err(err) => warn!("Failed to fetch on-chain program of {contract}: {err:#}"),
+        }
+    }
+    registry.set_chain_programs(programs).await;
+}
+
+/// Refreshes the on-chain status of the registry contracts every `interval`.
+pub fn spawn_chain_tracker(registry: Arc<RegistryService>, node: NodeClient, interval: Duration) {
+    info!(
+        "Tracking on-chain programs from {} every {interval:?}",
+        node.base_url
+    );
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            refresh_chain_status(&registry, &node).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::Conf;
+    use crate::metrics::RegistryMetrics;
+    use crate::registry::{ProgramMetadata, VisibleContracts};
+    use crate::storage::LocalStorageBackend;
+    use axum::{extract::Path, http::StatusCode, response::IntoResponse, routing::get, Json};
+    use bytes::Bytes;
+
+    /// Stand-in for the node REST API knowing two contracts.
+    async fn spawn_node() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let app = axum::Router::new().route(
+            "/v1/contract/{contract}",
+            get(|Path(contract): Path<String>| async move {
+                match contract.as_str() {
+                    "orders" => Json(serde_json::json!({
+                        "name": "orders",
+                        "program_id": [0xab, 0xcd],
+                        "verifier": "sp1-4",
+                    }))
+                    .into_response(),
+                    "tokens" => Json(serde_json::json!({
+                        "name": "tokens",
+                        "program_id": "0x2222",
+                        "verifier": "risc0-1",
+                    }))
+                    .into_response(),
+                    _ => StatusCode::NOT_FOUND.into_response(),
+                }
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn marks_programs_against_the_node() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let registry = RegistryService::with_storage(
+            &Conf::default(),
+            Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf())),
+            RegistryMetrics::unregistered().expect("metrics"),
+        )
+        .await
+        .expect("registry");
+        for (contract, program_id) in [
+            ("orders", "ABCD"),
+            ("orders", "0001"),
+            ("tokens", "1111"),
+            ("wallet", "3333"),
+        ] {
+            registry
+                .upload(
+                    contract,
+                    program_id,
+                    ProgramMetadata {
+                        toolchain: None,
+                        commit: None,
+                        zkvm: "sp1".to_string(),
+                    },
+                    Bytes::from_static(b"elf"),
+                )
+                .await
+                .expect("upload");
+        }
+        let listed = registry.list_contract("orders").await.expect("orders");
+        assert!(listed.iter().all(|program| program.chain_status.is_none()));
+
+        let node = NodeClient::new(&spawn_node().await).expect("client");
+        refresh_chain_status(&registry, &node).await;
+
+        let statuses = registry
+            .list_all(&VisibleContracts::all())
+            .await
+            .into_iter()
+            .flat_map(|(contract, programs)| {
+                programs
+                    .into_iter()
+                    .map(move |program| (contract.clone(), program))
+            })
+            .map(|(contract, program)| (contract, program.program_id, program.chain_status))
+            .collect::<Vec<_>>();
+        assert!(statuses.contains(&(
+            "orders".to_string(),
+            "ABCD".to_string(),
+            Some(ChainStatus::Active)
+        )));
+        assert!(statuses.contains(&(
+            "orders".to_string(),
+            "0001".to_string(),
+            Some(ChainStatus::Historical)
+        )));
+        assert!(statuses.contains(&(
+            "wallet".to_string(),
+            "3333".to_string(),
+            Some(ChainStatus::Unknown)
+        )));
+
+        let missing = registry
+            .contract_summaries(&VisibleContracts::all())
+            .await
+            .into_iter()
+            .map(|summary| {
+                (
+                    summary.contract,
+                    summary.on_chain.map(|on_chain| on_chain.missing_elf),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            missing,
+            vec![
+                ("orders".to_string(), Some(false)),
+                ("tokens".to_string(), Some(true)),
+                ("wallet".to_string(), None),
+            ]
+        );
+    }
+}
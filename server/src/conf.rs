@@ -1,18 +1,37 @@
 use config::{Config, Environment, File};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::PathBuf;
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+use crate::auth::Operation;
+
+/// Its `Debug` output, logged at startup, redacts secrets.
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Conf {
     pub id: String,
     /// The log format to use - "json", "node" or "full" (default)
     pub log_format: String,
     /// Directory name to store node state.
     pub data_directory: PathBuf,
-    /// API key used to authenticate uploads.
+    /// API key allowed to upload to and read every contract (empty disables it).
     pub api_key: String,
-    /// API key used to authenticate admin actions (deletes).
+    /// API key allowed to delete from and read every contract, and perform admin actions (empty disables it).
     pub admin_key: String,
+    /// Additional API keys, each scoped to contract patterns and operations.
+    pub api_keys: Vec<ApiKeyConf>,
+    /// Issuer of the OIDC tokens accepted instead of API keys (empty disables them).
+    pub oidc_issuer: Option<String>,
+    /// Audience the OIDC tokens must be issued for.
+    pub oidc_audience: Option<String>,
+    /// JWKS endpoint of the issuer.
+    pub oidc_jwks_url: Option<String>,
+    /// Local JWKS file, used instead of `oidc_jwks_url`.
+    pub oidc_jwks_file: Option<PathBuf>,
+    /// How long fetched JWKS keys are used before being fetched again.
+    pub oidc_jwks_refresh_secs: u64,
+    /// Claim patterns mapped to the contracts and operations OIDC tokens are allowed.
+    pub oidc_policies: Vec<OidcPolicyConf>,
     /// Storage backend ("local" or "gcs").
     pub storage_backend: String,
     /// GCS bucket name when storage_backend = "gcs".
@@ -21,11 +40,219 @@ pub struct Conf {
     pub gcs_prefix: Option<String>,
     /// Optional override for local storage directory.
     pub local_storage_directory: Option<PathBuf>,
+    /// Maximum total size in bytes of ELF binaries kept in the in-memory cache (0 disables it).
+    pub binary_cache_max_bytes: u64,
+    /// Byte budget of binaries preloaded into the cache at startup (0 disables startup warm-up).
+    pub cache_warmup_max_bytes: u64,
+    /// Maximum number of programs preloaded per contract during cache warm-up.
+    pub cache_warmup_programs_per_contract: usize,
+    /// Hyli node REST API used to track the on-chain program of each contract (empty disables it).
+    pub node_url: Option<String>,
+    /// Interval between two refreshes of the on-chain programs.
+    pub chain_status_refresh_secs: u64,
+    /// Registry this instance mirrors: programs missing locally are fetched
+    /// from it on download and its listings are merged in (empty disables it).
+    pub upstream_url: Option<String>,
+    /// Reject uploads and deletes, serving only what the upstream provides.
+    pub upstream_read_only: bool,
+    /// How long an upstream contract listing is reused before being fetched again.
+    pub upstream_listing_refresh_secs: u64,
+    /// Hex secp256k1 public key the upstream signs its manifests with: mirrored
+    /// ELFs are checked against them (empty trusts the upstream listings).
+    pub upstream_manifest_key: Option<String>,
+    /// Publisher keys allowed to sign uploads, per contract pattern.
+    pub publishers: Vec<PublisherConf>,
+    /// Reject unsigned uploads, including for contracts without publisher keys.
+    pub require_signed_uploads: bool,
+    /// Hex secp256k1 secret key contract manifests are signed with (empty disables them).
+    pub manifest_signing_key: Option<String>,
+    /// Secret pre-signed download URLs are signed with, at least 32 bytes (empty disables them).
+    pub download_url_secret: Option<String>,
+    /// Longest validity of a pre-signed download URL.
+    pub download_url_max_ttl_secs: u64,
+    /// Redirect pre-signed downloads to a signed URL of the storage backend
+    /// (GCS) instead of serving the ELF.
+    pub download_url_redirect: bool,
+    /// Number of recent registry events kept for clients resuming the event stream.
+    pub event_log_capacity: usize,
+    /// Outgoing webhooks notified after uploads and deletes.
+    pub webhooks: Vec<WebhookConf>,
+    /// Delivery attempts per webhook event before it is dropped.
+    pub webhook_max_attempts: u32,
+    /// Delay before the first webhook retry, doubled after each failed attempt.
+    pub webhook_retry_initial_ms: u64,
+    /// Upper bound of the delay between webhook retries.
+    pub webhook_retry_max_ms: u64,
     /// When running only the indexer, the address of the DA server to connect to
     pub rest_server_port: u16,
     pub rest_server_max_body_size: usize,
 }
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct WebhookConf {
+    /// Name identifying the hook in delivery logs.
+    pub name: String,
+    /// Endpoint receiving events as JSON POST requests.
+    pub url: String,
+    /// Shared secret the payloads are signed with (HMAC-SHA256).
+    pub secret: String,
+    /// Contract patterns (`*` wildcard) the hook is notified for; empty means all contracts.
+    #[serde(default)]
+    pub contracts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKeyConf {
+    /// Name recorded as the author of the actions performed with the key.
+    pub name: String,
+    /// Hex SHA-256 of the key, so the key itself is not kept in the config.
+    pub key_sha256: String,
+    /// Contract patterns (`*` wildcard) the key may act on; registry-wide
+    /// admin actions need a `*` pattern.
+    pub contracts: Vec<String>,
+    /// Operations the key may perform: "upload", "delete", "read" and "admin".
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OidcPolicyConf {
+    /// Name the uploads are recorded under, as `oidc:<name>`.
+    pub name: String,
+    /// Patterns (`*` wildcard) the token claims must all match, e.g.
+    /// `repository`, `ref` or `workflow`.
+    pub claims: BTreeMap<String, String>,
+    /// Contract patterns (`*` wildcard) matching tokens may act on.
+    pub contracts: Vec<String>,
+    /// Operations matching tokens may perform: "upload", "delete", "read" and "admin".
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PublisherConf {
+    /// Contract pattern (`*` wildcard) the keys may publish to.
+    pub contract: String,
+    /// Compressed secp256k1 public keys, hex encoded.
+    pub public_keys: Vec<String>,
+}
+
+/// Shown instead of a configured secret.
+fn redact(secret: &str) -> &'static str {
+    if secret.trim().is_empty() {
+        ""
+    } else {
+        "<redacted>"
+    }
+}
+
+impl fmt::Debug for Conf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Destructured so that new fields must be listed here.
+        let Conf {
+            id,
+            log_format,
+            data_directory,
+            api_key,
+            admin_key,
+            api_keys,
+            oidc_issuer,
+            oidc_audience,
+            oidc_jwks_url,
+            oidc_jwks_file,
+            oidc_jwks_refresh_secs,
+            oidc_policies,
+            storage_backend,
+            gcs_bucket,
+            gcs_prefix,
+            local_storage_directory,
+            binary_cache_max_bytes,
+            cache_warmup_max_bytes,
+            cache_warmup_programs_per_contract,
+            node_url,
+            chain_status_refresh_secs,
+            upstream_url,
+            upstream_read_only,
+            upstream_listing_refresh_secs,
+            upstream_manifest_key,
+            publishers,
+            require_signed_uploads,
+            manifest_signing_key,
+            download_url_secret,
+            download_url_max_ttl_secs,
+            download_url_redirect,
+            event_log_capacity,
+            webhooks,
+            webhook_max_attempts,
+            webhook_retry_initial_ms,
+            webhook_retry_max_ms,
+            rest_server_port,
+            rest_server_max_body_size,
+        } = self;
+        f.debug_struct("Conf")
+            .field("id", &id)
+            .field("log_format", &log_format)
+            .field("data_directory", &data_directory)
+            .field("api_key", &redact(api_key))
+            .field("admin_key", &redact(admin_key))
+            .field("api_keys", &api_keys)
+            .field("oidc_issuer", &oidc_issuer)
+            .field("oidc_audience", &oidc_audience)
+            .field("oidc_jwks_url", &oidc_jwks_url)
+            .field("oidc_jwks_file", &oidc_jwks_file)
+            .field("oidc_jwks_refresh_secs", &oidc_jwks_refresh_secs)
+            .field("oidc_policies", &oidc_policies)
+            .field("storage_backend", &storage_backend)
+            .field("gcs_bucket", &gcs_bucket)
+            .field("gcs_prefix", &gcs_prefix)
+            .field("local_storage_directory", &local_storage_directory)
+            .field("binary_cache_max_bytes", &binary_cache_max_bytes)
+            .field("cache_warmup_max_bytes", &cache_warmup_max_bytes)
+            .field(
+                "cache_warmup_programs_per_contract",
+                &cache_warmup_programs_per_contract,
+            )
+            .field("node_url", &node_url)
+            .field("chain_status_refresh_secs", &chain_status_refresh_secs)
+            .field("upstream_url", &upstream_url)
+            .field("upstream_read_only", &upstream_read_only)
+            .field(
+                "upstream_listing_refresh_secs",
+                &upstream_listing_refresh_secs,
+            )
+            .field("upstream_manifest_key", &upstream_manifest_key)
+            .field("publishers", &publishers)
+            .field("require_signed_uploads", &require_signed_uploads)
+            .field(
+                "manifest_signing_key",
+                &manifest_signing_key.as_deref().map(redact),
+            )
+            .field(
+                "download_url_secret",
+                &download_url_secret.as_deref().map(redact),
+            )
+            .field("download_url_max_ttl_secs", &download_url_max_ttl_secs)
+            .field("download_url_redirect", &download_url_redirect)
+            .field("event_log_capacity", &event_log_capacity)
+            .field("webhooks", &webhooks)
+            .field("webhook_max_attempts", &webhook_max_attempts)
+            .field("webhook_retry_initial_ms", &webhook_retry_initial_ms)
+            .field("webhook_retry_max_ms", &webhook_retry_max_ms)
+            .field("rest_server_port", &rest_server_port)
+            .field("rest_server_max_body_size", &rest_server_max_body_size)
+            .finish()
+    }
+}
+
+impl fmt::Debug for WebhookConf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookConf")
+            .field("name", &self.name)
+            .field("url", &self.url)
+            .field("secret", &redact(&self.secret))
+            .field("contracts", &self.contracts)
+            .finish()
+    }
+}
+
 impl Conf {
     pub fn new(config_files: Vec<String>) -> Result<Self, anyhow::Error> {
         let mut s = Config::builder().add_source(File::from_str(
@@ -48,3 +275,29 @@ impl Conf {
         Ok(conf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_output_redacts_secrets() {
+        let config = Conf {
+            api_key: "api-secret".to_string(),
+            admin_key: "admin-secret".to_string(),
+            manifest_signing_key: Some("signing-secret".to_string()),
+            download_url_secret: Some("download-secret".to_string()),
+            webhooks: vec![WebhookConf {
+                name: "ci".to_string(),
+                secret: "hook-secret".to_string(),
+                ..Default::default()
+            }],
+            rest_server_port: 9003,
+            ..Default::default()
+        };
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("secret\""), "{debug}");
+        assert!(debug.contains("rest_server_port: 9003"));
+        assert!(debug.contains("name: \"ci\""));
+    }
+}
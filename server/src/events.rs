@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use hyli_modules::bus::BusMessage;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::registry::ProgramInfo;
+
+/// Capacity of the broadcast channel registry events are published on.
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Change to the registry content, published after it has been persisted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RegistryEvent {
+    ProgramUploaded {
+        contract: String,
+        program: Box<ProgramInfo>,
+    },
+    ProgramDeleted {
+        contract: String,
+        program_id: String,
+    },
+    ContractDeleted {
+        contract: String,
+    },
+}
+
+impl RegistryEvent {
+    pub fn contract(&self) -> &str {
+        match self {
+            RegistryEvent::ProgramUploaded { contract, .. }
+            | RegistryEvent::ProgramDeleted { contract, .. }
+            | RegistryEvent::ContractDeleted { contract } => contract,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RegistryEvent::ProgramUploaded { .. } => "program_uploaded",
+            RegistryEvent::ProgramDeleted { .. } => "program_deleted",
+            RegistryEvent::ContractDeleted { .. } => "contract_deleted",
+        }
+    }
+}
+
+impl BusMessage for RegistryEvent {}
+
+/// Bus command asking for a program binary, answered with `None` when the
+/// program is not in the registry.
+#[derive(Debug, Clone)]
+pub struct GetProgramElf {
+    pub contract: String,
+    pub program_id: String,
+}
+
+/// Bus command listing a contract's programs, most recent first.
+#[derive(Debug, Clone)]
+pub struct ListContractPrograms {
+    pub contract: String,
+}
+
+/// Registry event with the id it was published under.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoggedEvent {
+    pub id: u64,
+    #[serde(flatten)]
+    pub event: RegistryEvent,
+}
+
+/// Events published after a given id, followed by a receiver for the next ones.
+pub struct EventReplay {
+    pub missed: Vec<LoggedEvent>,
+    /// Some events after the requested id are no longer in the log.
+    pub gap: bool,
+    pub receiver: broadcast::Receiver<LoggedEvent>,
+}
+
+/// Publishes registry events and keeps the most recent ones so subscribers
+/// can resume from the last event they saw.
+pub struct EventLog {
+    capacity: usize,
+    inner: Mutex<EventLogInner>,
+    sender: broadcast::Sender<LoggedEvent>,
+}
+
+struct EventLogInner {
+    next_id: u64,
+    recent: VecDeque<LoggedEvent>,
+}
+
+impl EventLog {
+    /// Ids start from the current time in microseconds so they keep
+    /// increasing across restarts.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(EventLogInner {
+                next_id: Utc::now().timestamp_micros().max(1) as u64,
+                recent: VecDeque::new(),
+            }),
+            sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    pub fn publish(&self, event: RegistryEvent) -> u64 {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        let logged = LoggedEvent {
+            id: inner.next_id,
+            event,
+        };
+        inner.next_id += 1;
+        if self.capacity > 0 {
+            if inner.recent.len() == self.capacity {
+                inner.recent.pop_front();
+            }
+            inner.recent.push_back(logged.clone());
+        }
+        // Sending only fails when nobody is subscribed.
+        let _ = self.sender.send(logged.clone());
+        logged.id
+    }
+
+    /// Receives every event published after the call.
+    pub fn subscribe(&self) -> broadcast::Receiver<LoggedEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Returns the logged events after `last_id` together with a receiver
+    /// for the following ones, without missing or repeating any event.
+    pub fn resume(&self, last_id: u64) -> EventReplay {
+        let inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        let oldest_id = inner
+            .recent
+            .front()
+            .map_or(inner.next_id, |oldest| oldest.id);
+        EventReplay {
+            missed: inner
+                .recent
+                .iter()
+                .filter(|logged| logged.id > last_id)
+                .cloned()
+                .collect(),
+            gap: last_id.saturating_add(1) < oldest_id,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deleted(contract: &str) -> RegistryEvent {
+        RegistryEvent::ContractDeleted {
+            contract: contract.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_replays_logged_events_and_flags_gaps() {
+        let log = EventLog::new(2);
+        let first = log.publish(deleted("a"));
+        let second = log.publish(deleted("b"));
+        let third = log.publish(deleted("c"));
+        assert!(first < second && second < third);
+
+        let replay = log.resume(second);
+        assert!(!replay.gap);
+        assert_eq!(
+            replay
+                .missed
+                .iter()
+                .map(|logged| logged.id)
+                .collect::<Vec<_>>(),
+            vec![third]
+        );
+
+        // The first event was evicted, so resuming from before it has a gap.
+        let replay = log.resume(first - 1);
+        assert!(replay.gap);
+        assert_eq!(replay.missed.len(), 2);
+        assert!(!log.resume(first).gap);
+
+        let mut receiver = replay.receiver;
+        let fourth = log.publish(deleted("d"));
+        assert_eq!(receiver.recv().await.expect("event").id, fourth);
+    }
+}
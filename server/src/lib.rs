@@ -0,0 +1,31 @@
+//! Hyli registry server: stores zkVM ELF binaries per contract and serves
+//! them over HTTP. Use [`RegistryBuilder`] to embed it in another process.
+
+pub mod app;
+pub mod artifacts;
+pub mod auth;
+pub mod backup;
+pub mod builder;
+pub mod cache;
+pub mod chain;
+pub mod conf;
+pub mod events;
+pub mod manifest;
+pub mod metrics;
+pub mod oidc;
+pub mod ownership;
+pub mod presign;
+pub mod provenance;
+pub mod query;
+pub mod registry;
+pub mod signing;
+pub mod stats;
+pub mod storage;
+pub mod transparency;
+pub mod upstream;
+pub mod webhooks;
+
+pub use builder::{RegistryApp, RegistryBuilder, SpawnedRegistry};
+pub use conf::Conf;
+pub use registry::RegistryService;
+pub use storage::{GcsStorageBackend, LocalStorageBackend, StorageBackend};
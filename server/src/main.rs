@@ -1,8 +1,6 @@
 use anyhow::{Context, Result};
-use app::{AppModule, AppModuleCtx};
 use axum::Router;
-use clap::Parser;
-use conf::Conf;
+use clap::{Parser, Subcommand};
 use hyli_modules::{
     bus::SharedMessageBus,
     modules::{
@@ -11,14 +9,15 @@ use hyli_modules::{
     },
     utils::logger::setup_otlp,
 };
+use hyli_registry_server::{
+    app::{AppModule, AppModuleCtx},
+    backup::{export_to_file, import_archive, Compression, ImportMode},
+    Conf, RegistryService,
+};
 use sdk::{api::NodeInfo, info};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-mod app;
-mod conf;
-mod registry;
-mod storage;
-
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
@@ -38,6 +37,36 @@ pub struct Args {
     /// Argument used by hylix tests commands
     #[arg(long)]
     pub server_port: Option<u16>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// One-off maintenance commands, run against the configured storage instead
+/// of starting the server.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Write a backup archive of every program to a file
+    Export {
+        #[arg(long)]
+        output: PathBuf,
+
+        #[arg(long, value_enum, default_value_t = Compression::Zstd)]
+        compression: Compression,
+    },
+    /// Restore a backup archive. Stop the server first: a running server
+    /// would not see the restored programs until restarted.
+    Import {
+        #[arg(long)]
+        input: PathBuf,
+
+        #[arg(long, value_enum, default_value_t = ImportMode::Merge)]
+        mode: ImportMode,
+
+        /// Only validate the archive and print the changes
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+    },
 }
 
 #[tokio::main]
@@ -52,12 +81,16 @@ async fn main() -> Result<()> {
     )
     .context("setting up tracing")?;
 
-    let mut config = config;
-    if config.api_key.trim().is_empty() {
-        anyhow::bail!("api_key must be set to enable authenticated uploads");
+    if let Some(command) = args.command {
+        return run_command(command, config).await;
     }
-    if config.admin_key.trim().is_empty() {
-        anyhow::bail!("admin_key must be set to enable admin deletes");
+
+    let mut config = config;
+    if config.api_key.trim().is_empty()
+        && config.admin_key.trim().is_empty()
+        && config.api_keys.is_empty()
+    {
+        anyhow::bail!("api_key, admin_key or api_keys must be set to enable authenticated uploads");
     }
     if config.rest_server_max_body_size == 0 {
         config.rest_server_max_body_size = usize::MAX;
@@ -125,3 +158,31 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+async fn run_command(command: Command, mut config: Conf) -> Result<()> {
+    // No point preloading binaries for a one-off command.
+    config.cache_warmup_max_bytes = 0;
+    let registry = RegistryService::new(&config).await?;
+    match command {
+        Command::Export {
+            output,
+            compression,
+        } => {
+            let manifest = export_to_file(registry, &output, compression).await?;
+            info!(
+                "Exported {} programs to {}",
+                manifest.programs,
+                output.display()
+            );
+        }
+        Command::Import {
+            input,
+            mode,
+            dry_run,
+        } => {
+            let report = import_archive(&registry, &input, mode, dry_run).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    Ok(())
+}
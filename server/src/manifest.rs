@@ -0,0 +1,266 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Utc;
+/// Digest signed by the registry: SHA-256 of `hyli-registry-manifest:v1\n<payload>`.
+pub use hyli_registry::verify::manifest_digest;
+use hyli_registry::verify::verify_signature;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey, SignOnly};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::conf::Conf;
+use crate::registry::RegistryService;
+
+/// Programs of a contract with the digest of their ELF.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractManifest {
+    pub contract: String,
+    pub generated_at: String,
+    /// Ordered by program id.
+    pub programs: Vec<ManifestProgram>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestProgram {
+    pub program_id: String,
+    /// Hex SHA-256 of the ELF.
+    pub sha256: String,
+    pub size_bytes: u64,
+    pub uploaded_at: String,
+}
+
+/// Manifest as served: the exact JSON that was signed, the signature and
+/// the key it verifies with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedManifest {
+    /// JSON serialized [`ContractManifest`].
+    pub payload: String,
+    /// Compressed secp256k1 public key of the registry, hex encoded.
+    pub public_key: String,
+    /// Compact ECDSA signature of [`manifest_digest`] of the payload, hex encoded.
+    pub signature: String,
+}
+
+impl SignedManifest {
+    /// Checks the signature with `key`, known beforehand rather than the
+    /// served `public_key`, and returns the manifest of `contract`.
+    pub fn verify(&self, contract: &str, key: &PublicKey) -> Result<ContractManifest> {
+        verify_signature(manifest_digest(&self.payload), &self.signature, key)
+            .with_context(|| format!("manifest of {contract} is not signed by the expected key"))?;
+        let manifest: ContractManifest =
+            serde_json::from_str(&self.payload).context("parsing manifest")?;
+        if manifest.contract != contract {
+            bail!(
+                "manifest is for contract {}, not {contract}",
+                manifest.contract
+            );
+        }
+        Ok(manifest)
+    }
+}
+
+/// Signs contract manifests and transparency log heads with `manifest_signing_key`.
+pub struct ManifestSigner {
+    secp: Secp256k1<SignOnly>,
+    key: SecretKey,
+    public_key: String,
+}
+
+impl ManifestSigner {
+    /// Signer configured by `manifest_signing_key`, if any.
+    pub fn from_conf(config: &Conf) -> Result<Option<Self>> {
+        let Some(key) = config
+            .manifest_signing_key
+            .as_deref()
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+        else {
+            return Ok(None);
+        };
+        let key = hex::decode(key)
+            .context("manifest_signing_key is not hex")
+            .and_then(|bytes| {
+                SecretKey::from_slice(&bytes).context("invalid manifest_signing_key")
+            })?;
+        let secp = Secp256k1::signing_only();
+        let public_key = hex::encode(PublicKey::from_secret_key(&secp, &key).serialize());
+        info!("Signing contract manifests with public key {public_key}");
+        Ok(Some(Self {
+            secp,
+            key,
+            public_key,
+        }))
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Compact ECDSA signature of `digest`, hex encoded.
+    pub fn sign_digest(&self, digest: [u8; 32]) -> String {
+        hex::encode(
+            self.secp
+                .sign_ecdsa(&Message::from_digest(digest), &self.key)
+                .serialize_compact(),
+        )
+    }
+
+    pub fn sign(&self, manifest: &ContractManifest) -> Result<SignedManifest> {
+        let payload = serde_json::to_string(manifest).context("serializing manifest")?;
+        Ok(SignedManifest {
+            signature: self.sign_digest(manifest_digest(&payload)),
+            public_key: self.public_key.clone(),
+            payload,
+        })
+    }
+}
+
+/// Current manifest of `contract`, `None` if the contract is unknown.
+pub async fn contract_manifest(
+    registry: &RegistryService,
+    contract: &str,
+) -> Result<Option<ContractManifest>> {
+    let Some(entries) = registry.contract_digests(contract).await? else {
+        return Ok(None);
+    };
+    let programs = entries
+        .into_iter()
+        .map(|entry| {
+            Ok(ManifestProgram {
+                sha256: entry
+                    .sha256
+                    .ok_or_else(|| anyhow!("no digest for {}", entry.program_id))?,
+                program_id: entry.program_id,
+                size_bytes: entry.size_bytes,
+                uploaded_at: entry.uploaded_at,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(ContractManifest {
+        contract: contract.to_string(),
+        generated_at: Utc::now().to_rfc3339(),
+        programs,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::metrics::RegistryMetrics;
+    use crate::registry::{ProgramEntry, ProgramMetadata};
+    use crate::storage::{LocalStorageBackend, StorageBackend};
+    use bytes::Bytes;
+    use sha2::{Digest, Sha256};
+
+    fn metadata() -> ProgramMetadata {
+        ProgramMetadata {
+            toolchain: None,
+            commit: None,
+            zkvm: "sp1".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn signs_manifests_with_backfilled_digests() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let storage = Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf()));
+        // Stored before digests were recorded.
+        let legacy = ProgramEntry {
+            program_id: "p0".to_string(),
+            contract: "orders".to_string(),
+            object_path: "orders/legacy.elf".to_string(),
+            metadata_path: "orders/legacy.json".to_string(),
+            size_bytes: 6,
+            uploaded_at: "2024-01-01T00:00:00Z".to_string(),
+            metadata: metadata(),
+            sha256: None,
+            signature: None,
+            provenance: None,
+            artifacts: Default::default(),
+            uploaded_by: None,
+            oidc_claims: Default::default(),
+        };
+        storage
+            .write_object(&legacy.object_path, b"legacy")
+            .await
+            .expect("write elf");
+        storage
+            .write_object(
+                &legacy.metadata_path,
+                &serde_json::to_vec(&legacy).expect("json"),
+            )
+            .await
+            .expect("write metadata");
+
+        let registry = RegistryService::with_storage(
+            &Conf::default(),
+            storage,
+            RegistryMetrics::unregistered().expect("metrics"),
+        )
+        .await
+        .expect("registry");
+        registry
+            .upload("orders", "p1", metadata(), Bytes::from_static(b"elf-1"))
+            .await
+            .expect("upload");
+
+        let key = SecretKey::from_slice(&[7; 32]).expect("key");
+        let signer = ManifestSigner::from_conf(&Conf {
+            manifest_signing_key: Some(hex::encode(key.secret_bytes())),
+            ..Default::default()
+        })
+        .expect("signer")
+        .expect("configured");
+        let manifest = contract_manifest(&registry, "orders")
+            .await
+            .expect("manifest")
+            .expect("contract");
+        let signed = signer.sign(&manifest).expect("sign");
+
+        let public_key =
+            PublicKey::from_slice(&hex::decode(&signed.public_key).expect("hex")).expect("key");
+        assert_eq!(
+            signed
+                .verify("orders", &public_key)
+                .expect("valid signature"),
+            manifest
+        );
+        // Clients check it with the same code.
+        let client = hyli_registry::SignedManifest {
+            payload: signed.payload.clone(),
+            public_key: signed.public_key.clone(),
+            signature: signed.signature.clone(),
+        };
+        assert!(client.verify("orders", &public_key).is_ok());
+        assert!(client.verify("wallet", &public_key).is_err());
+        let tampered = hyli_registry::SignedManifest {
+            payload: signed.payload.replace("\"p1\"", "\"p2\""),
+            ..client
+        };
+        assert!(tampered.verify("orders", &public_key).is_err());
+
+        let payload: ContractManifest = serde_json::from_str(&signed.payload).expect("payload");
+        assert_eq!(payload, manifest);
+        let digests = payload
+            .programs
+            .iter()
+            .map(|program| (program.program_id.as_str(), program.sha256.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            digests,
+            vec![
+                ("p0", hex::encode(Sha256::digest(b"legacy"))),
+                ("p1", hex::encode(Sha256::digest(b"elf-1"))),
+            ]
+        );
+        let indexed = registry.index_snapshot().await.contracts["orders"].programs["p0"]
+            .sha256
+            .clone();
+        assert_eq!(indexed, Some(hex::encode(Sha256::digest(b"legacy"))));
+        assert!(contract_manifest(&registry, "wallet")
+            .await
+            .expect("manifest")
+            .is_none());
+    }
+}
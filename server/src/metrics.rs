@@ -0,0 +1,199 @@
+use anyhow::Result;
+use prometheus::{
+    core::Collector, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts,
+};
+
+use crate::cache::BinaryCache;
+use crate::stats::RegistryStats;
+
+pub struct RegistryMetrics {
+    pub requests: IntCounterVec,
+    pub bytes: IntCounterVec,
+    pub cache_hits: IntCounter,
+    pub cache_misses: IntCounter,
+    pub cache_evictions: IntCounter,
+    pub download_coalesced: IntCounter,
+    pub cache_bytes: IntGauge,
+    pub cache_entries: IntGauge,
+    pub index_rebuilds: IntCounter,
+    pub storage_latency: HistogramVec,
+    pub contracts: IntGauge,
+    pub contract_programs: IntGaugeVec,
+    pub contract_bytes: IntGaugeVec,
+    pub zkvm_bytes: IntGaugeVec,
+    pub oldest_upload: IntGauge,
+    pub newest_upload: IntGauge,
+    pub contracts_missing_elf: IntGauge,
+    pub upstream_fetches: IntCounterVec,
+}
+
+impl RegistryMetrics {
+    /// Creates the metrics and registers them in the default prometheus registry.
+    pub fn new() -> Result<Self> {
+        let metrics = Self::unregistered()?;
+        let registry = prometheus::default_registry();
+        for collector in metrics.collectors() {
+            registry.register(collector)?;
+        }
+        Ok(metrics)
+    }
+
+    /// Creates the metrics without registering them, so several instances can coexist.
+    pub fn unregistered() -> Result<Self> {
+        Ok(Self {
+            requests: IntCounterVec::new(
+                Opts::new(
+                    "hyli_registry_requests_total",
+                    "Total registry requests by operation.",
+                ),
+                &["op"],
+            )?,
+            bytes: IntCounterVec::new(
+                Opts::new(
+                    "hyli_registry_bytes_total",
+                    "Total bytes transferred by operation.",
+                ),
+                &["op"],
+            )?,
+            cache_hits: IntCounter::new("hyli_registry_cache_hits_total", "Registry cache hits.")?,
+            cache_misses: IntCounter::new(
+                "hyli_registry_cache_misses_total",
+                "Registry cache misses.",
+            )?,
+            cache_evictions: IntCounter::new(
+                "hyli_registry_cache_evictions_total",
+                "Binaries evicted from the registry cache.",
+            )?,
+            download_coalesced: IntCounter::new(
+                "hyli_registry_download_coalesced_total",
+                "Downloads that waited on an in-flight storage read for the same program.",
+            )?,
+            cache_bytes: IntGauge::new(
+                "hyli_registry_cache_bytes",
+                "Total size of binaries held in the registry cache.",
+            )?,
+            cache_entries: IntGauge::new(
+                "hyli_registry_cache_entries",
+                "Number of binaries held in the registry cache.",
+            )?,
+            index_rebuilds: IntCounter::new(
+                "hyli_registry_index_rebuilds_total",
+                "Index rebuild count.",
+            )?,
+            storage_latency: HistogramVec::new(
+                HistogramOpts::new(
+                    "hyli_registry_storage_latency_seconds",
+                    "Latency of storage operations.",
+                ),
+                &["op", "backend"],
+            )?,
+            contracts: IntGauge::new(
+                "hyli_registry_contracts",
+                "Number of contracts in the registry.",
+            )?,
+            contract_programs: IntGaugeVec::new(
+                Opts::new(
+                    "hyli_registry_contract_programs",
+                    "Number of programs stored per contract.",
+                ),
+                &["contract"],
+            )?,
+            contract_bytes: IntGaugeVec::new(
+                Opts::new(
+                    "hyli_registry_contract_bytes",
+                    "Total size of the binaries stored per contract.",
+                ),
+                &["contract"],
+            )?,
+            zkvm_bytes: IntGaugeVec::new(
+                Opts::new(
+                    "hyli_registry_zkvm_bytes",
+                    "Total size of the binaries stored per zkvm.",
+                ),
+                &["zkvm"],
+            )?,
+            oldest_upload: IntGauge::new(
+                "hyli_registry_oldest_upload_timestamp_seconds",
+                "Upload time of the oldest stored program.",
+            )?,
+            newest_upload: IntGauge::new(
+                "hyli_registry_newest_upload_timestamp_seconds",
+                "Upload time of the most recently stored program.",
+            )?,
+            contracts_missing_elf: IntGauge::new(
+                "hyli_registry_contracts_missing_onchain_elf",
+                "Contracts whose on-chain program id has no ELF in the registry.",
+            )?,
+            upstream_fetches: IntCounterVec::new(
+                Opts::new(
+                    "hyli_registry_upstream_fetches_total",
+                    "Requests to the upstream registry by kind and outcome.",
+                ),
+                &["kind", "outcome"],
+            )?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<Box<dyn Collector>> {
+        vec![
+            Box::new(self.requests.clone()),
+            Box::new(self.bytes.clone()),
+            Box::new(self.cache_hits.clone()),
+            Box::new(self.cache_misses.clone()),
+            Box::new(self.cache_evictions.clone()),
+            Box::new(self.download_coalesced.clone()),
+            Box::new(self.cache_bytes.clone()),
+            Box::new(self.cache_entries.clone()),
+            Box::new(self.index_rebuilds.clone()),
+            Box::new(self.storage_latency.clone()),
+            Box::new(self.contracts.clone()),
+            Box::new(self.contract_programs.clone()),
+            Box::new(self.contract_bytes.clone()),
+            Box::new(self.zkvm_bytes.clone()),
+            Box::new(self.oldest_upload.clone()),
+            Box::new(self.newest_upload.clone()),
+            Box::new(self.contracts_missing_elf.clone()),
+            Box::new(self.upstream_fetches.clone()),
+        ]
+    }
+
+    pub fn record_cache(&self, cache: &BinaryCache, evictions: u64) {
+        self.cache_evictions.inc_by(evictions);
+        self.cache_bytes.set(cache.total_bytes() as i64);
+        self.cache_entries.set(cache.len() as i64);
+    }
+
+    /// Refreshes the storage gauges. Labelled gauges are reset first so
+    /// deleted contracts and zkvms disappear from the exported series.
+    pub fn record_index(&self, stats: &RegistryStats) {
+        self.contracts.set(stats.contracts as i64);
+        self.contract_programs.reset();
+        self.contract_bytes.reset();
+        for (contract, contract_stats) in &stats.per_contract {
+            self.contract_programs
+                .with_label_values(&[contract])
+                .set(contract_stats.programs as i64);
+            self.contract_bytes
+                .with_label_values(&[contract])
+                .set(contract_stats.bytes as i64);
+        }
+        self.zkvm_bytes.reset();
+        for (zkvm, zkvm_stats) in &stats.per_zkvm {
+            self.zkvm_bytes
+                .with_label_values(&[zkvm])
+                .set(zkvm_stats.bytes as i64);
+        }
+        self.oldest_upload
+            .set(upload_timestamp(stats.oldest_upload.as_deref()));
+        self.newest_upload
+            .set(upload_timestamp(stats.newest_upload.as_deref()));
+    }
+}
+
+fn upload_timestamp(uploaded_at: Option<&str>) -> i64 {
+    uploaded_at
+        .and_then(|uploaded_at| chrono::DateTime::parse_from_rfc3339(uploaded_at).ok())
+        .map(|uploaded_at| uploaded_at.timestamp())
+        .unwrap_or_default()
+}
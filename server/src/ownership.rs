@@ -0,0 +1,46 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+const MAX_IDENTITY_LEN: usize = 128;
+
+/// Publisher identity a contract belongs to, kept in the index. Identities
+/// are the names actions are recorded under: API key names, or
+/// `oidc:<policy>` for OIDC tokens.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractOwnership {
+    pub owner: String,
+    /// Other identities the owner lets upload to the contract.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub collaborators: BTreeSet<String>,
+    pub claimed_at: String,
+}
+
+impl ContractOwnership {
+    pub fn allows(&self, identity: &str) -> bool {
+        self.owner == identity || self.collaborators.contains(identity)
+    }
+}
+
+/// Outcome of checking that an identity may upload to a contract.
+#[derive(Debug, PartialEq, Eq)]
+pub enum UploadClaim {
+    /// The identity owns the contract or collaborates on it.
+    Allowed,
+    /// The contract had no owner and now belongs to the identity.
+    Claimed,
+    /// The contract belongs to someone else.
+    Denied { owner: String },
+}
+
+pub fn validate_identity(identity: &str) -> Result<(), String> {
+    if identity.trim().is_empty() || identity.len() > MAX_IDENTITY_LEN {
+        return Err(format!(
+            "Identity must be 1 to {MAX_IDENTITY_LEN} characters long"
+        ));
+    }
+    if identity.chars().any(char::is_control) {
+        return Err("Identity must not contain control characters".to_string());
+    }
+    Ok(())
+}
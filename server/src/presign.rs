@@ -0,0 +1,162 @@
+use anyhow::{bail, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::info;
+
+use crate::conf::Conf;
+
+/// Domain separator of the signed message.
+const DOWNLOAD_URL_CONTEXT: &str = "hyli-registry-download:v1";
+const MIN_SECRET_LEN: usize = 32;
+/// Validity of a URL when the request does not ask for one.
+const DEFAULT_TTL_SECS: u64 = 3600;
+
+/// Why a pre-signed download was refused.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PresignError {
+    Invalid,
+    Expired,
+}
+
+/// Issues and checks download URLs signed with HMAC-SHA256, bound to a
+/// contract, a program id and an expiry, which let whoever holds them
+/// download that one ELF without an API key.
+pub struct DownloadUrlSigner {
+    secret: Vec<u8>,
+    max_ttl_secs: u64,
+    /// Whether downloads are redirected to a signed URL of the storage backend.
+    redirect: bool,
+}
+
+impl DownloadUrlSigner {
+    /// Signer configured by `download_url_secret`, if any.
+    pub fn from_conf(config: &Conf) -> Result<Option<Self>> {
+        let Some(secret) = config
+            .download_url_secret
+            .as_deref()
+            .map(str::trim)
+            .filter(|secret| !secret.is_empty())
+        else {
+            return Ok(None);
+        };
+        if secret.len() < MIN_SECRET_LEN {
+            bail!("download_url_secret must be at least {MIN_SECRET_LEN} bytes long");
+        }
+        if config.download_url_max_ttl_secs == 0 {
+            bail!("download_url_max_ttl_secs must not be 0");
+        }
+        info!(
+            "Issuing pre-signed download URLs valid for at most {}s",
+            config.download_url_max_ttl_secs
+        );
+        Ok(Some(Self {
+            secret: secret.as_bytes().to_vec(),
+            max_ttl_secs: config.download_url_max_ttl_secs,
+            redirect: config.download_url_redirect,
+        }))
+    }
+
+    pub fn redirect(&self) -> bool {
+        self.redirect
+    }
+
+    /// Expiry, in unix seconds, of a URL issued at `now` and valid for
+    /// `ttl_secs` (one hour by default).
+    pub fn expiry(&self, ttl_secs: Option<u64>, now: i64) -> Result<i64, String> {
+        let ttl_secs = ttl_secs.unwrap_or(DEFAULT_TTL_SECS.min(self.max_ttl_secs));
+        if ttl_secs == 0 || ttl_secs > self.max_ttl_secs {
+            return Err(format!(
+                "expires_in_secs must be between 1 and {}",
+                self.max_ttl_secs
+            ));
+        }
+        Ok(now + ttl_secs as i64)
+    }
+
+    /// Hex HMAC of the contract, expiry and program id.
+    pub fn sign(&self, contract: &str, program_id: &str, expires: i64) -> String {
+        hex::encode(
+            self.mac(contract, program_id, expires)
+                .finalize()
+                .into_bytes(),
+        )
+    }
+
+    /// Checks `signature` in constant time, then the expiry against `now`.
+    pub fn verify(
+        &self,
+        contract: &str,
+        program_id: &str,
+        expires: i64,
+        signature: &str,
+        now: i64,
+    ) -> Result<(), PresignError> {
+        let signature = hex::decode(signature.trim()).map_err(|_| PresignError::Invalid)?;
+        self.mac(contract, program_id, expires)
+            .verify_slice(&signature)
+            .map_err(|_| PresignError::Invalid)?;
+        if expires <= now {
+            return Err(PresignError::Expired);
+        }
+        Ok(())
+    }
+
+    /// The program id comes last, so that no id can be mistaken for an
+    /// expiry.
+    fn mac(&self, contract: &str, program_id: &str, expires: i64) -> Hmac<Sha256> {
+        #[allow(clippy::expect_used, reason = "HMAC accepts keys of any size")]
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(
+            format!("{DOWNLOAD_URL_CONTEXT}\n{contract}\n{expires}\n{program_id}").as_bytes(),
+        );
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer() -> DownloadUrlSigner {
+        DownloadUrlSigner {
+            secret: b"0123456789abcdef0123456789abcdef".to_vec(),
+            max_ttl_secs: 600,
+            redirect: false,
+        }
+    }
+
+    #[test]
+    fn urls_are_bound_to_program_and_expiry() {
+        let signer = signer();
+        let now = 1_700_000_000;
+        let expires = signer.expiry(None, now).expect("default ttl");
+        assert_eq!(expires, now + 600);
+        assert!(signer.expiry(Some(601), now).is_err());
+        assert!(signer.expiry(Some(0), now).is_err());
+
+        let signature = signer.sign("orders", "program-a", expires);
+        assert_eq!(
+            signer.verify("orders", "program-a", expires, &signature, now),
+            Ok(())
+        );
+        for (contract, program_id, signed_expires) in [
+            ("wallet", "program-a", expires),
+            ("orders", "program-b", expires),
+            ("orders", "program-a", expires + 1),
+        ] {
+            assert_eq!(
+                signer.verify(contract, program_id, signed_expires, &signature, now),
+                Err(PresignError::Invalid)
+            );
+        }
+        assert_eq!(
+            signer.verify("orders", "program-a", expires, "not hex", now),
+            Err(PresignError::Invalid)
+        );
+        assert_eq!(
+            signer.verify("orders", "program-a", expires, &signature, expires),
+            Err(PresignError::Expired)
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+/// Longest accepted value of a free-text provenance field.
+const MAX_FIELD_LEN: usize = 512;
+const MAX_BUILD_COMMAND_LEN: usize = 4096;
+
+/// How a program was built, uploaded along with its ELF and stored next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Provenance {
+    /// Repository the program was built from (e.g. `https://github.com/org/repo`).
+    pub source_repo: String,
+    /// Branch, tag or commit that was built.
+    pub source_ref: String,
+    /// Digest of the builder image, `sha256:<hex>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builder_image_digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustc_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo_prove_version: Option<String>,
+    /// Hex SHA-256 of the `Cargo.lock` used for the build.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo_lock_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_command: Option<String>,
+}
+
+/// Part of the provenance shown in listings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceSummary {
+    pub source_repo: String,
+    pub source_ref: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builder_image_digest: Option<String>,
+}
+
+impl Provenance {
+    /// Parses and validates a provenance document.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let provenance: Self =
+            serde_json::from_str(raw).map_err(|err| format!("Invalid provenance: {err}"))?;
+        provenance.validate()?;
+        Ok(provenance)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        check_text("source_repo", &self.source_repo, MAX_FIELD_LEN)?;
+        if self.source_repo.chars().any(char::is_whitespace) {
+            return Err("source_repo must not contain whitespace".to_string());
+        }
+        check_text("source_ref", &self.source_ref, MAX_FIELD_LEN)?;
+        if self.source_ref.chars().any(char::is_whitespace) {
+            return Err("source_ref must not contain whitespace".to_string());
+        }
+        if let Some(digest) = &self.builder_image_digest {
+            if !digest.strip_prefix("sha256:").is_some_and(is_sha256_hex) {
+                return Err("builder_image_digest must be sha256:<64 hex characters>".to_string());
+            }
+        }
+        if let Some(version) = &self.rustc_version {
+            check_text("rustc_version", version, MAX_FIELD_LEN)?;
+        }
+        if let Some(version) = &self.cargo_prove_version {
+            check_text("cargo_prove_version", version, MAX_FIELD_LEN)?;
+        }
+        if let Some(digest) = &self.cargo_lock_sha256 {
+            if !is_sha256_hex(digest) {
+                return Err("cargo_lock_sha256 must be 64 hex characters".to_string());
+            }
+        }
+        if let Some(command) = &self.build_command {
+            check_text("build_command", command, MAX_BUILD_COMMAND_LEN)?;
+        }
+        Ok(())
+    }
+
+    pub fn summary(&self) -> ProvenanceSummary {
+        ProvenanceSummary {
+            source_repo: self.source_repo.clone(),
+            source_ref: self.source_ref.clone(),
+            builder_image_digest: self.builder_image_digest.clone(),
+        }
+    }
+}
+
+fn check_text(field: &str, value: &str, max_len: usize) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("{field} must not be empty"));
+    }
+    if value.len() > max_len {
+        return Err(format!("{field} is longer than {max_len} bytes"));
+    }
+    if value.chars().any(char::is_control) {
+        return Err(format!("{field} must not contain control characters"));
+    }
+    Ok(())
+}
+
+fn is_sha256_hex(value: &str) -> bool {
+    value.len() == 64
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_provenance_documents() {
+        let provenance = Provenance::parse(
+            r#"{
+                "source_repo": "https://github.com/hyli-org/orders",
+                "source_ref": "v1.2.0",
+                "builder_image_digest": "sha256:0000000000000000000000000000000000000000000000000000000000000000",
+                "rustc_version": "1.85.0",
+                "cargo_prove_version": "4.1.0",
+                "cargo_lock_sha256": "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "build_command": "cargo prove build --docker"
+            }"#,
+        )
+        .expect("valid provenance");
+        assert_eq!(provenance.summary().source_ref, "v1.2.0");
+        assert!(Provenance::parse(r#"{"source_repo": "repo", "source_ref": "main"}"#).is_ok());
+
+        for invalid in [
+            r#"{"source_repo": "repo"}"#,
+            r#"{"source_repo": "repo", "source_ref": "main", "unknown": 1}"#,
+            r#"{"source_repo": "my repo", "source_ref": "main"}"#,
+            r#"{"source_repo": "repo", "source_ref": ""}"#,
+            r#"{"source_repo": "repo", "source_ref": "main", "builder_image_digest": "latest"}"#,
+            r#"{"source_repo": "repo", "source_ref": "main", "cargo_lock_sha256": "ABC"}"#,
+            r#"{"source_repo": "repo", "source_ref": "main", "build_command": "cargo\nbuild"}"#,
+        ] {
+            assert!(Provenance::parse(invalid).is_err(), "{invalid} accepted");
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::cmp::{Ordering, Reverse};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::registry::{ProgramEntry, ProgramInfo};
+
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+const MAX_SEARCH_LIMIT: usize = 1000;
+
+/// Filters accepted by the search endpoint. Every filter is optional and
+/// they are combined with AND.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProgramQuery {
+    /// Exact zkvm name, case-insensitive.
+    pub zkvm: Option<String>,
+    /// Exact toolchain identifier.
+    pub toolchain: Option<String>,
+    /// Commit prefix, so short hashes match full ones.
+    pub commit: Option<String>,
+    /// Contract name pattern where `*` matches any run of characters.
+    pub contract: Option<String>,
+    pub uploaded_after: Option<DateTime<Utc>>,
+    pub uploaded_before: Option<DateTime<Utc>>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    #[serde(default)]
+    pub sort: SortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    UploadedAt,
+    Size,
+    Contract,
+    ProgramId,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramMatch {
+    pub contract: String,
+    #[serde(flatten)]
+    pub program: ProgramInfo,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResults {
+    /// Number of matching programs before `limit` is applied.
+    pub total: usize,
+    pub programs: Vec<ProgramMatch>,
+}
+
+impl ProgramQuery {
+    pub fn matches(&self, entry: &ProgramEntry) -> bool {
+        if let Some(zkvm) = &self.zkvm {
+            if !entry.metadata.zkvm.eq_ignore_ascii_case(zkvm) {
+                return false;
+            }
+        }
+        if let Some(toolchain) = &self.toolchain {
+            if entry.metadata.toolchain.as_deref() != Some(toolchain.as_str()) {
+                return false;
+            }
+        }
+        if let Some(commit) = &self.commit {
+            let matches_commit = entry
+                .metadata
+                .commit
+                .as_deref()
+                .is_some_and(|entry_commit| entry_commit.starts_with(commit.as_str()));
+            if !matches_commit {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.contract {
+            if !glob_match(pattern, &entry.contract) {
+                return false;
+            }
+        }
+        if self.uploaded_after.is_some() || self.uploaded_before.is_some() {
+            let Some(uploaded_at) = entry.uploaded_at() else {
+                return false;
+            };
+            if self.uploaded_after.is_some_and(|after| uploaded_at < after)
+                || self
+                    .uploaded_before
+                    .is_some_and(|before| uploaded_at > before)
+            {
+                return false;
+            }
+        }
+        if self.min_size.is_some_and(|min| entry.size_bytes < min)
+            || self.max_size.is_some_and(|max| entry.size_bytes > max)
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Filters, sorts and truncates the given entries.
+    pub fn apply<'a>(&self, entries: impl Iterator<Item = &'a ProgramEntry>) -> SearchResults {
+        let mut matches = entries
+            .filter(|entry| self.matches(entry))
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| {
+            let ordering = match self.sort {
+                SortField::UploadedAt => a.uploaded_at_micros().cmp(&b.uploaded_at_micros()),
+                SortField::Size => a.size_bytes.cmp(&b.size_bytes),
+                SortField::Contract => a.contract.cmp(&b.contract),
+                SortField::ProgramId => a.program_id.cmp(&b.program_id),
+            }
+            .then_with(|| a.contract.cmp(&b.contract))
+            .then_with(|| a.program_id.cmp(&b.program_id));
+            match self.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        });
+
+        let total = matches.len();
+        let limit = self
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .min(MAX_SEARCH_LIMIT);
+        let programs = matches
+            .into_iter()
+            .take(limit)
+            .map(|entry| ProgramMatch {
+                contract: entry.contract.clone(),
+                program: ProgramInfo::from_entry(entry),
+            })
+            .collect();
+        SearchResults { total, programs }
+    }
+}
+
+/// Pagination parameters of the list endpoints. Listings are only paginated
+/// when one of them is present, so existing clients keep the full response.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageQuery {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+impl PageQuery {
+    pub fn is_paginated(&self) -> bool {
+        self.limit.is_some() || self.cursor.is_some()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT)
+    }
+
+    pub fn cursor(&self) -> Result<Option<ListCursor>, String> {
+        self.cursor.as_deref().map(ListCursor::decode).transpose()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramPage<T> {
+    pub programs: Vec<T>,
+    /// Opaque cursor to pass back to get the next page, absent on the last one.
+    pub next_cursor: Option<String>,
+}
+
+/// Position of the last program of a page in listing order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListCursor {
+    contract: String,
+    uploaded_at: i64,
+    program_id: String,
+}
+
+impl ListCursor {
+    fn of(entry: &ProgramEntry) -> Self {
+        Self {
+            contract: entry.contract.clone(),
+            uploaded_at: entry.uploaded_at_micros(),
+            program_id: entry.program_id.clone(),
+        }
+    }
+
+    fn key(&self) -> (&str, Reverse<i64>, &str) {
+        (&self.contract, Reverse(self.uploaded_at), &self.program_id)
+    }
+
+    pub fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(raw: &str) -> Result<Self, String> {
+        hex::decode(raw)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| "Invalid cursor".to_string())
+    }
+}
+
+fn listing_key(entry: &ProgramEntry) -> (&str, Reverse<i64>, &str) {
+    (
+        &entry.contract,
+        Reverse(entry.uploaded_at_micros()),
+        &entry.program_id,
+    )
+}
+
+/// Listing order: contract name, then most recent upload first, then
+/// program id to break ties.
+pub fn listing_order(a: &ProgramEntry, b: &ProgramEntry) -> Ordering {
+    listing_key(a).cmp(&listing_key(b))
+}
+
+/// Sorts entries in listing order and returns the page following `cursor`,
+/// with the cursor of the next page if more entries remain.
+pub fn paginate<'a>(
+    entries: impl Iterator<Item = &'a ProgramEntry>,
+    cursor: Option<&ListCursor>,
+    limit: usize,
+) -> (Vec<&'a ProgramEntry>, Option<ListCursor>) {
+    let mut entries = entries
+        .filter(|entry| cursor.is_none_or(|cursor| listing_key(entry) > cursor.key()))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| listing_order(a, b));
+    let next_cursor = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|entry| ListCursor::of(entry))
+    } else {
+        None
+    };
+    (entries, next_cursor)
+}
+
+/// Matches `text` against a pattern where `*` stands for any (possibly
+/// empty) run of characters and everything else is literal.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return text.is_empty();
+    };
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::ProgramMetadata;
+
+    fn entry(
+        contract: &str,
+        program_id: &str,
+        zkvm: &str,
+        commit: &str,
+        size: u64,
+    ) -> ProgramEntry {
+        ProgramEntry {
+            program_id: program_id.to_string(),
+            contract: contract.to_string(),
+            object_path: String::new(),
+            metadata_path: String::new(),
+            size_bytes: size,
+            uploaded_at: format!("2024-01-{:02}T00:00:00Z", size),
+            metadata: ProgramMetadata {
+                toolchain: Some("cargo-prove".to_string()),
+                commit: Some(commit.to_string()),
+                zkvm: zkvm.to_string(),
+            },
+            sha256: None,
+            signature: None,
+            provenance: None,
+            artifacts: Default::default(),
+            uploaded_by: None,
+            oidc_claims: Default::default(),
+        }
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("orders", "orders"));
+        assert!(!glob_match("orders", "orders2"));
+        assert!(glob_match("order*", "orders"));
+        assert!(glob_match("*ers", "orders"));
+        assert!(glob_match("o*d*s", "orders"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("a*a", "a"));
+        assert!(!glob_match("wallet*", "orders"));
+    }
+
+    #[test]
+    fn filters_by_metadata_date_and_size() {
+        let entries = [
+            entry("orders", "a", "risc0", "abc123", 3),
+            entry("orders", "b", "sp1", "abc999", 5),
+            entry("wallet", "c", "RISC0", "def456", 10),
+        ];
+
+        let query = ProgramQuery {
+            zkvm: Some("risc0".to_string()),
+            ..Default::default()
+        };
+        let results = query.apply(entries.iter());
+        assert_eq!(results.total, 2);
+        assert_eq!(results.programs[0].program.program_id, "c");
+
+        let query = ProgramQuery {
+            commit: Some("abc".to_string()),
+            uploaded_after: Some("2024-01-04T00:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+        let results = query.apply(entries.iter());
+        assert_eq!(results.total, 1);
+        assert_eq!(results.programs[0].program.program_id, "b");
+
+        let query = ProgramQuery {
+            contract: Some("wal*".to_string()),
+            max_size: Some(9),
+            ..Default::default()
+        };
+        assert_eq!(query.apply(entries.iter()).total, 0);
+    }
+
+    #[test]
+    fn sorts_and_limits() {
+        let entries = [
+            entry("orders", "a", "sp1", "abc", 3),
+            entry("orders", "b", "sp1", "abc", 5),
+            entry("wallet", "c", "sp1", "abc", 1),
+        ];
+        let query = ProgramQuery {
+            sort: SortField::Size,
+            order: SortOrder::Asc,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let results = query.apply(entries.iter());
+        assert_eq!(results.total, 3);
+        let ids = results
+            .programs
+            .iter()
+            .map(|program| program.program.program_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["c", "a"]);
+    }
+
+    #[test]
+    fn pagination_walks_listing_order_without_gaps() {
+        let entries = [
+            entry("wallet", "c", "sp1", "abc", 2),
+            entry("orders", "a", "sp1", "abc", 3),
+            entry("orders", "b", "sp1", "abc", 5),
+            entry("orders", "d", "sp1", "abc", 5),
+        ];
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next) = paginate(entries.iter(), cursor.as_ref(), 3);
+            seen.extend(
+                page.iter()
+                    .map(|entry| format!("{}/{}", entry.contract, entry.program_id)),
+            );
+            match next {
+                Some(next) => cursor = Some(ListCursor::decode(&next.encode()).unwrap()),
+                None => break,
+            }
+        }
+        assert_eq!(seen, vec!["orders/b", "orders/d", "orders/a", "wallet/c"]);
+        assert!(ListCursor::decode("not-a-cursor").is_err());
+    }
+}
@@ -1,19 +1,42 @@
+use crate::artifacts::{ArtifactEntry, ArtifactUpload, PutArtifact, MAX_ARTIFACTS_PER_PROGRAM};
+use crate::cache::BinaryCache;
+use crate::chain::{program_status, ChainStatus, ContractChainInfo, OnChainProgram};
 use crate::conf::Conf;
+use crate::events::{EventLog, EventReplay, LoggedEvent, RegistryEvent};
+use crate::metrics::RegistryMetrics;
+use crate::ownership::{ContractOwnership, UploadClaim};
+use crate::provenance::{Provenance, ProvenanceSummary};
+use crate::query::{
+    listing_order, paginate, ListCursor, ProgramMatch, ProgramPage, ProgramQuery, SearchResults,
+};
+use crate::signing::UploadSignature;
+use crate::stats::{CacheStats, RegistryStats};
 use crate::storage::{GcsStorageBackend, LocalStorageBackend, StorageBackend};
+use crate::transparency::{LogLeaf, TransparencyLog};
+use crate::upstream::{merge_listings, Upstream};
 use anyhow::{anyhow, Context, Result};
 use bytes::Bytes;
-use chrono::Utc;
-use prometheus::{HistogramVec, IntCounter, IntCounterVec, Opts};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, Mutex, OnceCell, RwLock};
 use tokio::time::Instant;
-use tracing::info;
+use tracing::{info, warn};
 
-const INDEX_FILE_NAME: &str = "index.json";
+pub(crate) const INDEX_FILE_NAME: &str = "index.json";
+/// Suffix of the provenance documents stored next to ELFs, which are not program metadata.
+pub(crate) const PROVENANCE_SUFFIX: &str = ".provenance.json";
+/// Suffix of the artifacts attached to programs, never taken for metadata
+/// whatever their name.
+pub(crate) const ARTIFACT_SUFFIX: &str = ".artifact";
+/// Objects the server keeps for itself (webhook queue, ...) live under this
+/// prefix, which is never a contract directory.
+pub const INTERNAL_PREFIX: &str = "_registry";
+
+type InflightDownload = Arc<OnceCell<Result<Option<Bytes>, Arc<anyhow::Error>>>>;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct IndexFile {
@@ -23,6 +46,135 @@ pub struct IndexFile {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ContractIndex {
     pub programs: HashMap<String, ProgramEntry>,
+    /// Owner of the contract; owned contracts stay in the index without programs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<ContractOwnership>,
+    /// Hidden from listings, its programs need a key allowed to read it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub private: bool,
+}
+
+impl IndexFile {
+    /// Settings of the contracts that have some.
+    pub fn settings(&self) -> BTreeMap<String, ContractSettings> {
+        self.contracts
+            .iter()
+            .filter(|(_, contract_entry)| {
+                contract_entry.ownership.is_some() || contract_entry.private
+            })
+            .map(|(contract, contract_entry)| {
+                (
+                    contract.clone(),
+                    ContractSettings {
+                        ownership: contract_entry.ownership.clone(),
+                        private: contract_entry.private,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces the settings of every contract with `settings`.
+    fn apply_settings(&mut self, settings: BTreeMap<String, ContractSettings>) {
+        for contract_entry in self.contracts.values_mut() {
+            contract_entry.ownership = None;
+            contract_entry.private = false;
+        }
+        for (contract, settings) in settings {
+            let contract_entry = self.contracts.entry(contract).or_default();
+            contract_entry.ownership = settings.ownership;
+            contract_entry.private = settings.private;
+        }
+        self.contracts
+            .retain(|_, contract_entry| !contract_entry.is_unused());
+    }
+}
+
+/// Ownership and visibility of a contract. They are also stored apart from
+/// the index, so that rebuilding the index from the metadata files keeps them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<ContractOwnership>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub private: bool,
+}
+
+fn contract_settings_object_path() -> String {
+    format!("{INTERNAL_PREFIX}/contracts.json")
+}
+
+/// The index and the contract settings it holds, serialized together.
+struct IndexBytes {
+    index: Vec<u8>,
+    settings: Vec<u8>,
+}
+
+fn serialize_with_settings(index: &IndexFile) -> Result<IndexBytes> {
+    Ok(IndexBytes {
+        index: serde_json::to_vec(index).context("serializing index")?,
+        settings: serde_json::to_vec(&index.settings()).context("serializing contract settings")?,
+    })
+}
+
+impl ContractIndex {
+    /// Whether the entry can leave the index: no programs and no settings.
+    fn is_unused(&self) -> bool {
+        self.programs.is_empty() && self.ownership.is_none() && !self.private
+    }
+
+    /// Owner of a contract uploaded to before ownership existed: whoever
+    /// uploaded its latest program, if that is known.
+    fn legacy_ownership(&self) -> Option<ContractOwnership> {
+        if self.ownership.is_some() {
+            return None;
+        }
+        let latest = self
+            .programs
+            .values()
+            .filter(|entry| entry.uploaded_by.is_some())
+            .max_by(|a, b| a.uploaded_at.cmp(&b.uploaded_at))?;
+        Some(ContractOwnership {
+            owner: latest.uploaded_by.clone()?,
+            collaborators: Default::default(),
+            claimed_at: Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+/// Contracts a listing includes: every public contract, and the private
+/// contracts the caller may read.
+#[derive(Debug, Clone, Default)]
+pub struct VisibleContracts {
+    all: bool,
+    private: HashSet<String>,
+}
+
+impl VisibleContracts {
+    /// Every contract, private ones included.
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            private: HashSet::new(),
+        }
+    }
+
+    /// Public contracts and the given private contracts.
+    pub fn public_and(private: HashSet<String>) -> Self {
+        Self {
+            all: false,
+            private,
+        }
+    }
+
+    /// Whether `contract` is shown should it be private.
+    pub fn shows_private(&self, contract: &str) -> bool {
+        self.all || self.private.contains(contract)
+    }
+
+    pub(crate) fn includes(&self, contract: &str, contract_entry: &ContractIndex) -> bool {
+        !contract_entry.private || self.shows_private(contract)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +186,39 @@ pub struct ProgramEntry {
     pub size_bytes: u64,
     pub uploaded_at: String,
     pub metadata: ProgramMetadata,
+    /// Hex SHA-256 of the ELF; missing for programs stored before it was
+    /// recorded, until their manifest is first built.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Publisher signature, for signed uploads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<UploadSignature>,
+    /// Set when a provenance document is stored next to the ELF.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<ProvenanceSummary>,
+    /// Files attached to the program, by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub artifacts: BTreeMap<String, ArtifactEntry>,
+    /// Name of the API key the program was uploaded with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploaded_by: Option<String>,
+    /// Claims of the OIDC token the program was uploaded with (repository, ref, workflow, ...).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub oidc_claims: BTreeMap<String, String>,
+}
+
+impl ProgramEntry {
+    pub fn uploaded_at(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.uploaded_at)
+            .ok()
+            .map(|uploaded_at| uploaded_at.with_timezone(&Utc))
+    }
+
+    pub fn uploaded_at_micros(&self) -> i64 {
+        self.uploaded_at()
+            .map(|uploaded_at| uploaded_at.timestamp_micros())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,21 +228,99 @@ pub struct ProgramMetadata {
     pub zkvm: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProgramInfo {
     pub program_id: String,
     pub size_bytes: u64,
     pub uploaded_at: String,
     pub metadata: ProgramMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<UploadSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<ProvenanceSummary>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub artifacts: BTreeMap<String, ArtifactEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploaded_by: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub oidc_claims: BTreeMap<String, String>,
+    /// Set when on-chain status tracking is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_status: Option<ChainStatus>,
+}
+
+/// Documents and details stored along with an uploaded ELF.
+#[derive(Debug, Clone, Default)]
+pub struct UploadAttachments {
+    /// Publisher signature, already verified by the caller.
+    pub signature: Option<UploadSignature>,
+    /// Build provenance, already validated by the caller.
+    pub provenance: Option<Provenance>,
+    /// Attached files, with distinct validated names.
+    pub artifacts: Vec<ArtifactUpload>,
+    /// Name of the API key, or OIDC policy, that authorized the upload.
+    pub uploaded_by: Option<String>,
+    /// Claims of the OIDC token that authorized the upload.
+    pub oidc_claims: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContractSummary {
+    pub contract: String,
+    pub program_count: usize,
+    pub total_bytes: u64,
+    pub latest_upload: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<ContractOwnership>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub private: bool,
+    /// Program registered on chain, when tracking is enabled and the contract is deployed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_chain: Option<ContractChainInfo>,
+}
+
+/// How cache warm-up ranks the programs of each contract.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WarmupStrategy {
+    /// Most recently uploaded programs first.
+    #[default]
+    Recent,
+    /// Most downloaded programs (since the server started) first.
+    Downloads,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WarmupOptions {
+    pub strategy: WarmupStrategy,
+    /// Total size of the binaries to preload.
+    pub max_bytes: u64,
+    /// Maximum number of programs preloaded per contract.
+    pub programs_per_contract: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WarmupSummary {
+    pub programs: usize,
+    pub bytes: u64,
 }
 
 impl ProgramInfo {
-    fn from_entry(entry: &ProgramEntry) -> Self {
+    pub fn from_entry(entry: &ProgramEntry) -> Self {
         Self {
             program_id: entry.program_id.clone(),
             size_bytes: entry.size_bytes,
             uploaded_at: entry.uploaded_at.clone(),
             metadata: entry.metadata.clone(),
+            sha256: entry.sha256.clone(),
+            signature: entry.signature.clone(),
+            provenance: entry.provenance.clone(),
+            artifacts: entry.artifacts.clone(),
+            uploaded_by: entry.uploaded_by.clone(),
+            oidc_claims: entry.oidc_claims.clone(),
+            chain_status: None,
         }
     }
 }
@@ -66,14 +329,37 @@ pub struct RegistryService {
     storage: Arc<dyn StorageBackend>,
     index: Arc<RwLock<IndexFile>>,
     cache: Arc<RwLock<BinaryCache>>,
+    inflight: Mutex<HashMap<(String, String), InflightDownload>>,
+    download_counts: std::sync::Mutex<HashMap<(String, String), u64>>,
     metrics: RegistryMetrics,
+    events: EventLog,
+    /// On-chain program per contract, `None` while chain tracking is disabled.
+    chain: std::sync::RwLock<Option<HashMap<String, OnChainProgram>>>,
+    /// Registry mirrored on misses, when `upstream_url` is set.
+    upstream: Option<Upstream>,
+    read_only: bool,
+    /// Every upload ever stored, for auditing.
+    transparency: TransparencyLog,
 }
 
 impl RegistryService {
-    pub async fn new(config: &Conf) -> Result<Self> {
+    /// Loads the index from the configured storage backend and, when
+    /// `cache_warmup_max_bytes` is set, starts preloading binaries in the background.
+    pub async fn new(config: &Conf) -> Result<Arc<Self>> {
         let storage = create_storage_backend(config).await?;
-        let metrics = RegistryMetrics::new()?;
+        Self::with_storage(config, storage, RegistryMetrics::new()?).await
+    }
+
+    /// Same as [`RegistryService::new`] on top of an existing storage backend.
+    pub async fn with_storage(
+        config: &Conf,
+        storage: Arc<dyn StorageBackend>,
+        metrics: RegistryMetrics,
+    ) -> Result<Arc<Self>> {
         let index = load_or_rebuild_index(storage.as_ref(), &metrics).await?;
+        metrics.record_index(&RegistryStats::from_index(&index));
+        let upstream = Upstream::from_conf(config, metrics.upstream_fetches.clone())?;
+        let transparency = TransparencyLog::load(storage.clone()).await?;
 
         info!(
             "Registry initialized with {} contracts and {} programs",
@@ -84,48 +370,392 @@ impl RegistryService {
                 .map(|entry| entry.programs.len() as u64)
                 .sum::<u64>()
         );
-        Ok(Self {
+        let registry = Arc::new(Self {
             storage,
             index: Arc::new(RwLock::new(index)),
-            cache: Arc::new(RwLock::new(BinaryCache::default())),
+            cache: Arc::new(RwLock::new(BinaryCache::new(config.binary_cache_max_bytes))),
+            inflight: Mutex::new(HashMap::new()),
+            download_counts: std::sync::Mutex::new(HashMap::new()),
             metrics,
-        })
+            events: EventLog::new(config.event_log_capacity),
+            chain: std::sync::RwLock::new(None),
+            upstream,
+            read_only: config.upstream_read_only,
+            transparency,
+        });
+
+        if config.cache_warmup_max_bytes > 0 {
+            registry
+                .spawn_warmup(WarmupOptions {
+                    strategy: WarmupStrategy::Recent,
+                    max_bytes: config.cache_warmup_max_bytes,
+                    programs_per_contract: config.cache_warmup_programs_per_contract,
+                })
+                .await;
+        }
+
+        Ok(registry)
+    }
+
+    pub fn storage(&self) -> Arc<dyn StorageBackend> {
+        self.storage.clone()
+    }
+
+    pub fn transparency(&self) -> &TransparencyLog {
+        &self.transparency
+    }
+
+    /// Whether local uploads and deletes are disabled (`upstream_read_only`).
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Receives every registry event published after the call.
+    pub fn subscribe(&self) -> broadcast::Receiver<LoggedEvent> {
+        self.events.subscribe()
+    }
+
+    /// Recent events after `last_event_id`, followed by the live ones.
+    pub fn resume_events(&self, last_event_id: u64) -> EventReplay {
+        self.events.resume(last_event_id)
+    }
+
+    fn publish(&self, event: RegistryEvent) {
+        self.events.publish(event);
+    }
+
+    pub async fn contract_names(&self) -> Vec<String> {
+        let mut contracts = self
+            .index
+            .read()
+            .await
+            .contracts
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        contracts.sort();
+        contracts
+    }
+
+    /// Last known on-chain programs, empty while chain tracking is disabled.
+    pub fn chain_programs(&self) -> HashMap<String, OnChainProgram> {
+        self.chain
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// Replaces the on-chain programs, which enables chain status in listings.
+    pub async fn set_chain_programs(&self, programs: HashMap<String, OnChainProgram>) {
+        let index = self.index.read().await;
+        *self.chain.write().unwrap_or_else(|err| err.into_inner()) = Some(programs);
+        self.record_missing_elfs(&index);
+    }
+
+    /// Updates the gauges derived from the index, after each change to it.
+    fn record_index(&self, index: &IndexFile) {
+        self.metrics.record_index(&RegistryStats::from_index(index));
+        self.record_missing_elfs(index);
+    }
+
+    fn record_missing_elfs(&self, index: &IndexFile) {
+        let chain = self.chain.read().unwrap_or_else(|err| err.into_inner());
+        let missing = chain.as_ref().map_or(0, |programs| {
+            programs
+                .iter()
+                .filter(|(contract, program)| !has_program(index, contract, program))
+                .count()
+        });
+        self.metrics.contracts_missing_elf.set(missing as i64);
+    }
+
+    fn chain_status(&self, contract: &str, program_id: &str) -> Option<ChainStatus> {
+        let chain = self.chain.read().unwrap_or_else(|err| err.into_inner());
+        chain
+            .as_ref()
+            .map(|programs| program_status(programs.get(contract), program_id))
+    }
+
+    fn program_info(&self, entry: &ProgramEntry) -> ProgramInfo {
+        ProgramInfo {
+            chain_status: self.chain_status(&entry.contract, &entry.program_id),
+            ..ProgramInfo::from_entry(entry)
+        }
+    }
+
+    fn sorted_programs(&self, contract_entry: &ContractIndex) -> Vec<ProgramInfo> {
+        let mut programs = contract_entry.programs.values().collect::<Vec<_>>();
+        programs.sort_by(|a, b| listing_order(a, b));
+        programs
+            .into_iter()
+            .map(|entry| self.program_info(entry))
+            .collect()
+    }
+
+    /// Picks the programs to preload and loads them into the binary cache in
+    /// a background task. Returns what was scheduled.
+    pub async fn spawn_warmup(self: &Arc<Self>, options: WarmupOptions) -> WarmupSummary {
+        let targets = self.warmup_targets(options).await;
+        let summary = WarmupSummary {
+            programs: targets.len(),
+            bytes: targets.iter().map(|(_, _, size)| size).sum(),
+        };
+        info!(
+            "Warming up binary cache with {} programs ({} bytes)",
+            summary.programs, summary.bytes
+        );
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut loaded = 0;
+            for (contract, program_id, _) in targets {
+                if registry
+                    .cache
+                    .read()
+                    .await
+                    .get(&contract, &program_id)
+                    .is_some()
+                {
+                    continue;
+                }
+                match registry.fetch_coalesced(&contract, &program_id).await {
+                    Ok(Some(_)) => loaded += 1,
+                    Ok(None) => {}
+                    Err(err) => warn!("Cache warm-up failed for {contract}/{program_id}: {err:#}"),
+                }
+            }
+            info!("Cache warm-up loaded {loaded} programs");
+        });
+
+        summary
+    }
+
+    /// Ranks each contract's programs by the warm-up strategy, then takes them
+    /// round-robin across contracts until the byte budget is spent.
+    async fn warmup_targets(&self, options: WarmupOptions) -> Vec<(String, String, u64)> {
+        let download_counts = match self.download_counts.lock() {
+            Ok(counts) => counts.clone(),
+            Err(_) => HashMap::new(),
+        };
+        let index = self.index.read().await;
+
+        let mut ranked = index
+            .contracts
+            .iter()
+            .map(|(contract, contract_entry)| {
+                let mut programs = contract_entry.programs.values().collect::<Vec<_>>();
+                programs.sort_by_key(|entry| {
+                    let downloads = match options.strategy {
+                        WarmupStrategy::Recent => 0,
+                        WarmupStrategy::Downloads => download_counts
+                            .get(&(contract.clone(), entry.program_id.clone()))
+                            .copied()
+                            .unwrap_or_default(),
+                    };
+                    std::cmp::Reverse((downloads, entry.uploaded_at_micros()))
+                });
+                programs.truncate(options.programs_per_contract);
+                (contract, programs)
+            })
+            .collect::<Vec<_>>();
+        ranked.sort_by_key(|(contract, _)| *contract);
+
+        let mut targets = Vec::new();
+        let mut budget = options.max_bytes;
+        for rank in 0..options.programs_per_contract {
+            for (contract, programs) in &ranked {
+                let Some(entry) = programs.get(rank) else {
+                    continue;
+                };
+                if entry.size_bytes <= budget {
+                    budget -= entry.size_bytes;
+                    targets.push((
+                        contract.to_string(),
+                        entry.program_id.clone(),
+                        entry.size_bytes,
+                    ));
+                }
+            }
+        }
+        targets
     }
 
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    pub async fn list_all(&self) -> HashMap<String, Vec<ProgramInfo>> {
+    pub async fn list_all(&self, visible: &VisibleContracts) -> BTreeMap<String, Vec<ProgramInfo>> {
         let index = self.index.read().await;
         self.metrics.requests.with_label_values(&["list_all"]).inc();
         index
             .contracts
             .iter()
-            .map(|(contract, entry)| {
-                let programs = entry
-                    .programs
-                    .values()
-                    .map(ProgramInfo::from_entry)
-                    .collect::<Vec<_>>();
-                (contract.clone(), programs)
-            })
+            .filter(|(contract, entry)| visible.includes(contract, entry))
+            .map(|(contract, entry)| (contract.clone(), self.sorted_programs(entry)))
             .collect()
     }
 
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub async fn list_all_page(
+        &self,
+        cursor: Option<&ListCursor>,
+        limit: usize,
+        visible: &VisibleContracts,
+    ) -> ProgramPage<ProgramMatch> {
+        let index = self.index.read().await;
+        self.metrics.requests.with_label_values(&["list_all"]).inc();
+        let (entries, next_cursor) = paginate(
+            index
+                .contracts
+                .iter()
+                .filter(|(contract, entry)| visible.includes(contract, entry))
+                .flat_map(|(_, contract_entry)| contract_entry.programs.values()),
+            cursor,
+            limit,
+        );
+        ProgramPage {
+            programs: entries
+                .into_iter()
+                .map(|entry| ProgramMatch {
+                    contract: entry.contract.clone(),
+                    program: self.program_info(entry),
+                })
+                .collect(),
+            next_cursor: next_cursor.map(|cursor| cursor.encode()),
+        }
+    }
+
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
     pub async fn list_contract(&self, contract: &str) -> Option<Vec<ProgramInfo>> {
+        let local = {
+            let index = self.index.read().await;
+            self.metrics
+                .requests
+                .with_label_values(&["list_contract"])
+                .inc();
+            index
+                .contracts
+                .get(contract)
+                .map(|contract_entry| self.sorted_programs(contract_entry))
+        };
+        let Some(upstream) = &self.upstream else {
+            return local;
+        };
+        let remote = upstream.listing(contract).await.map(|programs| {
+            programs
+                .into_iter()
+                .map(|program| ProgramInfo {
+                    chain_status: self.chain_status(contract, &program.program_id),
+                    ..program
+                })
+                .collect()
+        });
+        merge_listings(local, remote)
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub async fn list_contract_page(
+        &self,
+        contract: &str,
+        cursor: Option<&ListCursor>,
+        limit: usize,
+    ) -> Option<ProgramPage<ProgramInfo>> {
         let index = self.index.read().await;
         self.metrics
             .requests
             .with_label_values(&["list_contract"])
             .inc();
-        index.contracts.get(contract).map(|entry| {
-            entry
-                .programs
-                .values()
-                .map(ProgramInfo::from_entry)
-                .collect()
+        let contract_entry = index.contracts.get(contract)?;
+        let (entries, next_cursor) = paginate(contract_entry.programs.values(), cursor, limit);
+        Some(ProgramPage {
+            programs: entries
+                .into_iter()
+                .map(|entry| self.program_info(entry))
+                .collect(),
+            next_cursor: next_cursor.map(|cursor| cursor.encode()),
         })
     }
 
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub async fn contract_summaries(&self, visible: &VisibleContracts) -> Vec<ContractSummary> {
+        let index = self.index.read().await;
+        self.metrics
+            .requests
+            .with_label_values(&["list_contracts"])
+            .inc();
+        let chain = self.chain_programs();
+        let mut summaries = index
+            .contracts
+            .iter()
+            .filter(|(contract, entry)| visible.includes(contract, entry))
+            .map(|(contract, entry)| ContractSummary {
+                contract: contract.clone(),
+                program_count: entry.programs.len(),
+                total_bytes: entry.programs.values().map(|p| p.size_bytes).sum(),
+                latest_upload: entry
+                    .programs
+                    .values()
+                    .max_by_key(|program| program.uploaded_at_micros())
+                    .map(|program| program.uploaded_at.clone()),
+                ownership: entry.ownership.clone(),
+                private: entry.private,
+                on_chain: chain.get(contract).map(|program| ContractChainInfo {
+                    program: program.clone(),
+                    missing_elf: !entry
+                        .programs
+                        .keys()
+                        .any(|program_id| program.matches(program_id)),
+                }),
+            })
+            .collect::<Vec<_>>();
+        summaries.sort_by(|a, b| a.contract.cmp(&b.contract));
+        summaries
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    /// Statistics of the contracts `visible` includes, cache included.
+    pub async fn stats(&self, visible: &VisibleContracts) -> RegistryStats {
+        let index = self.index.read().await;
+        let mut stats = RegistryStats::from_visible(&index, visible);
+        self.metrics.requests.with_label_values(&["stats"]).inc();
+        let cache = self.cache.read().await;
+        let programs = cache
+            .entry_stats()
+            .into_iter()
+            .filter(|program| {
+                index
+                    .contracts
+                    .get(&program.contract)
+                    .is_none_or(|contract_entry| {
+                        visible.includes(&program.contract, contract_entry)
+                    })
+            })
+            .collect::<Vec<_>>();
+        stats.cache = CacheStats {
+            entries: programs.len(),
+            bytes: programs.iter().map(|program| program.size_bytes).sum(),
+            max_bytes: cache.max_bytes(),
+            programs,
+        };
+        stats
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub async fn search(&self, query: &ProgramQuery, visible: &VisibleContracts) -> SearchResults {
+        let index = self.index.read().await;
+        self.metrics.requests.with_label_values(&["search"]).inc();
+        let mut results = query.apply(
+            index
+                .contracts
+                .iter()
+                .filter(|(contract, entry)| visible.includes(contract, entry))
+                .flat_map(|(_, contract_entry)| contract_entry.programs.values()),
+        );
+        for program in &mut results.programs {
+            program.program.chain_status =
+                self.chain_status(&program.contract, &program.program.program_id);
+        }
+        results
+    }
+
     #[cfg_attr(
         feature = "instrumentation",
         tracing::instrument(skip(self, program_id, metadata, bytes))
@@ -136,11 +766,63 @@ impl RegistryService {
         program_id: &str,
         metadata: ProgramMetadata,
         bytes: Bytes,
+    ) -> Result<ProgramEntry> {
+        self.upload_with(
+            contract,
+            program_id,
+            metadata,
+            UploadAttachments::default(),
+            bytes,
+        )
+        .await
+    }
+
+    /// Uploads a program along with its signature and provenance.
+    pub async fn upload_with(
+        &self,
+        contract: &str,
+        program_id: &str,
+        metadata: ProgramMetadata,
+        attachments: UploadAttachments,
+        bytes: Bytes,
+    ) -> Result<ProgramEntry> {
+        let size_bytes = bytes.len() as u64;
+        let entry = self
+            .store_program(
+                contract,
+                program_id,
+                metadata,
+                attachments,
+                Utc::now().to_rfc3339(),
+                bytes,
+            )
+            .await?;
+
+        self.metrics.requests.with_label_values(&["upload"]).inc();
+        self.metrics
+            .bytes
+            .with_label_values(&["upload"])
+            .inc_by(size_bytes);
+
+        Ok(entry)
+    }
+
+    /// Stores a program, records it in the transparency log and adds it to the
+    /// index, replacing any previous version, then publishes it.
+    async fn store_program(
+        &self,
+        contract: &str,
+        program_id: &str,
+        metadata: ProgramMetadata,
+        attachments: UploadAttachments,
+        uploaded_at: String,
+        bytes: Bytes,
     ) -> Result<ProgramEntry> {
         let object_path = binary_object_path(contract, program_id);
         let metadata_path = metadata_object_path(contract, program_id);
+        let provenance_path = provenance_object_path(contract, program_id);
         let size_bytes = bytes.len() as u64;
-        let uploaded_at = Utc::now().to_rfc3339();
+        let sha256 = hex::encode(Sha256::digest(&bytes));
 
         let storage_start = Instant::now();
         self.storage
@@ -152,6 +834,26 @@ impl RegistryService {
             .with_label_values(&["write", self.storage.name()])
             .observe(storage_start.elapsed().as_secs_f64());
 
+        if let Some(provenance) = &attachments.provenance {
+            let provenance_bytes =
+                serde_json::to_vec(provenance).context("serializing provenance")?;
+            self.storage
+                .write_object(&provenance_path, &provenance_bytes)
+                .await
+                .context("storing provenance")?;
+        }
+        let mut artifacts = BTreeMap::new();
+        for artifact in &attachments.artifacts {
+            self.storage
+                .write_object(
+                    &artifact_object_path(contract, program_id, &artifact.name),
+                    &artifact.bytes,
+                )
+                .await
+                .with_context(|| format!("storing artifact {}", artifact.name))?;
+            artifacts.insert(artifact.name.clone(), artifact.entry());
+        }
+
         let entry = ProgramEntry {
             program_id: program_id.to_string(),
             contract: contract.to_string(),
@@ -160,6 +862,12 @@ impl RegistryService {
             size_bytes,
             uploaded_at,
             metadata,
+            sha256: Some(sha256.clone()),
+            signature: attachments.signature,
+            provenance: attachments.provenance.as_ref().map(Provenance::summary),
+            artifacts,
+            uploaded_by: attachments.uploaded_by,
+            oidc_claims: attachments.oidc_claims,
         };
 
         let metadata_bytes = serde_json::to_vec(&entry).context("serializing metadata")?;
@@ -173,13 +881,29 @@ impl RegistryService {
             .with_label_values(&["write_metadata", self.storage.name()])
             .observe(metadata_start.elapsed().as_secs_f64());
 
-        let index_bytes = {
+        // Logged before the index makes the program downloadable, so that
+        // nothing is served that the log has not seen.
+        self.transparency
+            .append(LogLeaf {
+                contract: contract.to_string(),
+                program_id: program_id.to_string(),
+                sha256,
+                uploaded_at: entry.uploaded_at.clone(),
+            })
+            .await
+            .context("recording the upload in the transparency log")?;
+
+        let (previous, index_bytes) = {
             let mut index = self.index.write().await;
             let contract_entry = index.contracts.entry(contract.to_string()).or_default();
-            contract_entry
+            let previous = contract_entry
                 .programs
                 .insert(program_id.to_string(), entry.clone());
-            serde_json::to_vec(&*index).context("serializing index")?
+            self.record_index(&index);
+            (
+                previous,
+                serde_json::to_vec(&*index).context("serializing index")?,
+            )
         };
 
         let index_start = Instant::now();
@@ -192,67 +916,234 @@ impl RegistryService {
             .with_label_values(&["write_index", self.storage.name()])
             .observe(index_start.elapsed().as_secs_f64());
 
+        // Drop the documents of the version being replaced.
+        if let Some(previous) = previous {
+            if previous.provenance.is_some() && entry.provenance.is_none() {
+                self.storage
+                    .delete_object(&provenance_path)
+                    .await
+                    .context("deleting previous provenance")?;
+            }
+            for name in previous.artifacts.keys() {
+                if !entry.artifacts.contains_key(name) {
+                    self.storage
+                        .delete_object(&artifact_object_path(contract, program_id, name))
+                        .await
+                        .with_context(|| format!("deleting previous artifact {name}"))?;
+                }
+            }
+        }
+
         {
             let mut cache = self.cache.write().await;
-            cache.insert(contract, program_id, bytes);
+            let evictions = cache.insert(contract, program_id, bytes);
+            self.metrics.record_cache(&cache, evictions);
         }
 
-        self.metrics.requests.with_label_values(&["upload"]).inc();
-        self.metrics
-            .bytes
-            .with_label_values(&["upload"])
-            .inc_by(size_bytes);
+        self.publish(RegistryEvent::ProgramUploaded {
+            contract: contract.to_string(),
+            program: Box::new(ProgramInfo::from_entry(&entry)),
+        });
 
         Ok(entry)
     }
 
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
     pub async fn download(&self, contract: &str, program_id: &str) -> Result<Option<Bytes>> {
-        if let Some(bytes) = self.cache.write().await.get_and_touch(contract, program_id) {
-            self.metrics.cache_hits.inc();
-            self.metrics.requests.with_label_values(&["download"]).inc();
-            self.metrics
-                .bytes
-                .with_label_values(&["download"])
-                .inc_by(bytes.len() as u64);
-            return Ok(Some(bytes));
-        }
-        self.metrics.cache_misses.inc();
-
-        let object_path = {
-            let index = self.index.read().await;
-            let entry = index
-                .contracts
-                .get(contract)
-                .and_then(|contract_entry| contract_entry.programs.get(program_id))
-                .cloned();
-            match entry {
-                Some(entry) => entry.object_path,
-                None => return Ok(None),
+        let cached = self.cache.read().await.get(contract, program_id);
+        let bytes = match cached {
+            Some(bytes) => {
+                self.metrics.cache_hits.inc();
+                bytes
+            }
+            None => {
+                self.metrics.cache_misses.inc();
+                match self.fetch_coalesced(contract, program_id).await? {
+                    Some(bytes) => bytes,
+                    None => return Ok(None),
+                }
             }
         };
 
-        let start = Instant::now();
-        let bytes = match self.storage.read_object(&object_path).await? {
-            Some(bytes) => Bytes::from(bytes),
-            None => return Ok(None),
-        };
+        if let Ok(mut counts) = self.download_counts.lock() {
+            *counts
+                .entry((contract.to_string(), program_id.to_string()))
+                .or_default() += 1;
+        }
+        self.metrics.requests.with_label_values(&["download"]).inc();
+        self.metrics
+            .bytes
+            .with_label_values(&["download"])
+            .inc_by(bytes.len() as u64);
+
+        Ok(Some(bytes))
+    }
+
+    pub async fn has_program(&self, contract: &str, program_id: &str) -> bool {
+        self.program_entry(contract, program_id).await.is_some()
+    }
+
+    /// URL of the storage backend a stored program can be downloaded from
+    /// directly until `expires` has elapsed. `None` when the program is not
+    /// stored here or the backend cannot sign URLs.
+    pub async fn signed_download_url(
+        &self,
+        contract: &str,
+        program_id: &str,
+        expires: std::time::Duration,
+    ) -> Result<Option<String>> {
+        let Some(entry) = self.program_entry(contract, program_id).await else {
+            return Ok(None);
+        };
+        let url = self.storage.signed_url(&entry.object_path, expires).await?;
+        if url.is_some() {
+            if let Ok(mut counts) = self.download_counts.lock() {
+                *counts
+                    .entry((contract.to_string(), program_id.to_string()))
+                    .or_default() += 1;
+            }
+            self.metrics.requests.with_label_values(&["download"]).inc();
+        }
+        Ok(url)
+    }
+
+    /// Reads a binary from storage on a cache miss. Concurrent misses for the
+    /// same program wait on a single storage read and share its result.
+    async fn fetch_coalesced(&self, contract: &str, program_id: &str) -> Result<Option<Bytes>> {
+        let key = (contract.to_string(), program_id.to_string());
+        let flight = {
+            let mut inflight = self.inflight.lock().await;
+            match inflight.get(&key) {
+                Some(flight) => {
+                    self.metrics.download_coalesced.inc();
+                    flight.clone()
+                }
+                None => {
+                    let flight = InflightDownload::default();
+                    inflight.insert(key.clone(), flight.clone());
+                    flight
+                }
+            }
+        };
+
+        let result = flight
+            .get_or_init(|| async {
+                self.read_and_cache(contract, program_id)
+                    .await
+                    .map_err(Arc::new)
+            })
+            .await
+            .clone();
+
+        {
+            let mut inflight = self.inflight.lock().await;
+            if inflight
+                .get(&key)
+                .is_some_and(|current| Arc::ptr_eq(current, &flight))
+            {
+                inflight.remove(&key);
+            }
+        }
+
+        result.map_err(|err| anyhow!("{err:#}"))
+    }
+
+    async fn read_and_cache(&self, contract: &str, program_id: &str) -> Result<Option<Bytes>> {
+        let entry = {
+            let index = self.index.read().await;
+            index
+                .contracts
+                .get(contract)
+                .and_then(|contract_entry| contract_entry.programs.get(program_id))
+                .cloned()
+        };
+        let Some(entry) = entry else {
+            return match &self.upstream {
+                Some(upstream) => self.mirror(upstream, contract, program_id).await,
+                None => Ok(None),
+            };
+        };
+
+        let start = Instant::now();
+        let bytes = match self.storage.read_object(&entry.object_path).await? {
+            Some(bytes) => Bytes::from(bytes),
+            None => return Ok(None),
+        };
         self.metrics
             .storage_latency
             .with_label_values(&["read", self.storage.name()])
             .observe(start.elapsed().as_secs_f64());
 
-        {
+        // Only cache the binary if it was not deleted or overwritten meanwhile.
+        let index = self.index.read().await;
+        let unchanged = index
+            .contracts
+            .get(contract)
+            .and_then(|contract_entry| contract_entry.programs.get(program_id))
+            .is_some_and(|current| current.uploaded_at == entry.uploaded_at);
+        if unchanged {
             let mut cache = self.cache.write().await;
-            cache.insert(contract, program_id, bytes.clone());
+            let evictions = cache.insert(contract, program_id, bytes.clone());
+            self.metrics.record_cache(&cache, evictions);
         }
 
-        self.metrics.requests.with_label_values(&["download"]).inc();
-        self.metrics
-            .bytes
-            .with_label_values(&["download"])
-            .inc_by(bytes.len() as u64);
+        Ok(Some(bytes))
+    }
 
+    /// Fetches a program missing locally from the upstream and stores it with
+    /// its upstream metadata.
+    async fn mirror(
+        &self,
+        upstream: &Upstream,
+        contract: &str,
+        program_id: &str,
+    ) -> Result<Option<Bytes>> {
+        if is_reserved_contract(contract) {
+            return Ok(None);
+        }
+        let Some(program) = upstream.listing(contract).await.and_then(|programs| {
+            programs
+                .into_iter()
+                .find(|program| program.program_id == program_id)
+        }) else {
+            return Ok(None);
+        };
+        let Some(bytes) = upstream.download(contract, &program).await? else {
+            return Ok(None);
+        };
+        let provenance = match program.provenance {
+            Some(_) => upstream.provenance(contract, program_id).await?,
+            None => None,
+        };
+        let mut artifacts = Vec::new();
+        for (name, artifact) in &program.artifacts {
+            let bytes = upstream
+                .artifact(contract, program_id, name, artifact)
+                .await?
+                .ok_or_else(|| anyhow!("artifact {name} of {contract}/{program_id} not found"))?;
+            artifacts.push(
+                ArtifactUpload::new(name, Some(&artifact.content_type), bytes).map_err(|err| {
+                    anyhow!("upstream artifact of {contract}/{program_id}: {err}")
+                })?,
+            );
+        }
+        self.store_program(
+            contract,
+            program_id,
+            program.metadata,
+            UploadAttachments {
+                signature: program.signature,
+                provenance,
+                artifacts,
+                uploaded_by: program.uploaded_by,
+                oidc_claims: program.oidc_claims,
+            },
+            program.uploaded_at,
+            bytes.clone(),
+        )
+        .await
+        .context("storing mirrored program")?;
+        info!("Mirrored {contract}/{program_id} from upstream");
         Ok(Some(bytes))
     }
 
@@ -270,33 +1161,32 @@ impl RegistryService {
             return Ok(false);
         };
 
-        self.storage
-            .delete_object(&entry.object_path)
-            .await
-            .context("deleting elf")?;
-        self.storage
-            .delete_object(&entry.metadata_path)
-            .await
-            .context("deleting metadata")?;
+        self.delete_program_objects(&entry).await?;
 
         let index_bytes = {
             let mut index = self.index.write().await;
             if let Some(contract_entry) = index.contracts.get_mut(contract) {
+                contract_entry.ownership = contract_entry
+                    .ownership
+                    .take()
+                    .or_else(|| contract_entry.legacy_ownership());
                 contract_entry.programs.remove(program_id);
-                if contract_entry.programs.is_empty() {
+                if contract_entry.is_unused() {
                     index.contracts.remove(contract);
                 }
             }
-            serde_json::to_vec(&*index).context("serializing index")?
+            self.record_index(&index);
+            serialize_with_settings(&index)?
         };
-        self.storage
-            .write_object(INDEX_FILE_NAME, &index_bytes)
-            .await
-            .context("writing index")?;
+        self.write_index_with_settings(index_bytes).await?;
 
         {
             let mut cache = self.cache.write().await;
             cache.remove_program(contract, program_id);
+            self.metrics.record_cache(&cache, 0);
+        }
+        if let Ok(mut counts) = self.download_counts.lock() {
+            counts.remove(&(contract.to_string(), program_id.to_string()));
         }
 
         self.metrics
@@ -304,9 +1194,41 @@ impl RegistryService {
             .with_label_values(&["delete_program"])
             .inc();
 
+        self.publish(RegistryEvent::ProgramDeleted {
+            contract: contract.to_string(),
+            program_id: program_id.to_string(),
+        });
+
         Ok(true)
     }
 
+    /// Deletes the ELF of a program with its metadata, provenance and artifacts.
+    async fn delete_program_objects(&self, entry: &ProgramEntry) -> Result<()> {
+        self.storage
+            .delete_object(&entry.object_path)
+            .await
+            .with_context(|| format!("deleting elf {}", entry.object_path))?;
+        self.storage
+            .delete_object(&entry.metadata_path)
+            .await
+            .with_context(|| format!("deleting metadata {}", entry.metadata_path))?;
+        if entry.provenance.is_some() {
+            let provenance_path = provenance_object_path(&entry.contract, &entry.program_id);
+            self.storage
+                .delete_object(&provenance_path)
+                .await
+                .with_context(|| format!("deleting provenance {provenance_path}"))?;
+        }
+        for name in entry.artifacts.keys() {
+            let artifact_path = artifact_object_path(&entry.contract, &entry.program_id, name);
+            self.storage
+                .delete_object(&artifact_path)
+                .await
+                .with_context(|| format!("deleting artifact {artifact_path}"))?;
+        }
+        Ok(())
+    }
+
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
     pub async fn delete_contract(&self, contract: &str) -> Result<bool> {
         let entries = {
@@ -314,6 +1236,7 @@ impl RegistryService {
             index
                 .contracts
                 .get(contract)
+                .filter(|entry| !entry.programs.is_empty())
                 .map(|entry| entry.programs.values().cloned().collect::<Vec<_>>())
         };
         let Some(entries) = entries else {
@@ -321,29 +1244,34 @@ impl RegistryService {
         };
 
         for entry in &entries {
-            self.storage
-                .delete_object(&entry.object_path)
-                .await
-                .with_context(|| format!("deleting elf {}", entry.object_path))?;
-            self.storage
-                .delete_object(&entry.metadata_path)
-                .await
-                .with_context(|| format!("deleting metadata {}", entry.metadata_path))?;
+            self.delete_program_objects(entry).await?;
         }
 
         let index_bytes = {
             let mut index = self.index.write().await;
-            index.contracts.remove(contract);
-            serde_json::to_vec(&*index).context("serializing index")?
+            // Ownership and visibility stay until an admin releases the contract.
+            if let Some(contract_entry) = index.contracts.get_mut(contract) {
+                contract_entry.ownership = contract_entry
+                    .ownership
+                    .take()
+                    .or_else(|| contract_entry.legacy_ownership());
+                contract_entry.programs.clear();
+                if contract_entry.is_unused() {
+                    index.contracts.remove(contract);
+                }
+            }
+            self.record_index(&index);
+            serialize_with_settings(&index)?
         };
-        self.storage
-            .write_object(INDEX_FILE_NAME, &index_bytes)
-            .await
-            .context("writing index")?;
+        self.write_index_with_settings(index_bytes).await?;
 
         {
             let mut cache = self.cache.write().await;
             cache.remove_contract(contract);
+            self.metrics.record_cache(&cache, 0);
+        }
+        if let Ok(mut counts) = self.download_counts.lock() {
+            counts.retain(|(counted_contract, _), _| counted_contract != contract);
         }
 
         self.metrics
@@ -351,202 +1279,672 @@ impl RegistryService {
             .with_label_values(&["delete_contract"])
             .inc();
 
+        self.publish(RegistryEvent::ContractDeleted {
+            contract: contract.to_string(),
+        });
+
         Ok(true)
     }
-}
 
-#[derive(Default)]
-struct BinaryCache {
-    per_contract: HashMap<String, VecDeque<CacheEntry>>,
-}
+    /// Writes the contract settings, then the index. Settings are read over
+    /// the index on startup, so they are never behind it.
+    async fn write_index_with_settings(&self, bytes: IndexBytes) -> Result<()> {
+        self.storage
+            .write_object(&contract_settings_object_path(), &bytes.settings)
+            .await
+            .context("writing contract settings")?;
+        self.storage
+            .write_object(INDEX_FILE_NAME, &bytes.index)
+            .await
+            .context("writing index")
+    }
 
-impl BinaryCache {
-    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    fn get_and_touch(&mut self, contract: &str, program_id: &str) -> Option<Bytes> {
-        let entries = self.per_contract.get_mut(contract)?;
-        let position = entries
-            .iter()
-            .position(|entry| entry.program_id == program_id)?;
-        let entry = entries.remove(position)?;
-        let bytes = entry.bytes.clone();
-        entries.push_front(entry);
-        Some(bytes)
+    pub async fn ownership(&self, contract: &str) -> Option<ContractOwnership> {
+        let index = self.index.read().await;
+        index.contracts.get(contract)?.ownership.clone()
     }
 
-    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    fn insert(&mut self, contract: &str, program_id: &str, bytes: Bytes) {
-        let entries = self.per_contract.entry(contract.to_string()).or_default();
-        entries.retain(|entry| entry.program_id != program_id);
-        entries.push_front(CacheEntry {
-            program_id: program_id.to_string(),
-            bytes,
-        });
-        while entries.len() > 2 {
-            entries.pop_back();
+    /// Checks that `identity` may change `contract`, without claiming it.
+    pub async fn check_owner(&self, contract: &str, identity: &str) -> UploadClaim {
+        let index = self.index.read().await;
+        let Some(contract_entry) = index.contracts.get(contract) else {
+            return UploadClaim::Allowed;
+        };
+        match contract_entry
+            .ownership
+            .clone()
+            .or_else(|| contract_entry.legacy_ownership())
+        {
+            Some(ownership) if ownership.allows(identity) => UploadClaim::Allowed,
+            Some(ownership) => UploadClaim::Denied {
+                owner: ownership.owner,
+            },
+            None => UploadClaim::Allowed,
         }
     }
 
-    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    fn remove_program(&mut self, contract: &str, program_id: &str) {
-        if let Some(entries) = self.per_contract.get_mut(contract) {
-            entries.retain(|entry| entry.program_id != program_id);
-            if entries.is_empty() {
-                self.per_contract.remove(contract);
-            }
+    /// Checks that `identity` may upload to `contract`, which is claimed for
+    /// it when nobody owns it yet. Contracts uploaded to before ownership
+    /// existed are given to their latest uploader instead, when it is known.
+    pub async fn claim_for_upload(&self, contract: &str, identity: &str) -> Result<UploadClaim> {
+        let (claim, index_bytes) = {
+            let mut index = self.index.write().await;
+            let contract_entry = index.contracts.entry(contract.to_string()).or_default();
+            let claim = if let Some(ownership) = &contract_entry.ownership {
+                if ownership.allows(identity) {
+                    return Ok(UploadClaim::Allowed);
+                }
+                return Ok(UploadClaim::Denied {
+                    owner: ownership.owner.clone(),
+                });
+            } else if let Some(ownership) = contract_entry.legacy_ownership() {
+                info!(
+                    "Contract {contract} given to {}, its latest uploader",
+                    ownership.owner
+                );
+                let claim = if ownership.allows(identity) {
+                    UploadClaim::Allowed
+                } else {
+                    UploadClaim::Denied {
+                        owner: ownership.owner.clone(),
+                    }
+                };
+                contract_entry.ownership = Some(ownership);
+                claim
+            } else {
+                contract_entry.ownership = Some(ContractOwnership {
+                    owner: identity.to_string(),
+                    collaborators: Default::default(),
+                    claimed_at: Utc::now().to_rfc3339(),
+                });
+                UploadClaim::Claimed
+            };
+            self.record_index(&index);
+            (claim, serialize_with_settings(&index)?)
+        };
+        self.write_index_with_settings(index_bytes).await?;
+        if claim == UploadClaim::Claimed {
+            info!("Contract {contract} claimed by {identity}");
         }
+        Ok(claim)
     }
 
-    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    fn remove_contract(&mut self, contract: &str) {
-        self.per_contract.remove(contract);
-    }
-}
-
-struct CacheEntry {
-    program_id: String,
-    bytes: Bytes,
-}
-
-struct RegistryMetrics {
-    requests: IntCounterVec,
-    bytes: IntCounterVec,
-    cache_hits: IntCounter,
-    cache_misses: IntCounter,
-    index_rebuilds: IntCounter,
-    storage_latency: HistogramVec,
-}
-
-impl RegistryMetrics {
-    fn new() -> Result<Self> {
-        let requests = IntCounterVec::new(
-            Opts::new(
-                "hyli_registry_requests_total",
-                "Total registry requests by operation.",
-            ),
-            &["op"],
-        )?;
-        let bytes = IntCounterVec::new(
-            Opts::new(
-                "hyli_registry_bytes_total",
-                "Total bytes transferred by operation.",
-            ),
-            &["op"],
-        )?;
-        let cache_hits = IntCounter::new("hyli_registry_cache_hits_total", "Registry cache hits.")?;
-        let cache_misses =
-            IntCounter::new("hyli_registry_cache_misses_total", "Registry cache misses.")?;
-        let index_rebuilds =
-            IntCounter::new("hyli_registry_index_rebuilds_total", "Index rebuild count.")?;
-        let storage_latency = HistogramVec::new(
-            prometheus::HistogramOpts::new(
-                "hyli_registry_storage_latency_seconds",
-                "Latency of storage operations.",
-            ),
-            &["op", "backend"],
-        )?;
-
-        let registry = prometheus::default_registry();
-        registry.register(Box::new(requests.clone()))?;
-        registry.register(Box::new(bytes.clone()))?;
-        registry.register(Box::new(cache_hits.clone()))?;
-        registry.register(Box::new(cache_misses.clone()))?;
-        registry.register(Box::new(index_rebuilds.clone()))?;
-        registry.register(Box::new(storage_latency.clone()))?;
-
-        Ok(Self {
-            requests,
-            bytes,
-            cache_hits,
-            cache_misses,
-            index_rebuilds,
-            storage_latency,
+    /// Gives back a contract claimed by `identity` for an upload that failed,
+    /// unless its ownership changed since.
+    pub async fn release_claim(&self, contract: &str, identity: &str) -> Result<()> {
+        let mut released = false;
+        self.update_ownership(contract, |ownership| {
+            if ownership.as_ref().is_some_and(|ownership| {
+                ownership.owner == identity && ownership.collaborators.is_empty()
+            }) {
+                *ownership = None;
+                released = true;
+            }
         })
+        .await?;
+        if released {
+            info!("Contract {contract} released after a failed upload by {identity}");
+        }
+        Ok(())
     }
-}
 
-fn binary_object_path(contract: &str, program_id: &str) -> String {
-    let digest = program_id_digest(program_id);
-    format!("{}/{}.elf", contract, digest)
-}
+    /// Changes the ownership of a contract, which is added to the index if
+    /// needed, and returns the new ownership. The index is only written when
+    /// the ownership changed.
+    pub async fn update_ownership(
+        &self,
+        contract: &str,
+        update: impl FnOnce(&mut Option<ContractOwnership>),
+    ) -> Result<Option<ContractOwnership>> {
+        let (ownership, index_bytes) = {
+            let mut index = self.index.write().await;
+            let mut ownership = index
+                .contracts
+                .get(contract)
+                .and_then(|contract_entry| contract_entry.ownership.clone());
+            let previous = ownership.clone();
+            update(&mut ownership);
+            if ownership == previous {
+                return Ok(ownership);
+            }
+            let contract_entry = index.contracts.entry(contract.to_string()).or_default();
+            contract_entry.ownership = ownership.clone();
+            if contract_entry.is_unused() {
+                index.contracts.remove(contract);
+            }
+            self.record_index(&index);
+            (ownership, serialize_with_settings(&index)?)
+        };
+        self.write_index_with_settings(index_bytes).await?;
+        Ok(ownership)
+    }
 
-fn metadata_object_path(contract: &str, program_id: &str) -> String {
-    let digest = program_id_digest(program_id);
-    format!("{}/{}.json", contract, digest)
-}
+    pub async fn is_private(&self, contract: &str) -> bool {
+        let index = self.index.read().await;
+        index
+            .contracts
+            .get(contract)
+            .is_some_and(|contract_entry| contract_entry.private)
+    }
 
-fn program_id_digest(program_id: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(program_id.as_bytes());
-    hex::encode(hasher.finalize())
-}
+    /// Names of the private contracts.
+    pub async fn private_contracts(&self) -> HashSet<String> {
+        let index = self.index.read().await;
+        index
+            .contracts
+            .iter()
+            .filter(|(_, contract_entry)| contract_entry.private)
+            .map(|(contract, _)| contract.clone())
+            .collect()
+    }
 
-async fn create_storage_backend(config: &Conf) -> Result<Arc<dyn StorageBackend>> {
-    match config.storage_backend.trim().to_lowercase().as_str() {
-        "local" => {
-            let root = config
-                .local_storage_directory
-                .clone()
-                .filter(|path| !path.as_os_str().is_empty())
-                .unwrap_or_else(|| PathBuf::from(&config.data_directory).join("registry"));
-            Ok(Arc::new(LocalStorageBackend::new(root)))
-        }
-        "gcs" => {
-            let bucket = config
-                .gcs_bucket
-                .clone()
-                .filter(|bucket| !bucket.trim().is_empty())
-                .ok_or_else(|| anyhow!("gcs_bucket must be set for gcs backend"))?;
-            let prefix = config
-                .gcs_prefix
-                .clone()
-                .filter(|value| !value.trim().is_empty());
-            let backend = GcsStorageBackend::new(bucket, prefix).await?;
-            Ok(Arc::new(backend))
+    /// Makes a contract private or public, adding it to the index if needed.
+    /// Returns whether the visibility changed.
+    pub async fn set_private(&self, contract: &str, private: bool) -> Result<bool> {
+        let index_bytes = {
+            let mut index = self.index.write().await;
+            let current = index
+                .contracts
+                .get(contract)
+                .is_some_and(|contract_entry| contract_entry.private);
+            if current == private {
+                return Ok(false);
+            }
+            let contract_entry = index.contracts.entry(contract.to_string()).or_default();
+            contract_entry.private = private;
+            if contract_entry.is_unused() {
+                index.contracts.remove(contract);
+            }
+            self.record_index(&index);
+            serialize_with_settings(&index)?
+        };
+        self.write_index_with_settings(index_bytes).await?;
+        Ok(true)
+    }
+
+    /// Provenance document of a stored program, `None` if it has none.
+    pub async fn provenance(&self, contract: &str, program_id: &str) -> Result<Option<Provenance>> {
+        let has_provenance = self
+            .program_entry(contract, program_id)
+            .await
+            .is_some_and(|entry| entry.provenance.is_some());
+        if !has_provenance {
+            return Ok(None);
         }
-        backend => Err(anyhow!("unsupported storage_backend: {backend}")),
+        let Some(bytes) = self
+            .storage
+            .read_object(&provenance_object_path(contract, program_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .context("parsing provenance")
     }
-}
 
-async fn load_or_rebuild_index(
-    storage: &dyn StorageBackend,
-    metrics: &RegistryMetrics,
-) -> Result<IndexFile> {
-    match storage.read_object(INDEX_FILE_NAME).await? {
-        Some(bytes) => {
-            let index: IndexFile = serde_json::from_slice(&bytes).context("parsing index")?;
-            Ok(index)
+    /// Attaches an artifact to a stored program, replacing any artifact of
+    /// the same name.
+    pub async fn put_artifact(
+        &self,
+        contract: &str,
+        program_id: &str,
+        artifact: ArtifactUpload,
+    ) -> Result<PutArtifact> {
+        let Some(current) = self.program_entry(contract, program_id).await else {
+            return Ok(PutArtifact::ProgramNotFound);
+        };
+        if !current.artifacts.contains_key(&artifact.name)
+            && current.artifacts.len() >= MAX_ARTIFACTS_PER_PROGRAM
+        {
+            return Ok(PutArtifact::TooMany);
         }
-        None => {
-            info!("Index file not found, rebuilding index from stored objects");
-            metrics.index_rebuilds.inc();
-            let objects = storage.list_objects(None).await?;
-            let mut index = IndexFile::default();
-            for object in objects {
-                if object == INDEX_FILE_NAME || !object.ends_with(".json") {
-                    continue;
-                }
-                let Some(metadata_bytes) = storage.read_object(&object).await? else {
-                    continue;
-                };
-                let entry: ProgramEntry = match serde_json::from_slice(&metadata_bytes) {
-                    Ok(entry) => entry,
-                    Err(_) => continue,
-                };
-                index
-                    .contracts
-                    .entry(entry.contract.clone())
-                    .or_default()
-                    .programs
-                    .insert(entry.program_id.clone(), entry);
-            }
-            let index_bytes = serde_json::to_vec(&index).context("serializing rebuilt index")?;
-            storage
-                .write_object(INDEX_FILE_NAME, &index_bytes)
+        self.storage
+            .write_object(
+                &artifact_object_path(contract, program_id, &artifact.name),
+                &artifact.bytes,
+            )
+            .await
+            .with_context(|| format!("storing artifact {}", artifact.name))?;
+
+        let stored = artifact.entry();
+        let updated = self
+            .update_program_entry(contract, program_id, |entry| {
+                entry
+                    .artifacts
+                    .insert(artifact.name.clone(), stored.clone());
+            })
+            .await?;
+        if updated.is_none() {
+            // The program was deleted while the artifact was written.
+            self.storage
+                .delete_object(&artifact_object_path(contract, program_id, &artifact.name))
                 .await
-                .context("writing rebuilt index")?;
-            Ok(index)
+                .context("deleting orphan artifact")?;
+            return Ok(PutArtifact::ProgramNotFound);
         }
+        self.metrics
+            .requests
+            .with_label_values(&["upload_artifact"])
+            .inc();
+        Ok(PutArtifact::Stored(stored))
     }
+
+    /// Content type and content of an artifact, `None` if there is no such artifact.
+    pub async fn artifact(
+        &self,
+        contract: &str,
+        program_id: &str,
+        name: &str,
+    ) -> Result<Option<(ArtifactEntry, Bytes)>> {
+        let Some(artifact) = self
+            .program_entry(contract, program_id)
+            .await
+            .and_then(|entry| entry.artifacts.get(name).cloned())
+        else {
+            return Ok(None);
+        };
+        let bytes = self
+            .storage
+            .read_object(&artifact_object_path(contract, program_id, name))
+            .await?;
+        self.metrics
+            .requests
+            .with_label_values(&["download_artifact"])
+            .inc();
+        Ok(bytes.map(|bytes| (artifact, Bytes::from(bytes))))
+    }
+
+    pub async fn delete_artifact(
+        &self,
+        contract: &str,
+        program_id: &str,
+        name: &str,
+    ) -> Result<bool> {
+        let updated = self
+            .update_program_entry(contract, program_id, |entry| {
+                entry.artifacts.remove(name);
+            })
+            .await?;
+        let Some((previous, _)) = updated else {
+            return Ok(false);
+        };
+        if !previous.artifacts.contains_key(name) {
+            return Ok(false);
+        }
+        self.storage
+            .delete_object(&artifact_object_path(contract, program_id, name))
+            .await
+            .context("deleting artifact")?;
+        Ok(true)
+    }
+
+    async fn program_entry(&self, contract: &str, program_id: &str) -> Option<ProgramEntry> {
+        self.index
+            .read()
+            .await
+            .contracts
+            .get(contract)
+            .and_then(|contract_entry| contract_entry.programs.get(program_id))
+            .cloned()
+    }
+
+    /// Applies `update` to a stored program and saves its metadata and the
+    /// index. Returns the entry before and after the update, `None` if the
+    /// program is unknown.
+    async fn update_program_entry(
+        &self,
+        contract: &str,
+        program_id: &str,
+        update: impl FnOnce(&mut ProgramEntry),
+    ) -> Result<Option<(ProgramEntry, ProgramEntry)>> {
+        let (previous, entry, index_bytes) = {
+            let mut index = self.index.write().await;
+            let Some(entry) = index
+                .contracts
+                .get_mut(contract)
+                .and_then(|contract_entry| contract_entry.programs.get_mut(program_id))
+            else {
+                return Ok(None);
+            };
+            let previous = entry.clone();
+            update(entry);
+            let entry = entry.clone();
+            (
+                previous,
+                entry,
+                serde_json::to_vec(&*index).context("serializing index")?,
+            )
+        };
+        let metadata_bytes = serde_json::to_vec(&entry).context("serializing metadata")?;
+        self.storage
+            .write_object(&entry.metadata_path, &metadata_bytes)
+            .await
+            .context("storing metadata")?;
+        self.storage
+            .write_object(INDEX_FILE_NAME, &index_bytes)
+            .await
+            .context("writing index")?;
+        Ok(Some((previous, entry)))
+    }
+
+    /// Programs of `contract` with their ELF digest, ordered by program id.
+    /// Digests missing from programs stored before they were recorded are
+    /// computed from storage and saved in the index.
+    pub async fn contract_digests(&self, contract: &str) -> Result<Option<Vec<ProgramEntry>>> {
+        let entries = {
+            let index = self.index.read().await;
+            index.contracts.get(contract).map(|contract_entry| {
+                contract_entry
+                    .programs
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+        };
+        let Some(mut entries) = entries else {
+            return Ok(None);
+        };
+
+        let mut backfilled = Vec::new();
+        for entry in entries.iter_mut().filter(|entry| entry.sha256.is_none()) {
+            // Deleted meanwhile when missing.
+            let Some(bytes) = self.storage.read_object(&entry.object_path).await? else {
+                continue;
+            };
+            entry.sha256 = Some(hex::encode(Sha256::digest(&bytes)));
+            backfilled.push(entry.clone());
+        }
+        entries.retain(|entry| entry.sha256.is_some());
+        entries.sort_by(|a, b| a.program_id.cmp(&b.program_id));
+
+        if !backfilled.is_empty() {
+            let index_bytes = {
+                let mut index = self.index.write().await;
+                let contract_entry = index.contracts.get_mut(contract);
+                if let Some(contract_entry) = contract_entry {
+                    for entry in &backfilled {
+                        if let Some(current) = contract_entry.programs.get_mut(&entry.program_id) {
+                            // Skip programs overwritten while their ELF was read.
+                            if current.uploaded_at == entry.uploaded_at {
+                                current.sha256 = entry.sha256.clone();
+                            }
+                        }
+                    }
+                }
+                serde_json::to_vec(&*index).context("serializing index")?
+            };
+            self.storage
+                .write_object(INDEX_FILE_NAME, &index_bytes)
+                .await
+                .context("writing index")?;
+            info!(
+                "Recorded the ELF digest of {} programs of {contract}",
+                backfilled.len()
+            );
+        }
+
+        Ok(Some(entries))
+    }
+
+    /// Copy of the index, for exports.
+    pub async fn index_snapshot(&self) -> IndexFile {
+        self.index.read().await.clone()
+    }
+
+    /// Registers imported programs, whose ELFs are already stored at their
+    /// `object_path`, and removes the `removed` (contract, program id) pairs.
+    pub(crate) async fn apply_import(
+        &self,
+        imported: Vec<ProgramEntry>,
+        removed: Vec<(String, String)>,
+        settings: BTreeMap<String, ContractSettings>,
+    ) -> Result<()> {
+        for entry in &imported {
+            let metadata_bytes = serde_json::to_vec(entry).context("serializing metadata")?;
+            self.storage
+                .write_object(&entry.metadata_path, &metadata_bytes)
+                .await
+                .with_context(|| format!("storing metadata {}", entry.metadata_path))?;
+        }
+
+        let removed_entries = {
+            let index = self.index.read().await;
+            removed
+                .iter()
+                .filter_map(|(contract, program_id)| {
+                    index
+                        .contracts
+                        .get(contract)
+                        .and_then(|contract_entry| contract_entry.programs.get(program_id))
+                        .cloned()
+                })
+                .collect::<Vec<_>>()
+        };
+        for entry in &removed_entries {
+            self.delete_program_objects(entry).await?;
+        }
+
+        // Like uploads, restored programs are logged before they are served.
+        let leaves = imported
+            .iter()
+            .map(|entry| {
+                let sha256 = entry.sha256.clone().with_context(|| {
+                    format!("{}/{} has no digest", entry.contract, entry.program_id)
+                })?;
+                Ok(LogLeaf {
+                    contract: entry.contract.clone(),
+                    program_id: entry.program_id.clone(),
+                    sha256,
+                    uploaded_at: entry.uploaded_at.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let logged = self
+            .transparency
+            .append_missing(leaves)
+            .await
+            .context("recording the imported programs in the transparency log")?;
+        if logged > 0 {
+            info!("Recorded {logged} imported programs in the transparency log");
+        }
+
+        let index_bytes = {
+            let mut index = self.index.write().await;
+            for entry in &removed_entries {
+                if let Some(contract_entry) = index.contracts.get_mut(&entry.contract) {
+                    contract_entry.programs.remove(&entry.program_id);
+                    if contract_entry.is_unused() {
+                        index.contracts.remove(&entry.contract);
+                    }
+                }
+            }
+            for entry in &imported {
+                index
+                    .contracts
+                    .entry(entry.contract.clone())
+                    .or_default()
+                    .programs
+                    .insert(entry.program_id.clone(), entry.clone());
+            }
+            // An import never makes a contract public nor changes its owner.
+            for (contract, settings) in settings {
+                let contract_entry = index.contracts.entry(contract).or_default();
+                contract_entry.private |= settings.private;
+                if contract_entry.ownership.is_none() {
+                    contract_entry.ownership = settings.ownership;
+                }
+            }
+            self.record_index(&index);
+            serialize_with_settings(&index)?
+        };
+        self.write_index_with_settings(index_bytes).await?;
+
+        {
+            let mut cache = self.cache.write().await;
+            for entry in removed_entries.iter().chain(&imported) {
+                cache.remove_program(&entry.contract, &entry.program_id);
+            }
+            self.metrics.record_cache(&cache, 0);
+        }
+        if let Ok(mut counts) = self.download_counts.lock() {
+            for entry in &removed_entries {
+                counts.remove(&(entry.contract.clone(), entry.program_id.clone()));
+            }
+        }
+
+        self.metrics.requests.with_label_values(&["import"]).inc();
+
+        for entry in removed_entries {
+            self.publish(RegistryEvent::ProgramDeleted {
+                contract: entry.contract,
+                program_id: entry.program_id,
+            });
+        }
+        for entry in imported {
+            self.publish(RegistryEvent::ProgramUploaded {
+                contract: entry.contract.clone(),
+                program: Box::new(ProgramInfo::from_entry(&entry)),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn has_program(index: &IndexFile, contract: &str, program: &OnChainProgram) -> bool {
+    index.contracts.get(contract).is_some_and(|contract_entry| {
+        contract_entry
+            .programs
+            .keys()
+            .any(|program_id| program.matches(program_id))
+    })
+}
+
+/// Contract names that would collide with the server's internal objects.
+pub fn is_reserved_contract(contract: &str) -> bool {
+    contract == INTERNAL_PREFIX
+}
+
+pub(crate) fn binary_object_path(contract: &str, program_id: &str) -> String {
+    let digest = program_id_digest(program_id);
+    format!("{}/{}.elf", contract, digest)
+}
+
+pub(crate) fn metadata_object_path(contract: &str, program_id: &str) -> String {
+    let digest = program_id_digest(program_id);
+    format!("{}/{}.json", contract, digest)
+}
+
+pub(crate) fn provenance_object_path(contract: &str, program_id: &str) -> String {
+    let digest = program_id_digest(program_id);
+    format!("{}/{}{}", contract, digest, PROVENANCE_SUFFIX)
+}
+
+pub(crate) fn artifact_object_path(contract: &str, program_id: &str, name: &str) -> String {
+    let digest = program_id_digest(program_id);
+    format!("{}/{}.{}{}", contract, digest, name, ARTIFACT_SUFFIX)
+}
+
+pub(crate) fn program_id_digest(program_id: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(program_id.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+pub(crate) async fn create_storage_backend(config: &Conf) -> Result<Arc<dyn StorageBackend>> {
+    match config.storage_backend.trim().to_lowercase().as_str() {
+        "local" => {
+            let root = config
+                .local_storage_directory
+                .clone()
+                .filter(|path| !path.as_os_str().is_empty())
+                .unwrap_or_else(|| PathBuf::from(&config.data_directory).join("registry"));
+            Ok(Arc::new(LocalStorageBackend::new(root)))
+        }
+        "gcs" => {
+            let bucket = config
+                .gcs_bucket
+                .clone()
+                .filter(|bucket| !bucket.trim().is_empty())
+                .ok_or_else(|| anyhow!("gcs_bucket must be set for gcs backend"))?;
+            let prefix = config
+                .gcs_prefix
+                .clone()
+                .filter(|value| !value.trim().is_empty());
+            let backend = GcsStorageBackend::new(bucket, prefix).await?;
+            Ok(Arc::new(backend))
+        }
+        backend => Err(anyhow!("unsupported storage_backend: {backend}")),
+    }
+}
+
+async fn load_or_rebuild_index(
+    storage: &dyn StorageBackend,
+    metrics: &RegistryMetrics,
+) -> Result<IndexFile> {
+    let (mut index, rebuilt) = match storage.read_object(INDEX_FILE_NAME).await? {
+        Some(bytes) => {
+            let index: IndexFile = serde_json::from_slice(&bytes).context("parsing index")?;
+            (index, false)
+        }
+        None => {
+            info!("Index file not found, rebuilding index from stored objects");
+            metrics.index_rebuilds.inc();
+            let objects = storage.list_objects(None).await?;
+            let mut index = IndexFile::default();
+            for object in objects {
+                if object == INDEX_FILE_NAME
+                    || is_reserved_contract(object.split('/').next().unwrap_or_default())
+                    || !object.ends_with(".json")
+                    || object.ends_with(PROVENANCE_SUFFIX)
+                {
+                    continue;
+                }
+                let Some(metadata_bytes) = storage.read_object(&object).await? else {
+                    continue;
+                };
+                let entry: ProgramEntry = match serde_json::from_slice(&metadata_bytes) {
+                    Ok(entry) => entry,
+                    Err(_) => continue,
+                };
+                index
+                    .contracts
+                    .entry(entry.contract.clone())
+                    .or_default()
+                    .programs
+                    .insert(entry.program_id.clone(), entry);
+            }
+            (index, true)
+        }
+    };
+
+    // Ownership and visibility are only lost if both copies are.
+    match storage
+        .read_object(&contract_settings_object_path())
+        .await?
+    {
+        Some(bytes) => index
+            .apply_settings(serde_json::from_slice(&bytes).context("parsing contract settings")?),
+        None => {
+            if rebuilt {
+                warn!("Contract settings not found: contracts are public and unowned");
+            }
+            let settings =
+                serde_json::to_vec(&index.settings()).context("serializing contract settings")?;
+            storage
+                .write_object(&contract_settings_object_path(), &settings)
+                .await
+                .context("writing contract settings")?;
+        }
+    }
+    if rebuilt {
+        let index_bytes = serde_json::to_vec(&index).context("serializing rebuilt index")?;
+        storage
+            .write_object(INDEX_FILE_NAME, &index_bytes)
+            .await
+            .context("writing rebuilt index")?;
+    }
+    Ok(index)
 }
 
 #[cfg(test)]
@@ -554,198 +1952,662 @@ mod tests {
     use super::*;
     use crate::storage::LocalStorageBackend;
     use bytes::Bytes;
+    use prometheus::core::Collector;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::sync::Arc;
     use tempfile::TempDir;
     use tokio::sync::RwLock;
 
     fn test_metrics() -> RegistryMetrics {
-        let requests = IntCounterVec::new(
-            Opts::new(
-                "hyli_registry_requests_total_test",
-                "Total registry requests by operation.",
-            ),
-            &["op"],
-        )
-        .unwrap();
-        let bytes = IntCounterVec::new(
-            Opts::new(
-                "hyli_registry_bytes_total_test",
-                "Total bytes transferred by operation.",
-            ),
-            &["op"],
-        )
-        .unwrap();
-        let cache_hits = IntCounter::new(
-            "hyli_registry_cache_hits_total_test",
-            "Registry cache hits.",
-        )
-        .unwrap();
-        let cache_misses = IntCounter::new(
-            "hyli_registry_cache_misses_total_test",
-            "Registry cache misses.",
-        )
-        .unwrap();
-        let index_rebuilds = IntCounter::new(
-            "hyli_registry_index_rebuilds_total_test",
-            "Index rebuild count.",
-        )
-        .unwrap();
-        let storage_latency = HistogramVec::new(
-            prometheus::HistogramOpts::new(
-                "hyli_registry_storage_latency_seconds_test",
-                "Latency of storage operations.",
-            ),
-            &["op", "backend"],
-        )
-        .unwrap();
+        RegistryMetrics::unregistered().expect("metrics")
+    }
+
+    async fn make_service() -> (RegistryService, TempDir) {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let storage = Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf()));
+        (make_service_with_storage(storage).await, temp_dir)
+    }
+
+    async fn make_service_with_storage(storage: Arc<dyn StorageBackend>) -> RegistryService {
+        let metrics = test_metrics();
+        let index = load_or_rebuild_index(storage.as_ref(), &metrics)
+            .await
+            .expect("load index");
+        let transparency = TransparencyLog::load(storage.clone())
+            .await
+            .expect("load transparency log");
+        RegistryService {
+            storage,
+            index: Arc::new(RwLock::new(index)),
+            cache: Arc::new(RwLock::new(BinaryCache::new(1024 * 1024))),
+            inflight: Mutex::new(HashMap::new()),
+            download_counts: std::sync::Mutex::new(HashMap::new()),
+            metrics,
+            events: EventLog::new(64),
+            chain: std::sync::RwLock::new(None),
+            upstream: None,
+            read_only: false,
+            transparency,
+        }
+    }
+
+    /// Local storage that counts ELF reads and makes them slow enough for
+    /// concurrent downloads to overlap.
+    struct SlowStorage {
+        inner: LocalStorageBackend,
+        elf_reads: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend for SlowStorage {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        async fn read_object(&self, path: &str) -> Result<Option<Vec<u8>>> {
+            if path.ends_with(".elf") {
+                self.elf_reads.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            self.inner.read_object(path).await
+        }
+
+        async fn write_object(&self, path: &str, data: &[u8]) -> Result<()> {
+            self.inner.write_object(path, data).await
+        }
+
+        async fn list_objects(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+            self.inner.list_objects(prefix).await
+        }
+
+        async fn delete_object(&self, path: &str) -> Result<()> {
+            self.inner.delete_object(path).await
+        }
+    }
+
+    /// Local storage that refuses to write the transparency log while
+    /// `fail_log` is set.
+    struct LogFailingStorage {
+        inner: LocalStorageBackend,
+        fail_log: AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend for LogFailingStorage {
+        fn name(&self) -> &'static str {
+            "log-failing"
+        }
+
+        async fn read_object(&self, path: &str) -> Result<Option<Vec<u8>>> {
+            self.inner.read_object(path).await
+        }
+
+        async fn write_object(&self, path: &str, data: &[u8]) -> Result<()> {
+            if path.contains("/transparency/") && self.fail_log.load(Ordering::SeqCst) {
+                anyhow::bail!("storage unavailable");
+            }
+            self.inner.write_object(path, data).await
+        }
+
+        async fn list_objects(&self, prefix: Option<&str>) -> Result<Vec<String>> {
+            self.inner.list_objects(prefix).await
+        }
+
+        async fn delete_object(&self, path: &str) -> Result<()> {
+            self.inner.delete_object(path).await
+        }
+    }
+
+    fn sample_metadata(toolchain: &str) -> ProgramMetadata {
+        ProgramMetadata {
+            toolchain: Some(toolchain.to_string()),
+            commit: Some("abc123".to_string()),
+            zkvm: "sp1".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn program_id_hash_paths_are_stable() {
+        let digest = program_id_digest("hello");
+        assert_eq!(
+            digest,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        let object_path = binary_object_path("contract", "hello");
+        let metadata_path = metadata_object_path("contract", "hello");
+        assert_eq!(
+            object_path,
+            "contract/2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824.elf"
+        );
+        assert_eq!(
+            metadata_path,
+            "contract/2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_overwrite_updates_index_and_storage() {
+        let (service, _temp_dir) = make_service().await;
+        let contract = "orders";
+        let program_id = "program-a";
+
+        service
+            .upload(
+                contract,
+                program_id,
+                sample_metadata("toolchain-v1"),
+                Bytes::from_static(b"first"),
+            )
+            .await
+            .expect("upload v1");
+
+        service
+            .upload(
+                contract,
+                program_id,
+                sample_metadata("toolchain-v2"),
+                Bytes::from_static(b"second"),
+            )
+            .await
+            .expect("upload v2");
+
+        let index = service.index.read().await;
+        let entry = index
+            .contracts
+            .get(contract)
+            .and_then(|contract_entry| contract_entry.programs.get(program_id))
+            .expect("entry present");
+        assert_eq!(entry.metadata.toolchain, Some("toolchain-v2".to_string()));
+        assert_eq!(entry.size_bytes, 6);
+
+        let stored = service
+            .storage
+            .read_object(&entry.object_path)
+            .await
+            .expect("read object");
+        assert_eq!(stored.as_deref(), Some(b"second".as_slice()));
+
+        let metadata_bytes = service
+            .storage
+            .read_object(&entry.metadata_path)
+            .await
+            .expect("read metadata")
+            .expect("metadata exists");
+        let stored_entry: ProgramEntry =
+            serde_json::from_slice(&metadata_bytes).expect("parse metadata");
+        assert_eq!(
+            stored_entry.metadata.toolchain,
+            Some("toolchain-v2".to_string())
+        );
+        assert_eq!(stored_entry.size_bytes, 6);
+    }
+
+    #[tokio::test]
+    async fn every_uploaded_version_is_recorded_in_the_transparency_log() {
+        let (service, _temp_dir) = make_service().await;
+        let mut uploaded = Vec::new();
+        for elf in [b"first".as_slice(), b"second"] {
+            uploaded.push(
+                service
+                    .upload(
+                        "orders",
+                        "program-a",
+                        sample_metadata("toolchain-v1"),
+                        Bytes::copy_from_slice(elf),
+                    )
+                    .await
+                    .expect("upload"),
+            );
+        }
+
+        let logged = service.transparency().leaves(0, 10).await;
+        assert_eq!(logged.len(), 2);
+        for (leaf, entry) in logged.iter().zip(&uploaded) {
+            assert_eq!(leaf.contract, "orders");
+            assert_eq!(leaf.program_id, "program-a");
+            assert_eq!(Some(&leaf.sha256), entry.sha256.as_ref());
+            assert_eq!(leaf.uploaded_at, entry.uploaded_at);
+        }
+    }
+
+    #[tokio::test]
+    async fn uploads_fail_when_they_cannot_be_logged() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let storage = Arc::new(LogFailingStorage {
+            inner: LocalStorageBackend::new(temp_dir.path().to_path_buf()),
+            fail_log: AtomicBool::new(true),
+        });
+        let service = make_service_with_storage(storage.clone()).await;
+
+        assert!(service
+            .upload(
+                "orders",
+                "program-a",
+                sample_metadata("toolchain-v1"),
+                Bytes::from_static(b"elf"),
+            )
+            .await
+            .is_err());
+        assert!(service.list_contract("orders").await.is_none());
+        assert_eq!(service.transparency().size().await, 0);
+
+        storage.fail_log.store(false, Ordering::SeqCst);
+        service
+            .upload(
+                "orders",
+                "program-a",
+                sample_metadata("toolchain-v1"),
+                Bytes::from_static(b"elf"),
+            )
+            .await
+            .expect("upload");
+        assert_eq!(service.transparency().size().await, 1);
+        let proof = service
+            .transparency()
+            .inclusion_proof("orders", "program-a", None)
+            .await
+            .expect("proof");
+        assert!(proof.is_some());
+    }
+
+    #[tokio::test]
+    async fn delete_program_removes_objects_and_updates_index() {
+        let (service, _temp_dir) = make_service().await;
+        let contract = "orders";
+
+        service
+            .upload(
+                contract,
+                "program-a",
+                sample_metadata("toolchain-a"),
+                Bytes::from_static(b"alpha"),
+            )
+            .await
+            .expect("upload a");
+        service
+            .upload(
+                contract,
+                "program-b",
+                sample_metadata("toolchain-b"),
+                Bytes::from_static(b"beta"),
+            )
+            .await
+            .expect("upload b");
+
+        let deleted = service
+            .delete_program(contract, "program-a")
+            .await
+            .expect("delete program");
+        assert!(deleted);
+
+        let index = service.index.read().await;
+        let contract_entry = index.contracts.get(contract).expect("contract exists");
+        assert_eq!(contract_entry.programs.len(), 1);
+        assert!(contract_entry.programs.contains_key("program-b"));
+
+        let removed_entry = contract_entry.programs.get("program-a");
+        assert!(removed_entry.is_none());
+    }
+
+    #[tokio::test]
+    async fn contracts_are_claimed_by_their_first_uploader() {
+        let (service, _temp_dir) = make_service().await;
+        assert_eq!(
+            service
+                .claim_for_upload("orders", "orders-team")
+                .await
+                .expect("claim"),
+            UploadClaim::Claimed
+        );
+        service
+            .upload(
+                "orders",
+                "program-a",
+                sample_metadata("toolchain-v1"),
+                Bytes::from_static(b"first"),
+            )
+            .await
+            .expect("upload");
+        assert_eq!(
+            service
+                .claim_for_upload("orders", "wallet-team")
+                .await
+                .expect("claim"),
+            UploadClaim::Denied {
+                owner: "orders-team".to_string()
+            }
+        );
+
+        service
+            .update_ownership("orders", |ownership| {
+                if let Some(ownership) = ownership {
+                    ownership.collaborators.insert("wallet-team".to_string());
+                }
+            })
+            .await
+            .expect("add collaborator");
+        assert_eq!(
+            service
+                .claim_for_upload("orders", "wallet-team")
+                .await
+                .expect("claim"),
+            UploadClaim::Allowed
+        );
+        let summaries = service.contract_summaries(&VisibleContracts::all()).await;
+        assert_eq!(
+            summaries[0].ownership.as_ref().map(|o| o.owner.as_str()),
+            Some("orders-team")
+        );
 
-        RegistryMetrics {
-            requests,
-            bytes,
-            cache_hits,
-            cache_misses,
-            index_rebuilds,
-            storage_latency,
-        }
-    }
+        // Ownership outlives the programs of the contract, and is persisted.
+        assert!(service
+            .delete_program("orders", "program-a")
+            .await
+            .expect("delete"));
+        let reloaded = make_service_with_storage(service.storage()).await;
+        let ownership = reloaded.ownership("orders").await.expect("still owned");
+        assert!(ownership.allows("wallet-team"));
 
-    async fn make_service() -> (RegistryService, TempDir) {
-        let temp_dir = tempfile::tempdir().expect("tempdir");
-        let storage = Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf()));
-        let metrics = test_metrics();
-        let index = load_or_rebuild_index(storage.as_ref(), &metrics)
+        service
+            .update_ownership("orders", |ownership| *ownership = None)
             .await
-            .expect("load index");
-        let service = RegistryService {
-            storage,
-            index: Arc::new(RwLock::new(index)),
-            cache: Arc::new(RwLock::new(BinaryCache::default())),
-            metrics,
-        };
-        (service, temp_dir)
+            .expect("release");
+        assert!(service.index_snapshot().await.contracts.is_empty());
     }
 
-    fn sample_metadata(toolchain: &str) -> ProgramMetadata {
-        ProgramMetadata {
-            toolchain: Some(toolchain.to_string()),
-            commit: Some("abc123".to_string()),
-            zkvm: "sp1".to_string(),
+    #[tokio::test]
+    async fn legacy_contracts_go_to_their_latest_uploader() {
+        let (service, _temp_dir) = make_service().await;
+        for (contract, uploaded_by) in [("orders", Some("orders-team")), ("wallet", None)] {
+            service
+                .upload_with(
+                    contract,
+                    "program-a",
+                    sample_metadata("toolchain-v1"),
+                    UploadAttachments {
+                        uploaded_by: uploaded_by.map(str::to_string),
+                        ..Default::default()
+                    },
+                    Bytes::from_static(b"elf"),
+                )
+                .await
+                .expect("upload");
         }
-    }
+        assert!(service.ownership("orders").await.is_none());
 
-    #[tokio::test]
-    async fn program_id_hash_paths_are_stable() {
-        let digest = program_id_digest("hello");
         assert_eq!(
-            digest,
-            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            service.check_owner("orders", "orders-team").await,
+            UploadClaim::Allowed
         );
-        let object_path = binary_object_path("contract", "hello");
-        let metadata_path = metadata_object_path("contract", "hello");
         assert_eq!(
-            object_path,
-            "contract/2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824.elf"
+            service
+                .claim_for_upload("orders", "wallet-team")
+                .await
+                .expect("claim"),
+            UploadClaim::Denied {
+                owner: "orders-team".to_string()
+            }
+        );
+        let ownership = service.ownership("orders").await.expect("derived owner");
+        assert_eq!(ownership.owner, "orders-team");
+
+        // Without a known uploader, the first upload claims it.
+        assert_eq!(
+            service.check_owner("wallet", "wallet-team").await,
+            UploadClaim::Allowed
         );
         assert_eq!(
-            metadata_path,
-            "contract/2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824.json"
+            service
+                .claim_for_upload("wallet", "wallet-team")
+                .await
+                .expect("claim"),
+            UploadClaim::Claimed
+        );
+        assert_eq!(
+            service.check_owner("wallet", "orders-team").await,
+            UploadClaim::Denied {
+                owner: "wallet-team".to_string()
+            }
         );
+
+        // Deleting the contract keeps its owner.
+        assert!(service.delete_contract("orders").await.expect("delete"));
+        assert!(!service.delete_contract("orders").await.expect("delete"));
+        let ownership = service.ownership("orders").await.expect("still owned");
+        assert_eq!(ownership.owner, "orders-team");
+        assert_eq!(
+            service
+                .claim_for_upload("orders", "wallet-team")
+                .await
+                .expect("claim"),
+            UploadClaim::Denied {
+                owner: "orders-team".to_string()
+            }
+        );
+
+        // An upload that failed gives its claim back.
+        assert_eq!(
+            service
+                .claim_for_upload("ledger", "ledger-team")
+                .await
+                .expect("claim"),
+            UploadClaim::Claimed
+        );
+        service
+            .release_claim("ledger", "ledger-team")
+            .await
+            .expect("release");
+        assert!(service.ownership("ledger").await.is_none());
+        assert!(!service
+            .index_snapshot()
+            .await
+            .contracts
+            .contains_key("ledger"));
     }
 
     #[tokio::test]
-    async fn upload_overwrite_updates_index_and_storage() {
+    async fn private_contracts_are_only_listed_to_readers() {
         let (service, _temp_dir) = make_service().await;
-        let contract = "orders";
-        let program_id = "program-a";
+        for contract in ["orders", "wallet"] {
+            service
+                .upload(
+                    contract,
+                    "program-a",
+                    sample_metadata("toolchain-v1"),
+                    Bytes::from_static(b"elf"),
+                )
+                .await
+                .expect("upload");
+        }
+        assert!(service.set_private("orders", true).await.expect("hide"));
+        assert!(!service
+            .set_private("orders", true)
+            .await
+            .expect("hide again"));
+        assert!(service.is_private("orders").await);
+
+        let public = VisibleContracts::default();
+        let listed = service.list_all(&public).await;
+        assert_eq!(listed.keys().collect::<Vec<_>>(), vec!["wallet"]);
+        let page = service.list_all_page(None, 10, &public).await;
+        assert_eq!(page.programs.len(), 1);
+        let summaries = service.contract_summaries(&public).await;
+        assert_eq!(summaries.len(), 1);
+        let results = service.search(&ProgramQuery::default(), &public).await;
+        assert_eq!(results.total, 1);
+
+        let reader = VisibleContracts::public_and(service.private_contracts().await);
+        let summaries = service.contract_summaries(&reader).await;
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries[0].private);
+        assert_eq!(
+            service
+                .search(&ProgramQuery::default(), &reader)
+                .await
+                .total,
+            2
+        );
+
+        // Visibility is kept without programs, and persisted.
+        assert!(service
+            .delete_program("orders", "program-a")
+            .await
+            .expect("delete"));
+        let reloaded = make_service_with_storage(service.storage()).await;
+        assert!(reloaded.is_private("orders").await);
+
+        assert!(service.set_private("orders", false).await.expect("show"));
+        assert!(!service
+            .index_snapshot()
+            .await
+            .contracts
+            .contains_key("orders"));
+    }
+
+    #[tokio::test]
+    async fn provenance_is_stored_next_to_the_elf() {
+        let (service, _temp_dir) = make_service().await;
+        let provenance = Provenance::parse(
+            r#"{"source_repo": "https://github.com/hyli-org/orders", "source_ref": "v1"}"#,
+        )
+        .expect("provenance");
+        let provenance_path = provenance_object_path("orders", "program-a");
 
         service
-            .upload(
-                contract,
-                program_id,
+            .upload_with(
+                "orders",
+                "program-a",
                 sample_metadata("toolchain-v1"),
+                UploadAttachments {
+                    provenance: Some(provenance.clone()),
+                    ..Default::default()
+                },
                 Bytes::from_static(b"first"),
             )
             .await
-            .expect("upload v1");
+            .expect("upload");
+        let listed = service.list_contract("orders").await.expect("listing");
+        assert_eq!(listed[0].provenance, Some(provenance.summary()));
+        assert_eq!(
+            service
+                .provenance("orders", "program-a")
+                .await
+                .expect("provenance"),
+            Some(provenance)
+        );
 
+        // A new version without provenance drops the previous document.
         service
             .upload(
-                contract,
-                program_id,
+                "orders",
+                "program-a",
                 sample_metadata("toolchain-v2"),
                 Bytes::from_static(b"second"),
             )
             .await
-            .expect("upload v2");
-
-        let index = service.index.read().await;
-        let entry = index
-            .contracts
-            .get(contract)
-            .and_then(|contract_entry| contract_entry.programs.get(program_id))
-            .expect("entry present");
-        assert_eq!(entry.metadata.toolchain, Some("toolchain-v2".to_string()));
-        assert_eq!(entry.size_bytes, 6);
-
-        let stored = service
-            .storage
-            .read_object(&entry.object_path)
+            .expect("upload");
+        assert!(service
+            .provenance("orders", "program-a")
             .await
-            .expect("read object");
-        assert_eq!(stored.as_deref(), Some(b"second".as_slice()));
-
-        let metadata_bytes = service
+            .expect("provenance")
+            .is_none());
+        assert!(service
             .storage
-            .read_object(&entry.metadata_path)
+            .read_object(&provenance_path)
             .await
-            .expect("read metadata")
-            .expect("metadata exists");
-        let stored_entry: ProgramEntry =
-            serde_json::from_slice(&metadata_bytes).expect("parse metadata");
-        assert_eq!(
-            stored_entry.metadata.toolchain,
-            Some("toolchain-v2".to_string())
-        );
-        assert_eq!(stored_entry.size_bytes, 6);
+            .expect("read")
+            .is_none());
     }
 
     #[tokio::test]
-    async fn delete_program_removes_objects_and_updates_index() {
+    async fn artifacts_follow_their_program() {
         let (service, _temp_dir) = make_service().await;
-        let contract = "orders";
-
+        let artifact = |name: &str, bytes: &'static [u8]| {
+            ArtifactUpload::new(name, Some("application/json"), Bytes::from_static(bytes))
+                .expect("artifact")
+        };
         service
-            .upload(
-                contract,
+            .upload_with(
+                "orders",
                 "program-a",
-                sample_metadata("toolchain-a"),
-                Bytes::from_static(b"alpha"),
+                sample_metadata("toolchain-v1"),
+                UploadAttachments {
+                    artifacts: vec![artifact("vk", b"vk-1"), artifact("sample_proof", b"proof")],
+                    ..Default::default()
+                },
+                Bytes::from_static(b"first"),
             )
             .await
-            .expect("upload a");
+            .expect("upload");
+        assert!(matches!(
+            service
+                .put_artifact("orders", "program-a", artifact("source.tar.gz", b"src"))
+                .await
+                .expect("put"),
+            PutArtifact::Stored(_)
+        ));
+        assert!(matches!(
+            service
+                .put_artifact("orders", "missing", artifact("vk", b"vk"))
+                .await
+                .expect("put"),
+            PutArtifact::ProgramNotFound
+        ));
+
+        let listed = service.list_contract("orders").await.expect("listing");
+        assert_eq!(
+            listed[0].artifacts.keys().collect::<Vec<_>>(),
+            vec!["sample_proof", "source.tar.gz", "vk"]
+        );
+        let (entry, bytes) = service
+            .artifact("orders", "program-a", "vk")
+            .await
+            .expect("artifact")
+            .expect("vk");
+        assert_eq!(&bytes[..], b"vk-1");
+        assert_eq!(entry.content_type, "application/json");
+        assert_eq!(entry.sha256, hex::encode(Sha256::digest(b"vk-1")));
+
+        assert!(service
+            .delete_artifact("orders", "program-a", "sample_proof")
+            .await
+            .expect("delete"));
+        assert!(!service
+            .delete_artifact("orders", "program-a", "sample_proof")
+            .await
+            .expect("delete"));
+
+        // A new version keeps only the artifacts sent with it.
         service
-            .upload(
-                contract,
-                "program-b",
-                sample_metadata("toolchain-b"),
-                Bytes::from_static(b"beta"),
+            .upload_with(
+                "orders",
+                "program-a",
+                sample_metadata("toolchain-v2"),
+                UploadAttachments {
+                    artifacts: vec![artifact("vk", b"vk-2")],
+                    ..Default::default()
+                },
+                Bytes::from_static(b"second"),
             )
             .await
-            .expect("upload b");
-
-        let deleted = service
-            .delete_program(contract, "program-a")
+            .expect("upload");
+        assert!(service
+            .artifact("orders", "program-a", "source.tar.gz")
             .await
-            .expect("delete program");
-        assert!(deleted);
-
-        let index = service.index.read().await;
-        let contract_entry = index.contracts.get(contract).expect("contract exists");
-        assert_eq!(contract_entry.programs.len(), 1);
-        assert!(contract_entry.programs.contains_key("program-b"));
+            .expect("artifact")
+            .is_none());
 
-        let removed_entry = contract_entry.programs.get("program-a");
-        assert!(removed_entry.is_none());
+        assert!(service
+            .delete_program("orders", "program-a")
+            .await
+            .expect("delete"));
+        let mut remaining = service
+            .storage
+            .list_objects(Some("orders"))
+            .await
+            .expect("list");
+        remaining.retain(|object| object.ends_with(ARTIFACT_SUFFIX));
+        assert!(remaining.is_empty(), "{remaining:?}");
     }
 
     #[tokio::test]
@@ -844,6 +2706,12 @@ mod tests {
             size_bytes: 42,
             uploaded_at: "2024-01-01T00:00:00Z".to_string(),
             metadata: sample_metadata("toolchain-a"),
+            sha256: None,
+            signature: None,
+            provenance: None,
+            artifacts: Default::default(),
+            uploaded_by: None,
+            oidc_claims: Default::default(),
         };
         let metadata_bytes = serde_json::to_vec(&entry).expect("serialize metadata");
         storage
@@ -870,4 +2738,232 @@ mod tests {
         let disk_index: IndexFile = serde_json::from_slice(&index_bytes).expect("parse index");
         assert!(disk_index.contracts.contains_key(contract));
     }
+
+    #[tokio::test]
+    async fn rebuilding_the_index_keeps_contract_settings() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let storage = Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf()));
+        let service = make_service_with_storage(storage.clone()).await;
+        service
+            .upload(
+                "orders",
+                "program-a",
+                sample_metadata("toolchain-v1"),
+                Bytes::from_static(b"elf"),
+            )
+            .await
+            .expect("upload");
+        assert_eq!(
+            service
+                .claim_for_upload("orders", "alice")
+                .await
+                .expect("claim"),
+            UploadClaim::Claimed
+        );
+        assert!(service.set_private("orders", true).await.expect("hide"));
+        // Private contracts without programs are kept too.
+        assert!(service.set_private("wallet", true).await.expect("hide"));
+
+        storage
+            .delete_object(INDEX_FILE_NAME)
+            .await
+            .expect("delete index");
+        let rebuilt = make_service_with_storage(storage).await;
+        assert!(rebuilt.is_private("orders").await);
+        assert!(rebuilt.is_private("wallet").await);
+        assert_eq!(
+            rebuilt.check_owner("orders", "bob").await,
+            UploadClaim::Denied {
+                owner: "alice".to_string()
+            }
+        );
+        assert!(rebuilt.list_contract("orders").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_share_a_single_storage_read() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let storage = Arc::new(SlowStorage {
+            inner: LocalStorageBackend::new(temp_dir.path().to_path_buf()),
+            elf_reads: AtomicUsize::new(0),
+        });
+        let service = make_service_with_storage(storage.clone()).await;
+        service
+            .upload(
+                "orders",
+                "program-a",
+                sample_metadata("toolchain-a"),
+                Bytes::from_static(b"alpha"),
+            )
+            .await
+            .expect("upload");
+        service
+            .cache
+            .write()
+            .await
+            .remove_program("orders", "program-a");
+
+        let (a, b, c) = tokio::join!(
+            service.download("orders", "program-a"),
+            service.download("orders", "program-a"),
+            service.download("orders", "program-a"),
+        );
+        for result in [a, b, c] {
+            assert_eq!(
+                result.expect("download").as_deref(),
+                Some(b"alpha".as_slice())
+            );
+        }
+        assert_eq!(storage.elf_reads.load(Ordering::SeqCst), 1);
+        assert_eq!(service.metrics.download_coalesced.get(), 2);
+        assert!(service.inflight.lock().await.is_empty());
+
+        service
+            .download("orders", "program-a")
+            .await
+            .expect("cached download");
+        assert_eq!(storage.elf_reads.load(Ordering::SeqCst), 1);
+        assert_eq!(service.metrics.cache_hits.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn warmup_picks_programs_by_strategy_within_budget() {
+        let (service, _temp_dir) = make_service().await;
+        for (contract, program_id, uploaded_at) in [
+            ("orders", "program-a", "2024-01-01T00:00:00Z"),
+            ("orders", "program-b", "2024-02-01T00:00:00Z"),
+            ("wallet", "program-c", "2024-01-15T00:00:00Z"),
+        ] {
+            service
+                .upload(
+                    contract,
+                    program_id,
+                    sample_metadata("toolchain"),
+                    Bytes::from_static(b"elf!"),
+                )
+                .await
+                .expect("upload");
+            service
+                .index
+                .write()
+                .await
+                .contracts
+                .get_mut(contract)
+                .and_then(|contract_entry| contract_entry.programs.get_mut(program_id))
+                .expect("entry present")
+                .uploaded_at = uploaded_at.to_string();
+        }
+        service
+            .download("orders", "program-a")
+            .await
+            .expect("download");
+        {
+            let mut cache = service.cache.write().await;
+            cache.remove_contract("orders");
+            cache.remove_contract("wallet");
+        }
+        let service = Arc::new(service);
+
+        let recent = service
+            .warmup_targets(WarmupOptions {
+                strategy: WarmupStrategy::Recent,
+                max_bytes: 8,
+                programs_per_contract: 2,
+            })
+            .await;
+        assert_eq!(
+            recent,
+            vec![
+                ("orders".to_string(), "program-b".to_string(), 4),
+                ("wallet".to_string(), "program-c".to_string(), 4),
+            ]
+        );
+
+        let summary = service
+            .spawn_warmup(WarmupOptions {
+                strategy: WarmupStrategy::Downloads,
+                max_bytes: 4,
+                programs_per_contract: 1,
+            })
+            .await;
+        assert_eq!(summary.programs, 1);
+        for _ in 0..100 {
+            if service.cache.read().await.len() == 1 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let cache = service.cache.read().await;
+        assert!(cache.get("orders", "program-a").is_some());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_elf_gauge_follows_uploads_and_deletes() {
+        let (service, _temp_dir) = make_service().await;
+        service
+            .set_chain_programs(HashMap::from([(
+                "orders".to_string(),
+                OnChainProgram {
+                    program_id: "abcd".to_string(),
+                    verifier: "sp1-4".to_string(),
+                },
+            )]))
+            .await;
+        let missing = &service.metrics.contracts_missing_elf;
+        assert_eq!(missing.get(), 1);
+
+        service
+            .upload(
+                "orders",
+                "0xABCD",
+                sample_metadata("toolchain"),
+                Bytes::from_static(b"elf"),
+            )
+            .await
+            .expect("upload");
+        assert_eq!(missing.get(), 0);
+
+        service
+            .delete_program("orders", "0xABCD")
+            .await
+            .expect("delete");
+        assert_eq!(missing.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn storage_gauges_follow_uploads_and_deletes() {
+        let (service, _temp_dir) = make_service().await;
+        for program_id in ["program-a", "program-b"] {
+            service
+                .upload(
+                    "orders",
+                    program_id,
+                    sample_metadata("toolchain"),
+                    Bytes::from_static(b"alpha"),
+                )
+                .await
+                .expect("upload");
+        }
+        let metrics = &service.metrics;
+        assert_eq!(metrics.contracts.get(), 1);
+        assert_eq!(
+            metrics.contract_bytes.with_label_values(&["orders"]).get(),
+            10
+        );
+        assert_eq!(metrics.zkvm_bytes.with_label_values(&["sp1"]).get(), 10);
+
+        service
+            .delete_contract("orders")
+            .await
+            .expect("delete contract");
+        assert_eq!(metrics.contracts.get(), 0);
+        assert!(metrics.contract_programs.collect()[0]
+            .get_metric()
+            .is_empty());
+
+        let stats = service.stats(&VisibleContracts::all()).await;
+        assert_eq!(stats.programs, 0);
+        assert_eq!(stats.cache.entries, 0);
+    }
 }
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Context, Result};
+use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, VerifyOnly};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::conf::Conf;
+use crate::query::glob_match;
+
+/// Domain separator of the signed upload message, bumped if its layout changes.
+const UPLOAD_MESSAGE_PREFIX: &str = "hyli-registry-upload:v1";
+
+/// Publisher signature of an upload, kept with the program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadSignature {
+    /// Compressed secp256k1 public key of the signer, hex encoded.
+    pub public_key: String,
+    /// Compact ECDSA signature of [`upload_digest`], hex encoded.
+    pub signature: String,
+}
+
+/// Digest signed by publishers: SHA-256 of the message
+/// `hyli-registry-upload:v1\n<contract>\n<program_id>\n<hex sha256 of the ELF>`.
+pub fn upload_digest(contract: &str, program_id: &str, elf: &[u8]) -> [u8; 32] {
+    let message = format!(
+        "{UPLOAD_MESSAGE_PREFIX}\n{contract}\n{program_id}\n{}",
+        hex::encode(Sha256::digest(elf))
+    );
+    Sha256::digest(message.as_bytes()).into()
+}
+
+/// Why an upload signature was refused.
+#[derive(Debug)]
+pub enum SignatureError {
+    /// The contract only accepts signed uploads.
+    Missing,
+    /// Malformed, or not a signature of this upload by the given key.
+    Invalid(anyhow::Error),
+    /// Valid signature from a key not registered for the contract.
+    UnknownPublisher(String),
+}
+
+struct Publisher {
+    contract: String,
+    public_keys: Vec<PublicKey>,
+}
+
+/// Publisher keys registered per contract pattern (`publishers` in the config).
+pub struct PublisherKeys {
+    secp: Secp256k1<VerifyOnly>,
+    publishers: Vec<Publisher>,
+    require_signed: bool,
+}
+
+impl PublisherKeys {
+    pub fn from_conf(config: &Conf) -> Result<Self> {
+        let publishers = config
+            .publishers
+            .iter()
+            .map(|publisher| {
+                let public_keys = publisher
+                    .public_keys
+                    .iter()
+                    .map(|key| parse_public_key(key))
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("publisher keys of {}", publisher.contract))?;
+                Ok(Publisher {
+                    contract: publisher.contract.clone(),
+                    public_keys,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            secp: Secp256k1::verification_only(),
+            publishers,
+            require_signed: config.require_signed_uploads,
+        })
+    }
+
+    fn keys_for<'a>(&'a self, contract: &'a str) -> impl Iterator<Item = &'a PublicKey> + 'a {
+        self.publishers
+            .iter()
+            .filter(move |publisher| glob_match(&publisher.contract, contract))
+            .flat_map(|publisher| publisher.public_keys.iter())
+    }
+
+    /// Whether uploads to `contract` must be signed.
+    pub fn requires_signature(&self, contract: &str) -> bool {
+        self.require_signed || self.keys_for(contract).next().is_some()
+    }
+
+    /// Checks an upload against the contract's publisher keys. Contracts
+    /// without keys accept unsigned uploads unless `require_signed_uploads`
+    /// is set; a signature sent for them is still verified.
+    pub fn verify(
+        &self,
+        contract: &str,
+        program_id: &str,
+        elf: &[u8],
+        signature: Option<&UploadSignature>,
+    ) -> Result<(), SignatureError> {
+        let mut registered = self.keys_for(contract).peekable();
+        let Some(signature) = signature else {
+            if registered.peek().is_some() || self.require_signed {
+                return Err(SignatureError::Missing);
+            }
+            return Ok(());
+        };
+
+        let public_key =
+            parse_public_key(&signature.public_key).map_err(SignatureError::Invalid)?;
+        let ecdsa = hex::decode(signature.signature.trim())
+            .context("signature is not hex")
+            .and_then(|bytes| Signature::from_compact(&bytes).context("malformed signature"))
+            .map_err(SignatureError::Invalid)?;
+        let message = Message::from_digest(upload_digest(contract, program_id, elf));
+        self.secp
+            .verify_ecdsa(&message, &ecdsa, &public_key)
+            .map_err(|_| {
+                SignatureError::Invalid(anyhow!("signature does not match this upload"))
+            })?;
+
+        if registered.peek().is_some() && !registered.any(|key| *key == public_key) {
+            return Err(SignatureError::UnknownPublisher(
+                signature.public_key.clone(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_public_key(key: &str) -> Result<PublicKey> {
+    let bytes = hex::decode(key.trim()).context("public key is not hex")?;
+    PublicKey::from_slice(&bytes).context("invalid secp256k1 public key")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::PublisherConf;
+    use secp256k1::SecretKey;
+
+    fn sign(secret: &SecretKey, contract: &str, program_id: &str, elf: &[u8]) -> UploadSignature {
+        let secp = Secp256k1::new();
+        let message = Message::from_digest(upload_digest(contract, program_id, elf));
+        UploadSignature {
+            public_key: hex::encode(PublicKey::from_secret_key(&secp, secret).serialize()),
+            signature: hex::encode(secp.sign_ecdsa(&message, secret).serialize_compact()),
+        }
+    }
+
+    #[test]
+    fn verifies_uploads_against_contract_publishers() {
+        let publisher = SecretKey::from_slice(&[1; 32]).expect("key");
+        let stranger = SecretKey::from_slice(&[2; 32]).expect("key");
+        let secp = Secp256k1::new();
+        let keys = PublisherKeys::from_conf(&Conf {
+            publishers: vec![PublisherConf {
+                contract: "order*".to_string(),
+                public_keys: vec![hex::encode(
+                    PublicKey::from_secret_key(&secp, &publisher).serialize(),
+                )],
+            }],
+            ..Default::default()
+        })
+        .expect("keys");
+
+        let signed = sign(&publisher, "orders", "p1", b"elf");
+        assert!(keys.verify("orders", "p1", b"elf", Some(&signed)).is_ok());
+        assert!(matches!(
+            keys.verify("orders", "p1", b"other elf", Some(&signed)),
+            Err(SignatureError::Invalid(_))
+        ));
+        assert!(matches!(
+            keys.verify("orders", "p2", b"elf", Some(&signed)),
+            Err(SignatureError::Invalid(_))
+        ));
+        assert!(matches!(
+            keys.verify("orders", "p1", b"elf", None),
+            Err(SignatureError::Missing)
+        ));
+        assert!(matches!(
+            keys.verify(
+                "orders",
+                "p1",
+                b"elf",
+                Some(&sign(&stranger, "orders", "p1", b"elf"))
+            ),
+            Err(SignatureError::UnknownPublisher(_))
+        ));
+
+        assert!(keys.requires_signature("orders"));
+        assert!(!keys.requires_signature("wallet"));
+
+        // Contracts without publishers accept both.
+        assert!(keys.verify("wallet", "p1", b"elf", None).is_ok());
+        assert!(keys
+            .verify(
+                "wallet",
+                "p1",
+                b"elf",
+                Some(&sign(&stranger, "wallet", "p1", b"elf"))
+            )
+            .is_ok());
+    }
+}
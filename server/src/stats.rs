@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::cache::CachedProgram;
+use crate::registry::{IndexFile, ProgramEntry, VisibleContracts};
+
+/// Aggregate view of what the registry stores, derived from the index.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RegistryStats {
+    pub contracts: usize,
+    pub programs: usize,
+    pub total_bytes: u64,
+    pub per_contract: BTreeMap<String, StorageStats>,
+    pub per_zkvm: BTreeMap<String, StorageStats>,
+    pub oldest_upload: Option<String>,
+    pub newest_upload: Option<String>,
+    pub cache: CacheStats,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StorageStats {
+    pub programs: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+    /// Cached binaries with their hits, most hit first.
+    pub programs: Vec<CachedProgram>,
+}
+
+impl RegistryStats {
+    pub fn from_index(index: &IndexFile) -> Self {
+        Self::from_visible(index, &VisibleContracts::all())
+    }
+
+    /// Aggregates over the contracts `visible` includes only.
+    pub fn from_visible(index: &IndexFile, visible: &VisibleContracts) -> Self {
+        let mut stats = Self::default();
+        let mut oldest: Option<&ProgramEntry> = None;
+        let mut newest: Option<&ProgramEntry> = None;
+
+        for (contract, contract_entry) in index
+            .contracts
+            .iter()
+            .filter(|(contract, contract_entry)| visible.includes(contract, contract_entry))
+        {
+            stats.contracts += 1;
+            let contract_stats = stats.per_contract.entry(contract.clone()).or_default();
+            for entry in contract_entry.programs.values() {
+                contract_stats.programs += 1;
+                contract_stats.bytes += entry.size_bytes;
+
+                let zkvm_stats = stats
+                    .per_zkvm
+                    .entry(entry.metadata.zkvm.clone())
+                    .or_default();
+                zkvm_stats.programs += 1;
+                zkvm_stats.bytes += entry.size_bytes;
+
+                if oldest
+                    .is_none_or(|oldest| entry.uploaded_at_micros() < oldest.uploaded_at_micros())
+                {
+                    oldest = Some(entry);
+                }
+                if newest
+                    .is_none_or(|newest| entry.uploaded_at_micros() > newest.uploaded_at_micros())
+                {
+                    newest = Some(entry);
+                }
+            }
+            stats.programs += contract_stats.programs;
+            stats.total_bytes += contract_stats.bytes;
+        }
+
+        stats.oldest_upload = oldest.map(|entry| entry.uploaded_at.clone());
+        stats.newest_upload = newest.map(|entry| entry.uploaded_at.clone());
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{ContractIndex, ProgramMetadata};
+
+    fn entry(contract: &str, program_id: &str, zkvm: &str, size: u64, day: u32) -> ProgramEntry {
+        ProgramEntry {
+            program_id: program_id.to_string(),
+            contract: contract.to_string(),
+            object_path: String::new(),
+            metadata_path: String::new(),
+            size_bytes: size,
+            uploaded_at: format!("2024-01-{day:02}T00:00:00Z"),
+            metadata: ProgramMetadata {
+                toolchain: None,
+                commit: None,
+                zkvm: zkvm.to_string(),
+            },
+            sha256: None,
+            signature: None,
+            provenance: None,
+            artifacts: Default::default(),
+            uploaded_by: None,
+            oidc_claims: Default::default(),
+        }
+    }
+
+    #[test]
+    fn aggregates_per_contract_and_zkvm() {
+        let mut index = IndexFile::default();
+        for program in [
+            entry("orders", "a", "sp1", 10, 3),
+            entry("orders", "b", "risc0", 20, 1),
+            entry("wallet", "c", "sp1", 5, 7),
+        ] {
+            index
+                .contracts
+                .entry(program.contract.clone())
+                .or_insert_with(ContractIndex::default)
+                .programs
+                .insert(program.program_id.clone(), program);
+        }
+
+        let stats = RegistryStats::from_index(&index);
+        assert_eq!(stats.contracts, 2);
+        assert_eq!(stats.programs, 3);
+        assert_eq!(stats.total_bytes, 35);
+        assert_eq!(
+            stats.per_contract["orders"],
+            StorageStats {
+                programs: 2,
+                bytes: 30
+            }
+        );
+        assert_eq!(
+            stats.per_zkvm["sp1"],
+            StorageStats {
+                programs: 2,
+                bytes: 15
+            }
+        );
+        assert_eq!(stats.oldest_upload.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(stats.newest_upload.as_deref(), Some("2024-01-07T00:00:00Z"));
+    }
+}
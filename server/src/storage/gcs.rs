@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use super::StorageBackend;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -9,6 +11,7 @@ use google_cloud_storage::http::objects::get::GetObjectRequest;
 use google_cloud_storage::http::objects::list::ListObjectsRequest;
 use google_cloud_storage::http::objects::upload::{Media, UploadObjectRequest, UploadType};
 use google_cloud_storage::http::Error as GcsError;
+use google_cloud_storage::sign::{SignedURLMethod, SignedURLOptions};
 use tracing::info;
 
 pub struct GcsStorageBackend {
@@ -149,4 +152,20 @@ impl StorageBackend for GcsStorageBackend {
             Err(err) => Err(err).context("deleting gcs object"),
         }
     }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    async fn signed_url(&self, path: &str, expires: Duration) -> Result<Option<String>> {
+        let object = self.object_path(path);
+        let options = SignedURLOptions {
+            method: SignedURLMethod::GET,
+            expires,
+            ..Default::default()
+        };
+        let url = self
+            .client
+            .signed_url(&self.bucket, &object, None, None, options)
+            .await
+            .context("signing gcs url")?;
+        Ok(Some(url))
+    }
 }
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use async_trait::async_trait;
 
@@ -14,4 +16,10 @@ pub trait StorageBackend: Send + Sync {
     async fn write_object(&self, path: &str, data: &[u8]) -> Result<()>;
     async fn list_objects(&self, prefix: Option<&str>) -> Result<Vec<String>>;
     async fn delete_object(&self, path: &str) -> Result<()>;
+
+    /// URL the object can be downloaded from directly until `expires` has
+    /// elapsed, `None` when the backend cannot sign URLs.
+    async fn signed_url(&self, _path: &str, _expires: Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
 }
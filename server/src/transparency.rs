@@ -0,0 +1,507 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use hyli_registry::verify::{leaf_hash, node_hash, tree_head_digest, Hash};
+pub use hyli_registry::verify::{verify_consistency, verify_inclusion};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use crate::manifest::ManifestSigner;
+use crate::registry::INTERNAL_PREFIX;
+use crate::storage::StorageBackend;
+
+/// Most leaves returned by one [`TransparencyLog::leaves`] call.
+pub const MAX_LEAVES_PER_PAGE: usize = 1000;
+
+/// Leaves per stored segment: an append only rewrites the last segment.
+#[cfg(not(test))]
+const SEGMENT_LEAVES: usize = 1024;
+#[cfg(test)]
+const SEGMENT_LEAVES: usize = 3;
+
+fn segment_object_path(segment: usize) -> String {
+    format!("{INTERNAL_PREFIX}/transparency/segments/{segment:08}.json")
+}
+
+/// One upload recorded in the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogLeaf {
+    pub contract: String,
+    pub program_id: String,
+    /// Hex SHA-256 of the uploaded ELF.
+    pub sha256: String,
+    pub uploaded_at: String,
+}
+
+impl LogLeaf {
+    /// Merkle leaf hash: SHA-256 of `0x00` followed by
+    /// `hyli-registry-leaf:v1\n<contract>\n<program_id>\n<sha256>\n<uploaded_at>`.
+    pub fn hash(&self) -> Hash {
+        leaf_hash(
+            &self.contract,
+            &self.program_id,
+            &self.sha256,
+            &self.uploaded_at,
+        )
+    }
+}
+
+/// A leaf as listed: leaves of private contracts the caller cannot read are
+/// reduced to their hash, which is enough to check the tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ListedLeaf {
+    Leaf(LogLeaf),
+    /// Hex Merkle leaf hash.
+    Redacted {
+        leaf_hash: String,
+    },
+}
+
+impl ListedLeaf {
+    pub fn new(leaf: LogLeaf, shown: bool) -> Self {
+        if shown {
+            Self::Leaf(leaf)
+        } else {
+            Self::Redacted {
+                leaf_hash: hex::encode(leaf.hash()),
+            }
+        }
+    }
+}
+
+/// Root of the log at a given size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeHead {
+    pub tree_size: u64,
+    /// Hex Merkle root of the first `tree_size` leaves.
+    pub root_hash: String,
+    pub timestamp: String,
+}
+
+impl TreeHead {
+    /// Digest signed by the registry: SHA-256 of
+    /// `hyli-registry-tree-head:v1\n<tree_size>\n<root_hash>\n<timestamp>`.
+    pub fn digest(&self) -> Hash {
+        tree_head_digest(self.tree_size, &self.root_hash, &self.timestamp)
+    }
+
+    /// Signs the head with the manifest key, left unsigned when there is none.
+    pub fn signed(self, signer: Option<&ManifestSigner>) -> SignedTreeHead {
+        let (public_key, signature) = match signer {
+            Some(signer) => (
+                Some(signer.public_key().to_string()),
+                Some(signer.sign_digest(self.digest())),
+            ),
+            None => (None, None),
+        };
+        SignedTreeHead {
+            head: self,
+            public_key,
+            signature,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTreeHead {
+    #[serde(flatten)]
+    pub head: TreeHead,
+    /// Compressed secp256k1 public key of the registry, hex encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Compact ECDSA signature of [`TreeHead::digest`], hex encoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Audit path of a leaf to the root of the tree of size `tree_size`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionProof {
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub leaf: LogLeaf,
+    /// Hex hashes, from the leaf up.
+    pub audit_path: Vec<String>,
+}
+
+/// Proof that the tree of size `first` is a prefix of the tree of size `second`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub first: u64,
+    pub second: u64,
+    /// Hex hashes.
+    pub proof: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersistedLog {
+    leaves: Vec<LogLeaf>,
+}
+
+#[derive(Default)]
+struct LogState {
+    leaves: Vec<LogLeaf>,
+    hashes: Vec<Hash>,
+    /// Roots of the perfect subtrees covering the leaves, largest first, with
+    /// their size, so that appending updates the root in `O(log n)`.
+    peaks: Vec<(usize, Hash)>,
+    root: Hash,
+}
+
+impl LogState {
+    fn push(&mut self, leaf: LogLeaf) {
+        let mut peak = (1, leaf.hash());
+        self.hashes.push(peak.1);
+        self.leaves.push(leaf);
+        while let Some(&(size, left)) = self.peaks.last() {
+            if size != peak.0 {
+                break;
+            }
+            self.peaks.pop();
+            peak = (size * 2, node_hash(&left, &peak.1));
+        }
+        self.peaks.push(peak);
+        let mut peaks = self.peaks.iter().rev().map(|(_, hash)| *hash);
+        let last = peaks.next().expect("the log has a leaf");
+        self.root = peaks.fold(last, |right, left| node_hash(&left, &right));
+    }
+}
+
+/// Append-only Merkle tree (RFC 6962 hashing) over every upload, stored
+/// under the internal prefix in segments of [`SEGMENT_LEAVES`] leaves.
+pub struct TransparencyLog {
+    storage: Arc<dyn StorageBackend>,
+    state: RwLock<LogState>,
+}
+
+impl TransparencyLog {
+    pub async fn load(storage: Arc<dyn StorageBackend>) -> Result<Self> {
+        let mut leaves = Vec::new();
+        for segment in 0.. {
+            let Some(bytes) = storage.read_object(&segment_object_path(segment)).await? else {
+                break;
+            };
+            let segment_leaves = serde_json::from_slice::<PersistedLog>(&bytes)
+                .with_context(|| format!("parsing transparency log segment {segment}"))?
+                .leaves;
+            let full = segment_leaves.len() == SEGMENT_LEAVES;
+            leaves.extend(segment_leaves);
+            if !full {
+                break;
+            }
+        }
+        let mut state = LogState {
+            root: Sha256::digest([]).into(),
+            ..Default::default()
+        };
+        for leaf in leaves {
+            state.push(leaf);
+        }
+        Ok(Self {
+            storage,
+            state: RwLock::new(state),
+        })
+    }
+
+    async fn write_segment(&self, segment: usize, leaves: &[LogLeaf]) -> Result<()> {
+        let bytes = serde_json::to_vec(&PersistedLog {
+            leaves: leaves.to_vec(),
+        })
+        .context("serializing transparency log segment")?;
+        self.storage
+            .write_object(&segment_object_path(segment), &bytes)
+            .await
+            .context("writing transparency log segment")
+    }
+
+    /// Appends a leaf and persists its segment, returning the leaf index.
+    /// The log is left unchanged if the leaf could not be stored.
+    pub async fn append(&self, leaf: LogLeaf) -> Result<u64> {
+        let mut state = self.state.write().await;
+        self.push_persisted(&mut state, leaf).await
+    }
+
+    /// Appends, in order, the leaves the log does not have yet, such as the
+    /// uploads of a restored backup, and returns how many were appended.
+    pub async fn append_missing(&self, leaves: Vec<LogLeaf>) -> Result<usize> {
+        let mut state = self.state.write().await;
+        let mut logged = state.hashes.iter().copied().collect::<HashSet<_>>();
+        let mut appended = 0;
+        for leaf in leaves {
+            if logged.insert(leaf.hash()) {
+                self.push_persisted(&mut state, leaf).await?;
+                appended += 1;
+            }
+        }
+        Ok(appended)
+    }
+
+    async fn push_persisted(&self, state: &mut LogState, leaf: LogLeaf) -> Result<u64> {
+        let index = state.leaves.len();
+        let segment = index / SEGMENT_LEAVES;
+        let mut leaves = state.leaves[segment * SEGMENT_LEAVES..].to_vec();
+        leaves.push(leaf.clone());
+        self.write_segment(segment, &leaves).await?;
+        state.push(leaf);
+        Ok(index as u64)
+    }
+
+    pub async fn size(&self) -> u64 {
+        self.state.read().await.hashes.len() as u64
+    }
+
+    pub async fn tree_head(&self) -> TreeHead {
+        let state = self.state.read().await;
+        TreeHead {
+            tree_size: state.hashes.len() as u64,
+            root_hash: hex::encode(state.root),
+            timestamp: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Leaves from `start`, at most [`MAX_LEAVES_PER_PAGE`] of them.
+    pub async fn leaves(&self, start: u64, limit: usize) -> Vec<LogLeaf> {
+        let state = self.state.read().await;
+        state
+            .leaves
+            .iter()
+            .skip(start.try_into().unwrap_or(usize::MAX))
+            .take(limit.min(MAX_LEAVES_PER_PAGE))
+            .cloned()
+            .collect()
+    }
+
+    /// Proof for the latest upload of a program within the first
+    /// `tree_size` leaves (the whole log by default), `None` if there is none.
+    pub async fn inclusion_proof(
+        &self,
+        contract: &str,
+        program_id: &str,
+        tree_size: Option<u64>,
+    ) -> Result<Option<InclusionProof>> {
+        let state = self.state.read().await;
+        let size = checked_size(tree_size.unwrap_or(state.hashes.len() as u64), &state)?;
+        let Some(index) = state.leaves[..size]
+            .iter()
+            .rposition(|leaf| leaf.contract == contract && leaf.program_id == program_id)
+        else {
+            return Ok(None);
+        };
+        Ok(Some(InclusionProof {
+            leaf_index: index as u64,
+            tree_size: size as u64,
+            leaf: state.leaves[index].clone(),
+            audit_path: inclusion_path(index, &state.hashes[..size])
+                .iter()
+                .map(hex::encode)
+                .collect(),
+        }))
+    }
+
+    pub async fn consistency_proof(&self, first: u64, second: u64) -> Result<ConsistencyProof> {
+        let state = self.state.read().await;
+        let second_size = checked_size(second, &state)?;
+        if first > second {
+            bail!("first tree size {first} is larger than {second}");
+        }
+        let proof = if first == 0 {
+            Vec::new()
+        } else {
+            consistency_path(first as usize, &state.hashes[..second_size], true)
+        };
+        Ok(ConsistencyProof {
+            first,
+            second,
+            proof: proof.iter().map(hex::encode).collect(),
+        })
+    }
+}
+
+fn checked_size(tree_size: u64, state: &LogState) -> Result<usize> {
+    match usize::try_from(tree_size) {
+        Ok(size) if size <= state.hashes.len() => Ok(size),
+        _ => bail!(
+            "tree size {tree_size} is larger than the log ({})",
+            state.hashes.len()
+        ),
+    }
+}
+
+/// Largest power of two smaller than `n` (`n > 1`).
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - (n - 1).leading_zeros() - 1)
+}
+
+fn merkle_root(hashes: &[Hash]) -> Hash {
+    match hashes {
+        [] => Sha256::digest([]).into(),
+        [hash] => *hash,
+        _ => {
+            let k = split(hashes.len());
+            node_hash(&merkle_root(&hashes[..k]), &merkle_root(&hashes[k..]))
+        }
+    }
+}
+
+fn inclusion_path(index: usize, hashes: &[Hash]) -> Vec<Hash> {
+    if hashes.len() <= 1 {
+        return Vec::new();
+    }
+    let k = split(hashes.len());
+    let (mut path, sibling) = if index < k {
+        (
+            inclusion_path(index, &hashes[..k]),
+            merkle_root(&hashes[k..]),
+        )
+    } else {
+        (
+            inclusion_path(index - k, &hashes[k..]),
+            merkle_root(&hashes[..k]),
+        )
+    };
+    path.push(sibling);
+    path
+}
+
+/// `SUBPROOF(m, D[n], b)` of RFC 6962.
+fn consistency_path(m: usize, hashes: &[Hash], complete: bool) -> Vec<Hash> {
+    if m == hashes.len() {
+        return if complete {
+            Vec::new()
+        } else {
+            vec![merkle_root(hashes)]
+        };
+    }
+    let k = split(hashes.len());
+    let (mut path, sibling) = if m <= k {
+        (
+            consistency_path(m, &hashes[..k], complete),
+            merkle_root(&hashes[k..]),
+        )
+    } else {
+        (
+            consistency_path(m - k, &hashes[k..], false),
+            merkle_root(&hashes[..k]),
+        )
+    };
+    path.push(sibling);
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorageBackend;
+
+    fn leaf(n: usize) -> LogLeaf {
+        LogLeaf {
+            contract: "orders".to_string(),
+            program_id: format!("p{n}"),
+            sha256: hex::encode(Sha256::digest(n.to_string().as_bytes())),
+            uploaded_at: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    fn root(hex_root: &str) -> Hash {
+        hex::decode(hex_root)
+            .expect("hex")
+            .try_into()
+            .expect("32 bytes")
+    }
+
+    fn hashes(hex_hashes: &[String]) -> Vec<Hash> {
+        hex_hashes.iter().map(|hash| root(hash)).collect()
+    }
+
+    #[tokio::test]
+    async fn proves_inclusion_and_consistency_across_reloads() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let storage: Arc<dyn StorageBackend> =
+            Arc::new(LocalStorageBackend::new(dir.path().to_path_buf()));
+        let log = TransparencyLog::load(storage.clone()).await.expect("load");
+        assert_eq!(
+            log.tree_head().await.root_hash,
+            hex::encode(Sha256::digest([]))
+        );
+
+        let mut heads = vec![log.tree_head().await];
+        for n in 0..7 {
+            assert_eq!(log.append(leaf(n)).await.expect("append"), n as u64);
+            heads.push(log.tree_head().await);
+        }
+
+        let log = TransparencyLog::load(storage).await.expect("reload");
+        assert_eq!(log.size().await, 7);
+        assert_eq!(log.tree_head().await.root_hash, heads[7].root_hash);
+        assert_eq!(log.leaves(5, 10).await, vec![leaf(5), leaf(6)]);
+
+        for size in 1..=7u64 {
+            for n in 0..size as usize {
+                let proof = log
+                    .inclusion_proof("orders", &format!("p{n}"), Some(size))
+                    .await
+                    .expect("proof")
+                    .expect("included");
+                assert_eq!(proof.leaf, leaf(n));
+                assert!(verify_inclusion(
+                    &proof.leaf.hash(),
+                    proof.leaf_index,
+                    proof.tree_size,
+                    &hashes(&proof.audit_path),
+                    &root(&heads[size as usize].root_hash),
+                ));
+                assert!(!verify_inclusion(
+                    &leaf(n + 1).hash(),
+                    proof.leaf_index,
+                    proof.tree_size,
+                    &hashes(&proof.audit_path),
+                    &root(&heads[size as usize].root_hash),
+                ));
+            }
+            for first in 0..=size {
+                let proof = log.consistency_proof(first, size).await.expect("proof");
+                assert!(verify_consistency(
+                    first,
+                    size,
+                    &hashes(&proof.proof),
+                    &root(&heads[first as usize].root_hash),
+                    &root(&heads[size as usize].root_hash),
+                ));
+            }
+        }
+
+        // Clients check the proofs served as JSON with the same code.
+        let client_head: hyli_registry::TreeHead =
+            serde_json::from_value(serde_json::to_value(&heads[7]).expect("json")).expect("head");
+        let proof = log
+            .inclusion_proof("orders", "p3", None)
+            .await
+            .expect("proof")
+            .expect("included");
+        let mut client_proof: hyli_registry::InclusionProof =
+            serde_json::from_value(serde_json::to_value(&proof).expect("json")).expect("proof");
+        assert!(client_proof.verify(&client_head).is_ok());
+        client_proof.leaf.sha256 = leaf(4).sha256;
+        assert!(client_proof.verify(&client_head).is_err());
+
+        assert!(log
+            .inclusion_proof("orders", "p6", Some(6))
+            .await
+            .expect("proof")
+            .is_none());
+        assert!(log.inclusion_proof("orders", "p0", Some(8)).await.is_err());
+        assert!(log.consistency_proof(5, 4).await.is_err());
+        let proof = log.consistency_proof(3, 7).await.expect("proof");
+        assert!(!verify_consistency(
+            3,
+            7,
+            &hashes(&proof.proof),
+            &root(&heads[4].root_hash),
+            &root(&heads[7].root_hash),
+        ));
+    }
+}
@@ -0,0 +1,617 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use chrono::DateTime;
+use prometheus::IntCounterVec;
+use secp256k1::PublicKey;
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::artifacts::ArtifactEntry;
+use crate::conf::Conf;
+use crate::manifest::SignedManifest;
+use crate::provenance::Provenance;
+use crate::registry::ProgramInfo;
+use crate::signing::{PublisherKeys, SignatureError};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client of the registry this instance mirrors. Contract listings are kept
+/// for `refresh` so a busy mirror does not query the upstream on every call.
+pub struct Upstream {
+    client: reqwest::Client,
+    base_url: reqwest::Url,
+    refresh: Duration,
+    listings: std::sync::Mutex<HashMap<String, CachedListing>>,
+    /// Key of the upstream's signed manifests, from `upstream_manifest_key`.
+    manifest_key: Option<PublicKey>,
+    /// Mirrored programs follow the publisher rules of local uploads.
+    publishers: PublisherKeys,
+    fetches: IntCounterVec,
+}
+
+struct CachedListing {
+    fetched_at: Instant,
+    /// `None` when the upstream does not know the contract.
+    programs: Option<Vec<ProgramInfo>>,
+}
+
+impl Upstream {
+    /// Upstream configured by `upstream_url`, if any.
+    pub fn from_conf(config: &Conf, fetches: IntCounterVec) -> Result<Option<Self>> {
+        let Some(url) = config
+            .upstream_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+        else {
+            return Ok(None);
+        };
+        let base_url = reqwest::Url::parse(url.trim_end_matches('/'))
+            .with_context(|| format!("invalid upstream url {url}"))?;
+        if base_url.cannot_be_a_base() {
+            bail!("invalid upstream url {url}");
+        }
+        let manifest_key = config
+            .upstream_manifest_key
+            .as_deref()
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| {
+                hex::decode(key)
+                    .context("upstream_manifest_key is not hex")
+                    .and_then(|bytes| {
+                        PublicKey::from_slice(&bytes).context("invalid upstream_manifest_key")
+                    })
+            })
+            .transpose()?;
+        if manifest_key.is_none() {
+            warn!("upstream_manifest_key is not set, mirrored ELFs are checked against the upstream listings only");
+        }
+        Ok(Some(Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .context("building upstream http client")?,
+            base_url,
+            refresh: Duration::from_secs(config.upstream_listing_refresh_secs),
+            listings: std::sync::Mutex::new(HashMap::new()),
+            manifest_key,
+            publishers: PublisherKeys::from_conf(config)?,
+            fetches,
+        }))
+    }
+
+    fn url(&self, segments: &[&str]) -> reqwest::Url {
+        let mut url = self.base_url.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    /// Programs the upstream lists for `contract`. A failed refresh keeps
+    /// serving the previous listing.
+    pub async fn listing(&self, contract: &str) -> Option<Vec<ProgramInfo>> {
+        let cached = {
+            let listings = self.listings.lock().unwrap_or_else(|err| err.into_inner());
+            listings.get(contract).map(|listing| {
+                (
+                    listing.fetched_at.elapsed() < self.refresh,
+                    listing.programs.clone(),
+                )
+            })
+        };
+        if let Some((true, programs)) = cached {
+            return programs;
+        }
+
+        match self.fetch_listing(contract).await {
+            Ok(programs) => {
+                self.record(
+                    "listing",
+                    if programs.is_some() {
+                        "ok"
+                    } else {
+                        "not_found"
+                    },
+                );
+                self.listings
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .insert(
+                        contract.to_string(),
+                        CachedListing {
+                            fetched_at: Instant::now(),
+                            programs: programs.clone(),
+                        },
+                    );
+                programs
+            }
+            Err(err) => {
+                self.record("listing", "error");
+                warn!("Failed to list {contract} on upstream: {err:#}");
+                cached.and_then(|(_, programs)| programs)
+            }
+        }
+    }
+
+    async fn fetch_listing(&self, contract: &str) -> Result<Option<Vec<ProgramInfo>>> {
+        let response = self
+            .client
+            .get(self.url(&["api", "elfs", contract]))
+            .send()
+            .await
+            .context("querying upstream")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("upstream answered HTTP {}", response.status());
+        }
+        let programs: Vec<ProgramInfo> = response.json().await.context("parsing listing")?;
+        Ok(Some(
+            programs
+                .into_iter()
+                .map(|program| ProgramInfo {
+                    // Only meaningful against this instance's own node.
+                    chain_status: None,
+                    ..program
+                })
+                .collect(),
+        ))
+    }
+
+    /// Downloads an ELF, checking it against the size and digest the upstream
+    /// lists and, with `upstream_manifest_key`, against its signed manifest.
+    /// Its publisher signature is checked like the one of a local upload.
+    pub async fn download(&self, contract: &str, program: &ProgramInfo) -> Result<Option<Bytes>> {
+        let (outcome, result) = match self
+            .fetch_bytes(&["api", "elfs", contract, &program.program_id])
+            .await
+        {
+            Ok(Some(bytes)) => match self.check_elf(contract, program, &bytes).await {
+                Ok(true) => ("ok", Ok(Some(bytes))),
+                Ok(false) => ("unverified", Ok(Some(bytes))),
+                Err(err) => ("invalid", Err(err)),
+            },
+            Ok(None) => ("not_found", Ok(None)),
+            Err(err) => ("error", Err(err)),
+        };
+        self.record("download", outcome);
+        result
+    }
+
+    /// Fails if `elf` does not match the listing or the signed manifest, and
+    /// returns whether it could be checked against any digest.
+    async fn check_elf(&self, contract: &str, program: &ProgramInfo, elf: &[u8]) -> Result<bool> {
+        let program_id = &program.program_id;
+        let sha256 = hex::encode(Sha256::digest(elf));
+        if elf.len() as u64 != program.size_bytes {
+            bail!(
+                "upstream ELF of {contract}/{program_id} has {} bytes, {} listed",
+                elf.len(),
+                program.size_bytes
+            );
+        }
+        if program
+            .sha256
+            .as_ref()
+            .is_some_and(|listed| !listed.eq_ignore_ascii_case(&sha256))
+        {
+            bail!("upstream ELF of {contract}/{program_id} does not match its listed digest");
+        }
+        self.publishers
+            .verify(contract, program_id, elf, program.signature.as_ref())
+            .map_err(|err| match err {
+                SignatureError::Missing => {
+                    anyhow!("upstream program {contract}/{program_id} is not signed")
+                }
+                SignatureError::Invalid(err) => err.context(format!(
+                    "invalid signature of upstream program {contract}/{program_id}"
+                )),
+                SignatureError::UnknownPublisher(key) => anyhow!(
+                    "upstream program {contract}/{program_id} is signed by {key}, not a publisher of {contract}"
+                ),
+            })?;
+        let Some(key) = &self.manifest_key else {
+            if program.sha256.is_none() {
+                warn!("Upstream lists no digest for {contract}/{program_id}, mirrored unverified");
+            }
+            return Ok(program.sha256.is_some());
+        };
+        let manifest = self
+            .fetch_manifest(contract)
+            .await?
+            .ok_or_else(|| anyhow!("upstream has no manifest for {contract}"))?
+            .verify(contract, key)?;
+        let signed = manifest
+            .programs
+            .iter()
+            .find(|signed| signed.program_id == *program_id)
+            .ok_or_else(|| anyhow!("{contract}/{program_id} is not in the upstream manifest"))?;
+        if signed.size_bytes != program.size_bytes || !signed.sha256.eq_ignore_ascii_case(&sha256) {
+            bail!("upstream ELF of {contract}/{program_id} does not match the signed manifest");
+        }
+        Ok(true)
+    }
+
+    async fn fetch_manifest(&self, contract: &str) -> Result<Option<SignedManifest>> {
+        let response = self
+            .client
+            .get(self.url(&["api", "manifests", contract]))
+            .send()
+            .await
+            .context("querying upstream")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("upstream answered HTTP {}", response.status());
+        }
+        response
+            .json()
+            .await
+            .map(Some)
+            .context("parsing upstream manifest")
+    }
+
+    /// Downloads an artifact, checking it against the size and digest the upstream lists.
+    pub async fn artifact(
+        &self,
+        contract: &str,
+        program_id: &str,
+        name: &str,
+        artifact: &ArtifactEntry,
+    ) -> Result<Option<Bytes>> {
+        let (outcome, result) = match self
+            .fetch_bytes(&["api", "elfs", contract, program_id, "artifacts", name])
+            .await
+        {
+            Ok(Some(bytes))
+                if bytes.len() as u64 != artifact.size_bytes
+                    || hex::encode(Sha256::digest(&bytes)) != artifact.sha256 =>
+            {
+                (
+                    "invalid",
+                    Err(anyhow!(
+                        "upstream artifact {name} of {contract}/{program_id} does not match its listing"
+                    )),
+                )
+            }
+            Ok(bytes) => (if bytes.is_some() { "ok" } else { "not_found" }, Ok(bytes)),
+            Err(err) => ("error", Err(err)),
+        };
+        self.record("artifact", outcome);
+        result
+    }
+
+    async fn fetch_bytes(&self, segments: &[&str]) -> Result<Option<Bytes>> {
+        let response = self
+            .client
+            .get(self.url(segments))
+            .send()
+            .await
+            .context("querying upstream")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("upstream answered HTTP {}", response.status());
+        }
+        Ok(Some(
+            response
+                .bytes()
+                .await
+                .context("reading upstream response")?,
+        ))
+    }
+
+    /// Provenance document of a program the upstream lists with one.
+    pub async fn provenance(&self, contract: &str, program_id: &str) -> Result<Option<Provenance>> {
+        let result = self.fetch_provenance(contract, program_id).await;
+        self.record(
+            "provenance",
+            match &result {
+                Ok(Some(_)) => "ok",
+                Ok(None) => "not_found",
+                Err(_) => "error",
+            },
+        );
+        result
+    }
+
+    async fn fetch_provenance(
+        &self,
+        contract: &str,
+        program_id: &str,
+    ) -> Result<Option<Provenance>> {
+        let response = self
+            .client
+            .get(self.url(&["api", "elfs", contract, program_id, "provenance"]))
+            .send()
+            .await
+            .context("querying upstream")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            bail!("upstream answered HTTP {}", response.status());
+        }
+        let raw = response
+            .text()
+            .await
+            .context("reading upstream provenance")?;
+        Provenance::parse(&raw)
+            .map(Some)
+            .map_err(|err| anyhow!("upstream provenance of {contract}/{program_id}: {err}"))
+    }
+
+    fn record(&self, kind: &str, outcome: &str) {
+        self.fetches.with_label_values(&[kind, outcome]).inc();
+    }
+}
+
+/// Adds the upstream programs not stored locally to a local listing, keeping
+/// the most recent first.
+pub fn merge_listings(
+    local: Option<Vec<ProgramInfo>>,
+    upstream: Option<Vec<ProgramInfo>>,
+) -> Option<Vec<ProgramInfo>> {
+    let Some(upstream) = upstream else {
+        return local;
+    };
+    let mut programs = local.unwrap_or_default();
+    let missing = upstream
+        .into_iter()
+        .filter(|remote| {
+            !programs
+                .iter()
+                .any(|program| program.program_id == remote.program_id)
+        })
+        .collect::<Vec<_>>();
+    programs.extend(missing);
+    programs.sort_by_key(|program| {
+        let uploaded_at = DateTime::parse_from_rfc3339(&program.uploaded_at)
+            .map(|date| date.timestamp_micros())
+            .unwrap_or_default();
+        (Reverse(uploaded_at), program.program_id.clone())
+    });
+    Some(programs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::builder::RegistryBuilder;
+    use crate::registry::ProgramMetadata;
+    use crate::storage::LocalStorageBackend;
+
+    async fn build(root: &std::path::Path, config: Conf) -> crate::builder::RegistryApp {
+        RegistryBuilder::new(config)
+            .storage(Arc::new(LocalStorageBackend::new(root.to_path_buf())))
+            .register_metrics(false)
+            .build()
+            .await
+            .expect("build")
+    }
+
+    #[tokio::test]
+    async fn mirrors_missing_programs_from_upstream() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let origin = build(&dir.path().join("origin"), Conf::default()).await;
+        let metadata = ProgramMetadata {
+            toolchain: Some("1.0".to_string()),
+            commit: None,
+            zkvm: "sp1".to_string(),
+        };
+        let uploaded = origin
+            .registry()
+            .upload(
+                "orders",
+                "p1",
+                metadata.clone(),
+                Bytes::from_static(b"elf-1"),
+            )
+            .await
+            .expect("upload");
+        let server = origin.spawn().await.expect("spawn");
+
+        let mirror = build(
+            &dir.path().join("mirror"),
+            Conf {
+                upstream_url: Some(server.url()),
+                upstream_listing_refresh_secs: 300,
+                ..Default::default()
+            },
+        )
+        .await;
+        let registry = mirror.registry();
+        registry
+            .upload("orders", "local", metadata, Bytes::from_static(b"local"))
+            .await
+            .expect("local upload");
+
+        let listed = registry
+            .list_contract("orders")
+            .await
+            .expect("listing")
+            .into_iter()
+            .map(|program| program.program_id)
+            .collect::<Vec<_>>();
+        assert_eq!(listed, vec!["local", "p1"]);
+        assert!(registry.list_contract("wallet").await.is_none());
+
+        let elf = registry
+            .download("orders", "p1")
+            .await
+            .expect("download")
+            .expect("mirrored");
+        assert_eq!(&elf[..], b"elf-1");
+        drop(server);
+
+        // Served from the mirror's own storage once the upstream is gone.
+        let mirrored = registry
+            .index_snapshot()
+            .await
+            .contracts
+            .get("orders")
+            .and_then(|contract| contract.programs.get("p1").cloned())
+            .expect("stored");
+        assert_eq!(mirrored.uploaded_at, uploaded.uploaded_at);
+        assert_eq!(mirrored.metadata.toolchain.as_deref(), Some("1.0"));
+        assert!(registry
+            .download("orders", "missing")
+            .await
+            .expect("download")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn checks_mirrored_elfs_against_the_signed_manifest() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let key = secp256k1::SecretKey::from_slice(&[7; 32]).expect("key");
+        let origin = build(
+            &dir.path().join("origin"),
+            Conf {
+                manifest_signing_key: Some(hex::encode(key.secret_bytes())),
+                ..Default::default()
+            },
+        )
+        .await;
+        let metadata = ProgramMetadata {
+            toolchain: None,
+            commit: None,
+            zkvm: "sp1".to_string(),
+        };
+        for program_id in ["p1", "p2"] {
+            origin
+                .registry()
+                .upload(
+                    "orders",
+                    program_id,
+                    metadata.clone(),
+                    Bytes::from_static(b"elf"),
+                )
+                .await
+                .expect("upload");
+        }
+        let server = origin.spawn().await.expect("spawn");
+        let public_key = |key: &secp256k1::SecretKey| {
+            hex::encode(key.public_key(&secp256k1::Secp256k1::new()).serialize())
+        };
+
+        let mirror = build(
+            &dir.path().join("mirror"),
+            Conf {
+                upstream_url: Some(server.url()),
+                upstream_manifest_key: Some(public_key(&key)),
+                ..Default::default()
+            },
+        )
+        .await;
+        let elf = mirror
+            .registry()
+            .download("orders", "p1")
+            .await
+            .expect("download")
+            .expect("mirrored");
+        assert_eq!(&elf[..], b"elf");
+
+        // Pinned to a key the upstream does not sign with.
+        let other = secp256k1::SecretKey::from_slice(&[8; 32]).expect("key");
+        let mirror = build(
+            &dir.path().join("other-mirror"),
+            Conf {
+                upstream_url: Some(server.url()),
+                upstream_manifest_key: Some(public_key(&other)),
+                ..Default::default()
+            },
+        )
+        .await;
+        let err = mirror
+            .registry()
+            .download("orders", "p2")
+            .await
+            .expect_err("unverified manifest");
+        assert!(format!("{err:#}").contains("not signed by the expected key"));
+        assert!(!mirror
+            .registry()
+            .index_snapshot()
+            .await
+            .contracts
+            .contains_key("orders"));
+    }
+
+    #[tokio::test]
+    async fn mirrors_only_programs_signed_by_local_publishers() {
+        use secp256k1::{Message, Secp256k1, SecretKey};
+
+        use crate::registry::UploadAttachments;
+        use crate::signing::{upload_digest, UploadSignature};
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let secp = Secp256k1::new();
+        let publisher = SecretKey::from_slice(&[1; 32]).expect("key");
+        let stranger = SecretKey::from_slice(&[2; 32]).expect("key");
+        let public_key = |key: &SecretKey| hex::encode(key.public_key(&secp).serialize());
+        let origin = build(&dir.path().join("origin"), Conf::default()).await;
+        for (program_id, key) in [("p1", &publisher), ("p2", &stranger)] {
+            let message = Message::from_digest(upload_digest("orders", program_id, b"elf"));
+            origin
+                .registry()
+                .upload_with(
+                    "orders",
+                    program_id,
+                    ProgramMetadata {
+                        toolchain: None,
+                        commit: None,
+                        zkvm: "sp1".to_string(),
+                    },
+                    UploadAttachments {
+                        signature: Some(UploadSignature {
+                            public_key: public_key(key),
+                            signature: hex::encode(
+                                secp.sign_ecdsa(&message, key).serialize_compact(),
+                            ),
+                        }),
+                        ..Default::default()
+                    },
+                    Bytes::from_static(b"elf"),
+                )
+                .await
+                .expect("upload");
+        }
+        let server = origin.spawn().await.expect("spawn");
+
+        let mirror = build(
+            &dir.path().join("mirror"),
+            Conf {
+                upstream_url: Some(server.url()),
+                publishers: vec![crate::conf::PublisherConf {
+                    contract: "orders".to_string(),
+                    public_keys: vec![public_key(&publisher)],
+                }],
+                ..Default::default()
+            },
+        )
+        .await;
+        let registry = mirror.registry();
+        assert!(registry
+            .download("orders", "p1")
+            .await
+            .expect("download")
+            .is_some());
+        let err = registry
+            .download("orders", "p2")
+            .await
+            .expect_err("unknown publisher");
+        assert!(format!("{err:#}").contains("not a publisher of orders"));
+    }
+}
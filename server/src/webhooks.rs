@@ -0,0 +1,583 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::{broadcast, Mutex, Notify};
+use tracing::{info, warn};
+
+use crate::conf::{Conf, WebhookConf};
+use crate::events::{LoggedEvent, RegistryEvent};
+use crate::query::glob_match;
+use crate::registry::INTERNAL_PREFIX;
+use crate::storage::StorageBackend;
+
+pub const SIGNATURE_HEADER: &str = "x-hyli-registry-signature";
+pub const EVENT_HEADER: &str = "x-hyli-registry-event";
+pub const DELIVERY_HEADER: &str = "x-hyli-registry-delivery";
+
+/// Number of delivery attempts kept for the admin endpoint.
+const ATTEMPT_LOG_SIZE: usize = 200;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn queue_object_path() -> String {
+    format!("{INTERNAL_PREFIX}/webhooks/queue.json")
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_conf(config: &Conf) -> Self {
+        Self {
+            max_attempts: config.webhook_max_attempts.max(1),
+            initial_backoff: Duration::from_millis(config.webhook_retry_initial_ms),
+            max_backoff: Duration::from_millis(config.webhook_retry_max_ms),
+        }
+    }
+
+    /// Delay before retrying a delivery that failed `attempts` times.
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingDelivery {
+    id: u64,
+    hook: String,
+    event: RegistryEvent,
+    created_at: DateTime<Utc>,
+    attempts: u32,
+    next_attempt_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DeliveryQueue {
+    next_id: u64,
+    pending: Vec<PendingDelivery>,
+}
+
+/// Body POSTed to webhooks, signed in the `x-hyli-registry-signature` header.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub delivery_id: u64,
+    pub created_at: DateTime<Utc>,
+    pub event: RegistryEvent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryOutcome {
+    Delivered,
+    Retrying,
+    /// The last attempt failed; the delivery was dropped.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryAttempt {
+    pub delivery_id: u64,
+    pub hook: String,
+    pub event: &'static str,
+    pub contract: String,
+    pub attempt: u32,
+    pub attempted_at: DateTime<Utc>,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub outcome: DeliveryOutcome,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookStatus {
+    pub pending: usize,
+    /// Most recent attempts first.
+    pub attempts: Vec<DeliveryAttempt>,
+}
+
+/// Delivers registry events to the configured webhooks. Deliveries are queued
+/// in storage so they survive restarts, and retried with exponential backoff.
+pub struct WebhookDispatcher {
+    hooks: Vec<WebhookConf>,
+    retry: RetryPolicy,
+    storage: Arc<dyn StorageBackend>,
+    client: reqwest::Client,
+    queue: Mutex<DeliveryQueue>,
+    attempts: Mutex<VecDeque<DeliveryAttempt>>,
+    wake: Notify,
+}
+
+impl WebhookDispatcher {
+    /// Loads the persisted queue and starts delivering the events received on
+    /// `events`. Resumed deliveries are retried right away.
+    pub async fn start(
+        hooks: Vec<WebhookConf>,
+        retry: RetryPolicy,
+        storage: Arc<dyn StorageBackend>,
+        events: broadcast::Receiver<LoggedEvent>,
+    ) -> Result<Arc<Self>> {
+        validate_hooks(&hooks)?;
+
+        let mut queue: DeliveryQueue = match storage.read_object(&queue_object_path()).await? {
+            Some(bytes) => serde_json::from_slice(&bytes).context("parsing webhook queue")?,
+            None => DeliveryQueue::default(),
+        };
+        let now = Utc::now();
+        queue.pending.retain(|delivery| {
+            let configured = hooks.iter().any(|hook| hook.name == delivery.hook);
+            if !configured {
+                warn!(
+                    "Dropping webhook delivery {} for unconfigured hook {}",
+                    delivery.id, delivery.hook
+                );
+            }
+            configured
+        });
+        for delivery in &mut queue.pending {
+            delivery.next_attempt_at = now;
+        }
+        if !queue.pending.is_empty() {
+            info!(
+                "Resuming {} pending webhook deliveries",
+                queue.pending.len()
+            );
+        }
+
+        let dispatcher = Arc::new(Self {
+            hooks,
+            retry,
+            storage,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .context("building webhook http client")?,
+            queue: Mutex::new(queue),
+            attempts: Mutex::new(VecDeque::new()),
+            wake: Notify::new(),
+        });
+        tokio::spawn(dispatcher.clone().listen(events));
+        tokio::spawn(dispatcher.clone().deliver_loop());
+        Ok(dispatcher)
+    }
+
+    pub async fn status(&self) -> WebhookStatus {
+        WebhookStatus {
+            pending: self.queue.lock().await.pending.len(),
+            attempts: self.attempts.lock().await.iter().cloned().collect(),
+        }
+    }
+
+    async fn listen(self: Arc<Self>, mut events: broadcast::Receiver<LoggedEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(logged) => {
+                    if let Err(err) = self.enqueue(logged.event).await {
+                        warn!("Failed to queue webhook deliveries: {err:#}");
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Webhook dispatcher lagged, {skipped} registry events were not delivered"
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn enqueue(&self, event: RegistryEvent) -> Result<()> {
+        let hooks = self
+            .hooks
+            .iter()
+            .filter(|hook| {
+                hook.contracts.is_empty()
+                    || hook
+                        .contracts
+                        .iter()
+                        .any(|pattern| glob_match(pattern, event.contract()))
+            })
+            .collect::<Vec<_>>();
+        if hooks.is_empty() {
+            return Ok(());
+        }
+
+        {
+            let mut queue = self.queue.lock().await;
+            let now = Utc::now();
+            for hook in hooks {
+                queue.next_id += 1;
+                let id = queue.next_id;
+                queue.pending.push(PendingDelivery {
+                    id,
+                    hook: hook.name.clone(),
+                    event: event.clone(),
+                    created_at: now,
+                    attempts: 0,
+                    next_attempt_at: now,
+                });
+            }
+            self.persist(&queue).await?;
+        }
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    async fn deliver_loop(self: Arc<Self>) {
+        loop {
+            match self.deliver_due().await {
+                Some(next_attempt_at) => {
+                    let wait = (next_attempt_at - Utc::now()).to_std().unwrap_or_default();
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = self.wake.notified() => {}
+                    }
+                }
+                None => self.wake.notified().await,
+            }
+        }
+    }
+
+    /// Sends the deliveries that are due and returns when the next one is.
+    async fn deliver_due(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        let due = self
+            .queue
+            .lock()
+            .await
+            .pending
+            .iter()
+            .filter(|delivery| delivery.next_attempt_at <= now)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut attempts = Vec::with_capacity(due.len());
+        for delivery in &due {
+            attempts.push(self.attempt(delivery).await);
+        }
+
+        let mut queue = self.queue.lock().await;
+        if !attempts.is_empty() {
+            for attempt in &attempts {
+                match attempt.outcome {
+                    DeliveryOutcome::Delivered | DeliveryOutcome::Failed => {
+                        queue
+                            .pending
+                            .retain(|delivery| delivery.id != attempt.delivery_id);
+                    }
+                    DeliveryOutcome::Retrying => {
+                        if let Some(delivery) = queue
+                            .pending
+                            .iter_mut()
+                            .find(|delivery| delivery.id == attempt.delivery_id)
+                        {
+                            delivery.attempts = attempt.attempt;
+                            delivery.next_attempt_at =
+                                attempt.attempted_at + self.retry.backoff(attempt.attempt);
+                        }
+                    }
+                }
+            }
+            if let Err(err) = self.persist(&queue).await {
+                warn!("Failed to persist webhook queue: {err:#}");
+            }
+
+            let mut log = self.attempts.lock().await;
+            for attempt in attempts {
+                log.push_front(attempt);
+            }
+            log.truncate(ATTEMPT_LOG_SIZE);
+        }
+        queue
+            .pending
+            .iter()
+            .map(|delivery| delivery.next_attempt_at)
+            .min()
+    }
+
+    async fn attempt(&self, delivery: &PendingDelivery) -> DeliveryAttempt {
+        let attempt = delivery.attempts + 1;
+        let result = self.send(delivery).await;
+        let (status, error) = match &result {
+            Ok(status) if (200..300).contains(status) => (Some(*status), None),
+            Ok(status) => (Some(*status), Some(format!("HTTP {status}"))),
+            Err(err) => (None, Some(format!("{err:#}"))),
+        };
+        let outcome = match &error {
+            None => DeliveryOutcome::Delivered,
+            Some(_) if attempt >= self.retry.max_attempts => DeliveryOutcome::Failed,
+            Some(_) => DeliveryOutcome::Retrying,
+        };
+        if let Some(error) = &error {
+            warn!(
+                "Webhook {} delivery {} attempt {attempt} failed: {error}",
+                delivery.hook, delivery.id
+            );
+        }
+
+        DeliveryAttempt {
+            delivery_id: delivery.id,
+            hook: delivery.hook.clone(),
+            event: delivery.event.kind(),
+            contract: delivery.event.contract().to_string(),
+            attempt,
+            attempted_at: Utc::now(),
+            status,
+            error,
+            outcome,
+        }
+    }
+
+    /// Posts the signed payload and returns the HTTP status of the response.
+    async fn send(&self, delivery: &PendingDelivery) -> Result<u16> {
+        let Some(hook) = self.hooks.iter().find(|hook| hook.name == delivery.hook) else {
+            bail!("hook {} is not configured", delivery.hook);
+        };
+        let body = serde_json::to_vec(&WebhookPayload {
+            delivery_id: delivery.id,
+            created_at: delivery.created_at,
+            event: delivery.event.clone(),
+        })
+        .context("serializing webhook payload")?;
+
+        let response = self
+            .client
+            .post(&hook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.kind())
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(SIGNATURE_HEADER, sign_payload(&hook.secret, &body))
+            .body(body)
+            .send()
+            .await
+            .context("sending webhook")?;
+        Ok(response.status().as_u16())
+    }
+
+    async fn persist(&self, queue: &DeliveryQueue) -> Result<()> {
+        let bytes = serde_json::to_vec(queue).context("serializing webhook queue")?;
+        self.storage
+            .write_object(&queue_object_path(), &bytes)
+            .await
+            .context("writing webhook queue")
+    }
+}
+
+/// Signature of a webhook body, formatted as `sha256=<hex hmac>`.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    #[allow(clippy::expect_used, reason = "HMAC accepts keys of any size")]
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn validate_hooks(hooks: &[WebhookConf]) -> Result<()> {
+    for (position, hook) in hooks.iter().enumerate() {
+        if hook.name.trim().is_empty() {
+            bail!("webhook #{position} must have a name");
+        }
+        if hooks[..position]
+            .iter()
+            .any(|other| other.name == hook.name)
+        {
+            bail!("webhook name {} is used more than once", hook.name);
+        }
+        reqwest::Url::parse(&hook.url)
+            .with_context(|| format!("invalid url for webhook {}", hook.name))?;
+        if hook.secret.is_empty() {
+            bail!("webhook {} must have a secret", hook.name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventLog;
+    use crate::storage::LocalStorageBackend;
+    use axum::{extract::State, http::HeaderMap, routing::post, Router};
+    use bytes::Bytes;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type Received = Arc<std::sync::Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    /// Local HTTP endpoint recording webhook requests and answering 500 to
+    /// the first `failures` of them.
+    async fn spawn_receiver(failures: usize) -> (String, Received) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let received = Received::default();
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State((received, failures)): State<(Received, Arc<AtomicUsize>)>,
+                     headers: HeaderMap,
+                     body: Bytes| async move {
+                        received.lock().expect("lock").push((headers, body));
+                        let fail = failures
+                            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                                left.checked_sub(1)
+                            })
+                            .is_ok();
+                        if fail {
+                            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+                        } else {
+                            axum::http::StatusCode::OK
+                        }
+                    },
+                ),
+            )
+            .with_state((received.clone(), Arc::new(AtomicUsize::new(failures))));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}/hook"), received)
+    }
+
+    fn hook(name: &str, url: &str, contracts: &[&str]) -> WebhookConf {
+        WebhookConf {
+            name: name.to_string(),
+            url: url.to_string(),
+            secret: format!("{name}-secret"),
+            contracts: contracts.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    fn retry(initial_backoff: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff,
+            max_backoff: Duration::from_secs(3600),
+        }
+    }
+
+    async fn wait_for(
+        dispatcher: &WebhookDispatcher,
+        done: impl Fn(&WebhookStatus) -> bool,
+    ) -> WebhookStatus {
+        for _ in 0..250 {
+            let status = dispatcher.status().await;
+            if done(&status) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("timed out waiting for webhook deliveries");
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payloads_and_retries_failures() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let storage = Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf()));
+        let (url, received) = spawn_receiver(1).await;
+        let events = EventLog::new(16);
+        let dispatcher = WebhookDispatcher::start(
+            vec![hook("all", &url, &[]), hook("wallets", &url, &["wallet-*"])],
+            retry(Duration::from_millis(10)),
+            storage.clone(),
+            events.subscribe(),
+        )
+        .await
+        .expect("start");
+
+        events.publish(RegistryEvent::ProgramDeleted {
+            contract: "orders".to_string(),
+            program_id: "p1".to_string(),
+        });
+
+        let status = wait_for(&dispatcher, |status| {
+            status
+                .attempts
+                .iter()
+                .any(|attempt| attempt.outcome == DeliveryOutcome::Delivered)
+        })
+        .await;
+        assert_eq!(status.pending, 0);
+        let outcomes = status
+            .attempts
+            .iter()
+            .map(|attempt| (attempt.hook.as_str(), attempt.attempt, attempt.outcome))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("all", 2, DeliveryOutcome::Delivered),
+                ("all", 1, DeliveryOutcome::Retrying)
+            ]
+        );
+
+        let received = received.lock().expect("lock").clone();
+        assert_eq!(received.len(), 2);
+        let (headers, body) = &received[1];
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().expect("header"),
+            sign_payload("all-secret", body)
+        );
+        assert_eq!(headers[EVENT_HEADER], "program_deleted");
+        let payload: WebhookPayload = serde_json::from_slice(body).expect("payload");
+        assert_eq!(payload.event.contract(), "orders");
+
+        let queue = storage
+            .read_object(&queue_object_path())
+            .await
+            .expect("read")
+            .expect("queue");
+        let queue: DeliveryQueue = serde_json::from_slice(&queue).expect("parse");
+        assert!(queue.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pending_deliveries_survive_restart() {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let storage = Arc::new(LocalStorageBackend::new(temp_dir.path().to_path_buf()));
+
+        // Nothing listens on this port once the listener is dropped.
+        let unreachable = {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("bind");
+            format!("http://{}/hook", listener.local_addr().expect("addr"))
+        };
+        let events = EventLog::new(16);
+        let first = WebhookDispatcher::start(
+            vec![hook("all", &unreachable, &[])],
+            retry(Duration::from_secs(3600)),
+            storage.clone(),
+            events.subscribe(),
+        )
+        .await
+        .expect("start");
+        events.publish(RegistryEvent::ContractDeleted {
+            contract: "orders".to_string(),
+        });
+        wait_for(&first, |status| !status.attempts.is_empty()).await;
+
+        let (url, received) = spawn_receiver(0).await;
+        let second = WebhookDispatcher::start(
+            vec![hook("all", &url, &[])],
+            retry(Duration::from_secs(3600)),
+            storage,
+            EventLog::new(16).subscribe(),
+        )
+        .await
+        .expect("restart");
+        let status = wait_for(&second, |status| status.pending == 0).await;
+
+        assert_eq!(status.attempts[0].attempt, 2);
+        assert_eq!(status.attempts[0].outcome, DeliveryOutcome::Delivered);
+        assert_eq!(received.lock().expect("lock").len(), 1);
+    }
+}